@@ -0,0 +1,132 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use cache_simulator::access::{Access, AccessType};
+use cache_simulator::cache::cache::Cache;
+use cache_simulator::cache::victim::VictimCache;
+use cache_simulator::simulator::{Inclusion, Simulator};
+
+/// Number of accesses in the synthetic trace.
+const TRACE_LENGTH: usize = 100_000;
+
+/// Build a fixed trace mixing a hot working set, a streaming sweep, and scattered accesses,
+/// with about one write in four. A simple LCG keeps it the same on every run.
+fn synthetic_trace() -> Vec<Access> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as usize
+    };
+    (0..TRACE_LENGTH)
+        .map(|i| {
+            let random = next();
+            let address = match random % 4 {
+                0 | 1 => 0x1000_0000 + (random >> 2) % 0x4000,
+                2 => 0x2000_0000 + i * 8,
+                _ => (random >> 2) % 0x1000_0000,
+            };
+            let rw = if random % 16 < 4 { AccessType::Write } else { AccessType::Read };
+            Access { rw, address, size: 1, timestamp: None }
+        })
+        .collect()
+}
+
+fn new_simulator() -> Simulator {
+    let l1 = Cache::new(32 * 1024, 8, 64).unwrap();
+    let l2 = Cache::new(512 * 1024, 16, 64).unwrap();
+    Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Nine)
+}
+
+fn bench_simulation(c: &mut Criterion) {
+    let trace = synthetic_trace();
+    let mut group = c.benchmark_group("simulation");
+    // report accesses per second
+    group.throughput(Throughput::Elements(trace.len() as u64));
+    group.bench_function("l1_l2", |b| {
+        b.iter(|| {
+            let mut simulator = new_simulator();
+            for access in &trace {
+                simulator.access(access.rw, access.address);
+            }
+            black_box(simulator.stats.total_memory_traffic)
+        })
+    });
+    group.finish();
+}
+
+/// Run a trace through a bare cache so its sets are full of valid blocks.
+fn warm_up(cache: &mut Cache, trace: &[Access]) {
+    for &Access { address, .. } in trace {
+        let (index, tag) = (cache.index_of(address), cache.tag_of(address));
+        if !cache.contains(index, tag) {
+            if cache.set_is_full(index, tag) {
+                cache.evict_lru_block(index, tag);
+            }
+            cache.install(index, tag, address);
+        }
+    }
+}
+
+/// Get the set, way and tag of every valid block in a cache.
+fn resident_blocks(cache: &Cache) -> Vec<(usize, usize, usize)> {
+    cache.cache
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| block.valid)
+        .map(|(i, block)| (i / cache.assoc, i % cache.assoc, block.tag))
+        .collect()
+}
+
+fn bench_hot_paths(c: &mut Criterion) {
+    let trace = synthetic_trace();
+    let mut cache = Cache::new(32 * 1024, 8, 64).unwrap();
+    warm_up(&mut cache, &trace);
+
+    let mut group = c.benchmark_group("hot_paths");
+    group.throughput(Throughput::Elements(trace.len() as u64));
+    group.bench_function("index_of_tag_of", |b| {
+        b.iter(|| {
+            trace
+                .iter()
+                .map(|&Access { address, .. }| cache.index_of(black_box(address)) ^ cache.tag_of(black_box(address)))
+                .fold(0, |acc, bits| acc ^ bits)
+        })
+    });
+    let resident = resident_blocks(&cache);
+    group.throughput(Throughput::Elements(resident.len() as u64));
+    group.bench_function("update_lru", |b| {
+        b.iter(|| {
+            for &(index, _, tag) in &resident {
+                cache.update_lru(black_box(index), black_box(tag));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_high_assoc_lru(c: &mut Criterion) {
+    // 64 ways, where searching the set for the tag costs as much as re-ranking it
+    let trace = synthetic_trace();
+    let mut cache = Cache::new(32 * 1024, 64, 64).unwrap();
+    warm_up(&mut cache, &trace);
+    let resident = resident_blocks(&cache);
+
+    let mut group = c.benchmark_group("high_assoc_lru");
+    group.throughput(Throughput::Elements(resident.len() as u64));
+    group.bench_function("update_lru", |b| {
+        b.iter(|| {
+            for &(index, _, tag) in &resident {
+                cache.update_lru(black_box(index), black_box(tag));
+            }
+        })
+    });
+    group.bench_function("update_lru_way", |b| {
+        b.iter(|| {
+            for &(index, way, _) in &resident {
+                cache.update_lru_way(black_box(index), black_box(way));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_simulation, bench_hot_paths, bench_high_assoc_lru);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use std::fmt;
+
+/// A decoded trace record.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Access {
+    pub rw: AccessType,
+    pub address: usize,
+    /// The number of bytes accessed, 1 if the record doesn't give a size.
+    pub size: usize,
+    /// The cycle the access was made in, if the trace records one.
+    pub timestamp: Option<u64>,
+}
+
+impl Access {
+    /// Get the address of every lookup the access makes in a cache with the given block
+    /// size: its own address, then the start of each further block it spans.
+    ///
+    /// ## Arguments
+    /// * `block_size` - The block size of the cache being looked up.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::{Access, AccessType};
+    ///
+    /// let access = Access { rw: AccessType::Read, address: 0x11c, size: 8, timestamp: None };
+    /// assert_eq!(access.lookups(32).collect::<Vec<_>>(), [0x11c, 0x120]);
+    /// assert_eq!(access.lookups(64).collect::<Vec<_>>(), [0x11c]);
+    /// ```
+    pub fn lookups(&self, block_size: usize) -> impl Iterator<Item = usize> {
+        let last = self.address.saturating_add(self.size.max(1) - 1);
+        let blocks = self.address / block_size + 1..=last / block_size;
+        std::iter::once(self.address).chain(blocks.map(move |block| block * block_size))
+    }
+}
+
+impl fmt::Display for Access {
+    /// Format the access as a record of the legacy trace format, `<rw> <hex address>`, with
+    /// the size after the address unless it's 1, and then the timestamp if there is one.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:x}", self.rw.to_char(), self.address)?;
+        if self.size != 1 {
+            write!(f, " {}", self.size)?;
+        }
+        if let Some(timestamp) = self.timestamp {
+            write!(f, " @{}", timestamp)?;
+        }
+        Ok(())
+    }
+}
+
+/// The kind of memory access a trace record makes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccessType {
+    Read,
+    Write,
+    /// An instruction fetch, which behaves like a read but is counted separately.
+    InstructionFetch,
+}
+
+impl AccessType {
+    /// Parse the action letter of a legacy trace record, `r`, `w`, or `i` in either case.
+    /// The DineroIV numeric labels are only read under `TraceFormat::Dinero`, so a legacy
+    /// record never starts with a number.
+    ///
+    /// ## Arguments
+    /// * `action` - The action character from the trace.
+    ///
+    /// Returns the access type, or `None` if the character isn't a known action.
+    pub fn from_char(action: char) -> Option<Self> {
+        match action.to_ascii_lowercase() {
+            'r' => Some(AccessType::Read),
+            'w' => Some(AccessType::Write),
+            'i' => Some(AccessType::InstructionFetch),
+            _ => None,
+        }
+    }
+
+    /// Get the letter the legacy trace format uses for the access type.
+    pub fn to_char(self) -> char {
+        match self {
+            AccessType::Read => 'r',
+            AccessType::Write => 'w',
+            AccessType::InstructionFetch => 'i',
+        }
+    }
+}
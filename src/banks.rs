@@ -0,0 +1,51 @@
+use std::collections::VecDeque;
+
+/// Divides a cache into banks by the low bits of the index and counts bank conflicts: an
+/// access to a bank that one of the last few accesses also went to. It only watches the
+/// indices, so hits and misses are the same with or without it.
+pub struct BankConflicts {
+    pub banks: usize,
+    /// How many of the previous accesses an access can conflict with.
+    pub window: usize,
+    /// The banks of the most recent accesses, oldest first.
+    pub recent: VecDeque<usize>,
+}
+
+impl BankConflicts {
+    /// Creates a new bank model that hasn't seen any accesses.
+    ///
+    /// ## Arguments
+    /// * `banks` - The number of banks, at least 1.
+    /// * `window` - How many of the previous accesses an access can conflict with.
+    pub fn new(banks: usize, window: usize) -> Self {
+        Self {
+            banks,
+            window,
+            recent: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Get the bank a set is in.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) being accessed.
+    pub fn bank_of(&self, index: usize) -> usize {
+        index % self.banks
+    }
+
+    /// Record an access to a set.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) being accessed.
+    ///
+    /// Returns whether the access conflicts with one of the previous `window` accesses.
+    pub fn observe(&mut self, index: usize) -> bool {
+        let bank = self.bank_of(index);
+        let conflict = self.recent.contains(&bank);
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(bank);
+        conflict
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::{self, BufWriter, Write};
+use clap::Parser;
+use cache_simulator::trace_gen::{TraceGenerator, TraceSpec};
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+    let spec = TraceSpec {
+        accesses: args.accesses,
+        working_set: args.working_set,
+        base_address: args.base_address,
+        write_fraction: args.write_fraction,
+        sequential_fraction: args.sequential_fraction,
+        stride: args.stride,
+        seed: args.seed,
+    };
+
+    // write the trace in the simulator's native "<rw> <hex address>" format
+    let mut out = BufWriter::new(io::stdout().lock());
+    for access in TraceGenerator::new(spec) {
+        writeln!(out, "{}", access)?;
+    }
+    out.flush()
+}
+
+/// Command line arguments for the trace generator.
+#[derive(Parser)]
+#[command(about = "Generate a synthetic memory trace for the cache simulator on stdout")]
+struct Args {
+    /// Number of accesses to generate.
+    #[arg(long, default_value_t = TraceSpec::default().accesses)]
+    accesses: usize,
+    /// Size in bytes of the region every address falls in.
+    #[arg(long, default_value_t = TraceSpec::default().working_set)]
+    working_set: usize,
+    /// First address of the working set.
+    #[arg(long, default_value_t = TraceSpec::default().base_address)]
+    base_address: usize,
+    /// Fraction of accesses that are writes, from 0 to 1.
+    #[arg(long, default_value_t = TraceSpec::default().write_fraction)]
+    write_fraction: f64,
+    /// Fraction of accesses that continue a sequential stream instead of going to a random
+    /// address in the working set, from 0 to 1.
+    #[arg(long, default_value_t = TraceSpec::default().sequential_fraction)]
+    sequential_fraction: f64,
+    /// Bytes between consecutive accesses of the sequential stream.
+    #[arg(long, default_value_t = TraceSpec::default().stride)]
+    stride: usize,
+    /// Seed for the random number generator, so runs can be reproduced.
+    #[arg(long, default_value_t = TraceSpec::default().seed)]
+    seed: u64,
+}
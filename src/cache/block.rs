@@ -1,10 +1,25 @@
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Block {
     pub address: usize,
     pub tag: usize,
+    /// Recency of the block in a victim cache. A cache keeps its recency order apart from
+    /// the blocks and only copies it here in snapshots.
     pub lru: usize,
+    /// Re-reference prediction value, only used by the RRIP replacement policy.
+    pub rrpv: usize,
+    /// Hits since the block was installed (halved by aging), only used by LFU replacement.
+    #[serde(default)]
+    pub frequency: usize,
     pub valid: bool,
     pub dirty: bool,
+    /// Whether the block was brought in by a prefetch and hasn't been demanded since.
+    pub prefetched: bool,
+    /// Valid bit of each sector in a sectored cache, sector `s` at bit `s`.
+    pub sector_valid: u64,
+    /// Dirty bit of each sector in a sectored cache, sector `s` at bit `s`.
+    pub sector_dirty: u64,
 }
 
 impl Block {
@@ -13,8 +28,19 @@ impl Block {
             address: 0,
             tag: 0,
             lru: 0,
+            rrpv: 0,
+            frequency: 0,
             valid: false,
-            dirty: false
+            dirty: false,
+            prefetched: false,
+            sector_valid: 0,
+            sector_dirty: 0,
         }
     }
 }
+
+impl Default for Block {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,18 +1,114 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
 use fast_math::log2;
+use serde::{Deserialize, Serialize};
+use crate::rng::SplitMix64;
 use crate::cache::block::Block;
+use crate::cache::error::CacheConfigError;
+use crate::cache::replacement::{Clock, InsertionPosition, Lru, Replacement, ReplacementPolicy};
 
 pub struct Cache {
     pub cache_size: usize,
     pub assoc: usize,
     pub block_size: usize,
+    /// Size of the independently valid parts of a block, equal to `block_size` unless the
+    /// cache is sectored.
+    pub sector_size: usize,
     pub sets: usize,
     pub index_bits: usize,
     pub block_offset_bits: usize,
     pub tag_bits: usize,
-    pub cache: Vec<Vec<Block>>,
+    /// Every block in the cache, stored set by set. Way `w` of set `s` is at `s * assoc + w`.
+    pub cache: Vec<Block>,
+    pub set_stats: Vec<SetStats>,
+    pub replacement: ReplacementPolicy,
+    /// The recency order of every set, which LRU picks its victims from and the other
+    /// policies fall back on.
+    pub recency: Box<dyn Replacement>,
+    /// Number of bits in each block's re-reference prediction value under RRIP.
+    pub rrip_bits: usize,
+    /// DIP policy selector. Misses in LRU sampler sets count it up and misses in BIP sampler
+    /// sets count it down; follower sets use BIP while it is above `DIP_PSEL_MAX / 2`.
+    pub psel: usize,
+    /// Number of bimodal insertions so far, used to throttle BIP's occasional MRU insertion.
+    pub bip_insertions: usize,
+    /// Tree pseudo-LRU bits, stored set by set with `assoc - 1` nodes per set in heap order.
+    /// A node that is `true` points the next victim at its right subtree.
+    pub plru_tree: Vec<bool>,
+    /// Where the next blocks installed start in the replacement order.
+    pub insertion: InsertionPosition,
+    /// The fraction of blocks bimodal insertion puts at the MRU position instead of LRU.
+    pub bimodal_probability: f64,
+    /// The ways the next blocks may be installed in and evicted from, way `w` at bit `w`.
+    /// Every way unless a partitioned cache narrows it to one client's ways for an access.
+    /// Hits aren't restricted, so a client can hit on a block in any way.
+    pub allocation_ways: u64,
+    /// Whether each way indexes with its own hash of the address, see `skewed_index`.
+    pub skewed: bool,
+    /// Number of LRU updates so far in a skewed cache, which uses it to timestamp blocks.
+    pub lru_clock: usize,
+    /// Where random replacement draws its victims from, and bimodal insertion its MRU picks.
+    pub rng: SplitMix64,
+    /// The seed `rng` started from, which `reset` rewinds it to.
+    pub seed: u64,
+    /// Number of accesses between halvings of every LFU counter, 0 for no aging.
+    pub lfu_aging_interval: usize,
+    /// Number of accesses since the LFU counters were last halved.
+    pub lfu_accesses: usize,
+    /// Number of page offset bits when pages are colored.
+    pub page_offset_bits: usize,
+    /// Number of low page number bits recolored before indexing, 0 for no page coloring.
+    pub page_color_bits: usize,
+}
+
+/// Width of the addresses in a trace, which the offset, index, and tag bits split between them.
+pub const ADDRESS_BITS: usize = 32;
+/// Largest value of the saturating 10-bit DIP policy selector.
+pub const DIP_PSEL_MAX: usize = (1 << 10) - 1;
+/// Number of sampler sets dedicated to each DIP insertion policy in large caches.
+const DIP_SAMPLER_SETS: usize = 32;
+/// BIP inserts at the MRU position once in this many fills.
+const BIP_THROTTLE: usize = 32;
+/// The fraction of blocks bimodal insertion puts at the MRU position unless configured, the
+/// same as BIP's.
+pub const DEFAULT_BIMODAL_PROBABILITY: f64 = 1.0 / BIP_THROTTLE as f64;
+/// The way mask that lets a block go in any way.
+pub const ALL_WAYS: u64 = u64::MAX;
+
+/// A copy of everything a cache holds, including its replacement state, taken with
+/// `Cache::snapshot` and loaded back with `Cache::restore`. Per-set statistics aren't part
+/// of the state and aren't captured. The geometry is recorded so a snapshot can't be loaded
+/// into a different cache.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CacheSnapshot {
+    pub cache_size: usize,
+    pub assoc: usize,
+    pub block_size: usize,
+    pub blocks: Vec<Block>,
+    pub psel: usize,
+    pub bip_insertions: usize,
+    pub plru_tree: Vec<bool>,
+    pub lru_clock: usize,
+    #[serde(default = "unseeded")]
+    pub rng: SplitMix64,
+    #[serde(default)]
+    pub lfu_accesses: usize,
+}
+
+/// The generator of a cache whose seed was never set, for snapshots saved without one.
+fn unseeded() -> SplitMix64 {
+    SplitMix64::new(0)
+}
+
+/// The part a set plays in DIP's set dueling.
+enum DipRole {
+    LruSampler,
+    BipSampler,
+    Follower,
 }
 
 #[derive(PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum HitOrMiss {
     HIT,
     MISS,
@@ -21,97 +117,849 @@ pub enum HitOrMiss {
 pub struct EvictionResult {
     pub evicted_block_address: usize,
     pub evicted_block_was_dirty: bool,
+    /// Whether the block was prefetched and left without ever being demanded.
+    pub evicted_block_was_prefetched: bool,
+    /// Number of dirty sectors in the block, 1 for a dirty block in an unsectored cache.
+    pub evicted_dirty_sectors: usize,
+}
+
+/// What happened on an access with `Cache::access`: whether it hit, and on a miss, the
+/// block evicted to make room for the new one, if the set was full.
+pub struct AccessOutcome {
+    pub hit_or_miss: HitOrMiss,
+    pub evicted: Option<EvictionResult>,
+}
+
+/// Access counts for a single set, used to find sets that are hit unusually hard.
+#[derive(Clone, Default)]
+pub struct SetStats {
+    pub accesses: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    /// The distinct tags evicted from the set, how many different blocks took turns in it.
+    pub evicted_tags: HashSet<usize>,
 }
 
 impl Cache {
     /// Creates a new cache with the given constraints.
+    /// A `cache_size` of 0 creates an empty cache that never holds a block.
     ///
     /// ## Arguments
     /// * `cache_size` - The total size of the cache.
     /// * `assoc` - The associativity of the cache.
     /// * `block_size` - The size of the blocks in the cache.
     ///
+    /// Returns an error if the sizes don't describe a valid power-of-two geometry: the block
+    /// size, the associativity, and the resulting number of sets must each be a power of two,
+    /// and the error names the first one that isn't. The offset and index bits also have to
+    /// leave at least one tag bit of the `ADDRESS_BITS`-bit address.
+    ///
     /// ## Example
     /// ```rust
-    /// let mut l1 = Cache::new(1024, 2, 32);
+    /// # use cache_simulator::cache::cache::Cache;
+    /// # use cache_simulator::cache::error::CacheConfigError;
+    /// let mut l1 = Cache::new(1024, 2, 32)?;
+    /// assert_eq!(Cache::new(1536, 2, 48).err(), Some(CacheConfigError::BlockSizeNotPowerOfTwo(48)));
+    /// assert_eq!(Cache::new(3072, 2, 32).err(), Some(CacheConfigError::SetsNotPowerOfTwo(48)));
+    /// assert_eq!(Cache::new(3072, 3, 32).err(), Some(CacheConfigError::AssocNotPowerOfTwo(3)));
+    /// assert!(matches!(Cache::new(16, 1, 32), Err(CacheConfigError::AssocTooLarge { blocks: 0, .. })));
+    /// assert!(matches!(Cache::new(1 << 32, 1, 32), Err(CacheConfigError::NoTagBits { index_bits: 27, .. })));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
     /// ```
     pub fn new(
         cache_size: usize,
         assoc: usize,
         block_size: usize,
-    ) -> Self {
+    ) -> Result<Self, CacheConfigError> {
+        Self::with_address_bits(cache_size, assoc, block_size, None, None)
+    }
+
+    /// Creates a new cache like `Cache::new`, but with the split of the address into offset
+    /// and index bits given instead of derived from the sizes. Each given width is checked
+    /// against the geometry: `2^index_bits` must be the number of sets and `2^offset_bits`
+    /// the block size.
+    ///
+    /// ## Arguments
+    /// * `cache_size` - The total size of the cache.
+    /// * `assoc` - The associativity of the cache.
+    /// * `block_size` - The size of the blocks in the cache.
+    /// * `index_bits` - The number of index bits, if it isn't to be derived.
+    /// * `offset_bits` - The number of block offset bits, if it isn't to be derived.
+    ///
+    /// Returns an error if the geometry is invalid, as for `Cache::new`, or a given width
+    /// doesn't match it.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::Cache;
+    /// # use cache_simulator::cache::error::CacheConfigError;
+    /// let cache = Cache::with_address_bits(1024, 2, 32, Some(4), Some(5))?;
+    /// assert_eq!((cache.index_of(0x1234), cache.tag_of(0x1234)), (0x1, 0x9));
+    /// assert_eq!(
+    ///     Cache::with_address_bits(1024, 2, 32, Some(5), None).err(),
+    ///     Some(CacheConfigError::IndexBitsMismatch { index_bits: 5, sets: 16 })
+    /// );
+    /// assert_eq!(
+    ///     Cache::with_address_bits(1024, 2, 32, None, Some(4)).err(),
+    ///     Some(CacheConfigError::OffsetBitsMismatch { offset_bits: 4, block_size: 32 })
+    /// );
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn with_address_bits(
+        cache_size: usize,
+        assoc: usize,
+        block_size: usize,
+        index_bits: Option<usize>,
+        offset_bits: Option<usize>,
+    ) -> Result<Self, CacheConfigError> {
         if cache_size == 0 {
-            return Self {
+            return Ok(Self {
                 cache_size: 0,
                 assoc: 0,
                 block_size: 0,
+                sector_size: 0,
                 sets: 0,
                 index_bits: 0,
                 block_offset_bits: 0,
                 tag_bits: 0,
-                cache: vec![]
-            }
+                cache: vec![],
+                set_stats: vec![],
+                replacement: ReplacementPolicy::Lru,
+                recency: Box::new(Lru::new(0, 0)),
+                rrip_bits: 2,
+                psel: DIP_PSEL_MAX / 2,
+                bip_insertions: 0,
+                plru_tree: vec![],
+                insertion: InsertionPosition::Mru,
+                bimodal_probability: DEFAULT_BIMODAL_PROBABILITY,
+                allocation_ways: ALL_WAYS,
+                skewed: false,
+                lru_clock: 0,
+                rng: SplitMix64::new(0),
+                seed: 0,
+                lfu_aging_interval: 0,
+                lfu_accesses: 0,
+                page_offset_bits: 0,
+                page_color_bits: 0,
+            });
+        }
+        if block_size == 0 {
+            return Err(CacheConfigError::ZeroBlockSize);
+        }
+        if assoc == 0 {
+            return Err(CacheConfigError::ZeroAssoc);
+        }
+        if !block_size.is_power_of_two() {
+            return Err(CacheConfigError::BlockSizeNotPowerOfTwo(block_size));
+        }
+        if !assoc.is_power_of_two() {
+            return Err(CacheConfigError::AssocNotPowerOfTwo(assoc));
+        }
+        if assoc > cache_size / block_size {
+            return Err(CacheConfigError::AssocTooLarge { assoc, block_size, blocks: cache_size / block_size });
+        }
+        if !cache_size.is_multiple_of(assoc * block_size) {
+            return Err(CacheConfigError::SizeNotMultiple { cache_size, assoc, block_size });
         }
         let sets = cache_size / (assoc * block_size);
-        let index_bits = log2(sets as f32) as usize;
-        let block_offset_bits = log2(block_size as f32) as usize;
-        let tag_bits = 32 - index_bits - block_offset_bits;
-
-        // resize the cache
-        let mut cache: Vec<Vec<Block>> = vec![];
-        cache.resize(sets, Vec::new());
-        for i in 0..sets {
-            cache[i].resize(assoc, Block::new());
-            // set the lru values to all be different
-            for j in 0..assoc {
-                cache[i][j].lru = j;
-            }
+        if !sets.is_power_of_two() {
+            return Err(CacheConfigError::SetsNotPowerOfTwo(sets));
         }
+        let index_bits = match index_bits {
+            Some(index_bits) if index_bits >= usize::BITS as usize || 1 << index_bits != sets => {
+                return Err(CacheConfigError::IndexBitsMismatch { index_bits, sets });
+            }
+            Some(index_bits) => index_bits,
+            None => log2(sets as f32) as usize,
+        };
+        let block_offset_bits = match offset_bits {
+            Some(offset_bits) if offset_bits >= usize::BITS as usize || 1 << offset_bits != block_size => {
+                return Err(CacheConfigError::OffsetBitsMismatch { offset_bits, block_size });
+            }
+            Some(offset_bits) => offset_bits,
+            None => log2(block_size as f32) as usize,
+        };
+        let tag_bits = ADDRESS_BITS
+            .checked_sub(index_bits + block_offset_bits)
+            .filter(|&tag_bits| tag_bits > 0)
+            .ok_or(CacheConfigError::NoTagBits { index_bits, block_offset_bits, address_bits: ADDRESS_BITS })?;
 
-        Self {
+        // allocate every set at once
+        let cache: Vec<Block> = vec![Block::new(); sets * assoc];
+
+        Ok(Self {
             cache_size,
             assoc,
             block_size,
+            sector_size: block_size,
             sets,
             index_bits,
             block_offset_bits,
             tag_bits,
             cache,
+            set_stats: vec![],
+            replacement: ReplacementPolicy::Lru,
+            recency: Box::new(Lru::new(sets, assoc)),
+            rrip_bits: 2,
+            psel: DIP_PSEL_MAX / 2,
+            bip_insertions: 0,
+            plru_tree: vec![false; sets * (assoc - 1)],
+            insertion: InsertionPosition::Mru,
+            bimodal_probability: DEFAULT_BIMODAL_PROBABILITY,
+            allocation_ways: ALL_WAYS,
+            skewed: false,
+            lru_clock: 0,
+            rng: SplitMix64::new(0),
+            seed: 0,
+            lfu_aging_interval: 0,
+            lfu_accesses: 0,
+            page_offset_bits: 0,
+            page_color_bits: 0,
+        })
+    }
+
+    /// Get the block address of an address, which is the address with the block offset
+    /// bits removed. Every address within a block has the same block address.
+    ///
+    /// ## Arguments
+    /// * `address` - The address to decode.
+    pub fn block_address_of(&self, address: usize) -> usize {
+        address >> self.block_offset_bits
+    }
+
+    /// Get the index (or set) an address maps to.
+    /// A fully-associative cache has no index bits, so every address maps to set 0.
+    ///
+    /// ## Arguments
+    /// * `address` - The address to decode.
+    pub fn index_of(&self, address: usize) -> usize {
+        self.block_address_of(self.colored_address(address)) & ((1 << self.index_bits) - 1)
+    }
+
+    /// Get the address a page-colored cache indexes with: the address with the color bits
+    /// of its page number XORed with the page number bits just above them. That permutes the
+    /// pages of each color, spreading pages that shared a color over every color, and leaves
+    /// every other bit alone. Without page coloring the address is returned as it is.
+    ///
+    /// ## Arguments
+    /// * `address` - The address to recolor.
+    fn colored_address(&self, address: usize) -> usize {
+        if self.page_color_bits == 0 {
+            return address;
+        }
+        let color_mask = (1 << self.page_color_bits) - 1;
+        let recolor = (address >> (self.page_offset_bits + self.page_color_bits)) & color_mask;
+        address ^ (recolor << self.page_offset_bits)
+    }
+
+    /// Get the tag of an address.
+    ///
+    /// ## Arguments
+    /// * `address` - The address to decode.
+    pub fn tag_of(&self, address: usize) -> usize {
+        self.block_address_of(address) >> self.index_bits
+    }
+
+    /// Clear the contents of the cache without reallocating it, leaving it as it was when it
+    /// was created. Every block becomes invalid and clean, the recency order goes back to the
+    /// initial ordering from `Cache::new` where way `j` has an LRU value of `j`, and random
+    /// replacement starts over from the seed. Per-set statistics are zeroed if they're
+    /// enabled. The geometry and the options chosen for the cache are left unchanged.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::cache::cache::{Cache, HitOrMiss};
+    /// use cache_simulator::cache::replacement::ReplacementPolicy;
+    ///
+    /// // two sets of two ways, replacing at random. block b is at index b % 2 with tag b / 2
+    /// let mut cache = Cache::new(64, 2, 16)?;
+    /// cache.set_replacement_policy(ReplacementPolicy::Random)?;
+    /// cache.set_seed(7);
+    /// let fill = |cache: &mut Cache| -> Vec<usize> {
+    ///     let mut evicted = vec![];
+    ///     for block in 0..8 {
+    ///         if cache.read(block % 2, block / 2) == HitOrMiss::MISS {
+    ///             if cache.set_is_full(block % 2, block / 2) {
+    ///                 evicted.push(cache.evict_lru_block(block % 2, block / 2).evicted_block_address);
+    ///             }
+    ///             cache.install(block % 2, block / 2, block * 16);
+    ///         }
+    ///     }
+    ///     evicted
+    /// };
+    /// let evicted = fill(&mut cache);
+    ///
+    /// // every block, including the ones with tag 0, misses after a reset
+    /// cache.reset();
+    /// for block in 0..8 {
+    ///     assert!(cache.write(block % 2, block / 2) == HitOrMiss::MISS);
+    ///     assert!(cache.read(block % 2, block / 2) == HitOrMiss::MISS);
+    /// }
+    /// assert!(cache.set(0).iter().chain(cache.set(1)).all(|block| !block.valid && !block.dirty));
+    ///
+    /// // and the same accesses evict the same blocks as the first time
+    /// cache.reset();
+    /// assert_eq!(fill(&mut cache), evicted);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn reset(&mut self) {
+        self.cache.fill(Block::new());
+        self.recency.reset();
+        for set_stats in self.set_stats.iter_mut() {
+            *set_stats = SetStats::default();
+        }
+        self.psel = DIP_PSEL_MAX / 2;
+        self.bip_insertions = 0;
+        self.plru_tree.fill(false);
+        self.lru_clock = 0;
+        self.rng = SplitMix64::new(self.seed);
+        self.lfu_accesses = 0;
+    }
+
+    /// Seed the generator random replacement and bimodal insertion draw from.
+    ///
+    /// ## Arguments
+    /// * `seed` - The seed that determines every number drawn, also after a `reset`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = SplitMix64::new(seed);
+    }
+
+    /// Divide every block into independently valid sectors, so a miss only has to fetch the
+    /// sector it touches while one tag still covers the whole block.
+    ///
+    /// ## Arguments
+    /// * `sector_size` - The size of a sector, at most the block size.
+    ///
+    /// Returns an error if the sector size isn't a power of two dividing the block into at
+    /// most 64 sectors.
+    pub fn set_sector_size(&mut self, sector_size: usize) -> Result<(), CacheConfigError> {
+        if !sector_size.is_power_of_two()
+            || sector_size > self.block_size
+            || self.block_size / sector_size > u64::BITS as usize
+        {
+            return Err(CacheConfigError::InvalidSectorSize { sector_size, block_size: self.block_size });
+        }
+        self.sector_size = sector_size;
+        Ok(())
+    }
+
+    /// Choose where every block installed from now on starts in the replacement order.
+    /// Bimodal insertion draws from the same generator as random replacement.
+    ///
+    /// ## Arguments
+    /// * `insertion` - The position new blocks are inserted at.
+    /// * `bimodal_probability` - The fraction of blocks bimodal insertion puts at MRU.
+    ///
+    /// Returns an error if the probability isn't between 0 and 1.
+    pub fn set_insertion(&mut self, insertion: InsertionPosition, bimodal_probability: f64) -> Result<(), CacheConfigError> {
+        if !(0.0..=1.0).contains(&bimodal_probability) {
+            return Err(CacheConfigError::InvalidBimodalProbability(bimodal_probability));
+        }
+        self.insertion = insertion;
+        self.bimodal_probability = bimodal_probability;
+        Ok(())
+    }
+
+    /// Recolor the pages of every address before indexing, as an operating system that
+    /// colors its page allocations would. Only the index changes: the tag still comes from the
+    /// address itself, and the index bits below the page number are left alone. An empty
+    /// cache is left as it is.
+    ///
+    /// ## Arguments
+    /// * `page_size` - The size of a page, at least the block size.
+    /// * `color_bits` - The number of low page number bits to recolor, 0 for none.
+    ///
+    /// Returns an error if the page size isn't a power of two of at least the block size, or
+    /// the address is too narrow for the color bits and the bits that recolor them.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::Cache;
+    /// // pages 2, 4, 6 and 8 all have color 0, so their first blocks share set 0
+    /// let mut cache = Cache::new(8192, 1, 32)?;
+    /// let pages = [2, 4, 6, 8].map(|page| page * 4096);
+    /// assert_eq!(pages.map(|address| cache.index_of(address)), [0, 0, 0, 0]);
+    /// cache.set_page_coloring(4096, 1)?;
+    /// assert_eq!(pages.map(|address| cache.index_of(address)), [128, 0, 128, 0]);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn set_page_coloring(&mut self, page_size: usize, color_bits: usize) -> Result<(), CacheConfigError> {
+        if self.cache_size == 0 {
+            return Ok(());
+        }
+        let page_offset_bits = page_size.trailing_zeros() as usize;
+        if !page_size.is_power_of_two() || page_size < self.block_size || page_offset_bits + 2 * color_bits > ADDRESS_BITS {
+            return Err(CacheConfigError::InvalidPageColoring { page_size, color_bits, block_size: self.block_size });
+        }
+        self.page_offset_bits = page_offset_bits;
+        self.page_color_bits = color_bits;
+        Ok(())
+    }
+
+    /// Check whether blocks are divided into more than one sector.
+    pub fn is_sectored(&self) -> bool {
+        self.sector_size < self.block_size
+    }
+
+    /// Get the sector of its block that an address falls in.
+    ///
+    /// ## Arguments
+    /// * `address` - The address to decode.
+    pub fn sector_of(&self, address: usize) -> usize {
+        (address & (self.block_size - 1)) / self.sector_size
+    }
+
+    /// Check whether a sector of a resident block is valid.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `sector` - The sector to check.
+    pub fn has_sector(&self, index: usize, tag: usize, sector: usize) -> bool {
+        self.find_way(index, tag)
+            .is_some_and(|way| self.block(index, tag, way).sector_valid & (1 << sector) != 0)
+    }
+
+    /// Make a sector of a resident block valid after fetching it, dirty if it was fetched
+    /// for a write.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `sector` - The sector that was fetched.
+    /// * `dirty` - Whether the sector was fetched for a write.
+    pub fn fill_sector(&mut self, index: usize, tag: usize, sector: usize, dirty: bool) {
+        if let Some(way) = self.find_way(index, tag) {
+            let block = self.block_mut(index, tag, way);
+            block.sector_valid |= 1 << sector;
+            if dirty {
+                block.sector_dirty |= 1 << sector;
+                block.dirty = true;
+            }
+        }
+    }
+
+    /// Mark a resident block dirty without counting an access or touching its replacement
+    /// state, e.g. when it arrives carrying changes from another level.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    pub fn mark_dirty(&mut self, index: usize, tag: usize) {
+        if let Some(way) = self.find_way(index, tag) {
+            self.block_mut(index, tag, way).dirty = true;
+        }
+    }
+
+    /// Clear the dirty bit of a resident block after it was written back elsewhere, without
+    /// counting an access or touching its replacement state.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    pub fn mark_clean(&mut self, index: usize, tag: usize) {
+        if let Some(way) = self.find_way(index, tag) {
+            let block = self.block_mut(index, tag, way);
+            block.dirty = false;
+            block.sector_dirty = 0;
         }
     }
 
-    /// Print out information for the entire cache.
+    /// Check whether a block is resident and dirty, without updating the replacement state.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    pub fn is_dirty(&self, index: usize, tag: usize) -> bool {
+        self.find_way(index, tag).is_some_and(|way| self.block(index, tag, way).dirty)
+    }
+
+    /// Mark a single sector of a resident block dirty after a write hit. `write` already
+    /// marks the whole block dirty; this records which sector needs writing back.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `sector` - The sector that was written.
+    pub fn mark_sector_dirty(&mut self, index: usize, tag: usize, sector: usize) {
+        if let Some(way) = self.find_way(index, tag) {
+            self.block_mut(index, tag, way).sector_dirty |= 1 << sector;
+        }
+    }
+
+    /// Count the sectors of a block that need writing back.
+    ///
+    /// ## Arguments
+    /// * `block` - A block of this cache.
+    pub fn dirty_sectors(&self, block: &Block) -> usize {
+        match (block.dirty, self.is_sectored()) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => block.sector_dirty.count_ones() as usize,
+        }
+    }
+
+    /// Capture the full contents of the cache (tags, addresses, valid and dirty bits, and
+    /// replacement state) so it can be restored later, e.g. to start many measurement runs
+    /// from the same warmed state.
+    pub fn snapshot(&self) -> CacheSnapshot {
+        // the recency order travels in the blocks' lru values
+        let mut blocks = self.cache.clone();
+        for (i, block) in blocks.iter_mut().enumerate() {
+            block.lru = self.recency.rank(i / self.assoc, i % self.assoc);
+        }
+        CacheSnapshot {
+            cache_size: self.cache_size,
+            assoc: self.assoc,
+            block_size: self.block_size,
+            blocks,
+            psel: self.psel,
+            bip_insertions: self.bip_insertions,
+            plru_tree: self.plru_tree.clone(),
+            lru_clock: self.lru_clock,
+            rng: self.rng,
+            lfu_accesses: self.lfu_accesses,
+        }
+    }
+
+    /// Load contents captured by `snapshot`, replacing everything the cache holds.
+    ///
+    /// ## Arguments
+    /// * `snap` - A snapshot of a cache with the same geometry.
+    ///
+    /// ## Throws
+    /// This function will panic if the snapshot was taken from a cache with a different
+    /// geometry.
+    pub fn restore(&mut self, snap: &CacheSnapshot) {
+        if let Err(e) = self.try_restore(snap) {
+            panic!("Tried to restore a snapshot of a cache with a different geometry: {}", e);
+        }
+    }
+
+    /// Load contents captured by `snapshot`, like `restore`, but report a snapshot that
+    /// doesn't fit instead of panicking, e.g. for one read from a file.
+    ///
+    /// ## Arguments
+    /// * `snap` - A snapshot of a cache with the same geometry.
+    ///
+    /// Returns an error, leaving the cache unchanged, if the snapshot's geometry doesn't match.
+    pub fn try_restore(&mut self, snap: &CacheSnapshot) -> Result<(), CacheConfigError> {
+        if (snap.cache_size, snap.assoc, snap.block_size) != (self.cache_size, self.assoc, self.block_size)
+            || snap.blocks.len() != self.cache.len()
+            || snap.plru_tree.len() != self.plru_tree.len()
+        {
+            return Err(CacheConfigError::SnapshotMismatch {
+                cache_size: snap.cache_size,
+                assoc: snap.assoc,
+                block_size: snap.block_size,
+            });
+        }
+        self.cache.clone_from(&snap.blocks);
+        for (i, block) in snap.blocks.iter().enumerate() {
+            self.recency.set_rank(i / self.assoc, i % self.assoc, block.lru);
+        }
+        self.psel = snap.psel;
+        self.bip_insertions = snap.bip_insertions;
+        self.plru_tree.clone_from(&snap.plru_tree);
+        self.lru_clock = snap.lru_clock;
+        self.rng = snap.rng;
+        self.lfu_accesses = snap.lfu_accesses;
+        Ok(())
+    }
+
+    /// Select the replacement policy, checking that the geometry supports it.
+    /// An empty cache accepts any policy since it never replaces anything.
+    ///
+    /// ## Arguments
+    /// * `replacement` - The policy to use from now on.
+    ///
+    /// Returns an error if anything but LRU is selected for a skewed cache.
+    pub fn set_replacement_policy(&mut self, replacement: ReplacementPolicy) -> Result<(), CacheConfigError> {
+        if self.skewed && replacement != ReplacementPolicy::Lru {
+            return Err(CacheConfigError::SkewedNeedsLru(replacement));
+        }
+        // clock keeps its own bits in place of the recency order every other policy uses
+        if (replacement == ReplacementPolicy::Clock) != (self.replacement == ReplacementPolicy::Clock) {
+            self.recency = if replacement == ReplacementPolicy::Clock {
+                Box::new(Clock::new(self.sets, self.assoc))
+            } else {
+                Box::new(Lru::new(self.sets, self.assoc))
+            };
+        }
+        self.replacement = replacement;
+        Ok(())
+    }
+
+    /// Make every way index with its own hash of the address (skewed associativity), so
+    /// blocks that conflict in one way are usually spread out in the others.
+    ///
+    /// The other replacement policies keep their state per set, which a skewed cache doesn't
+    /// have: the ways a block can go in are a different group for nearly every block. Its
+    /// LRU order is kept with timestamps instead.
+    ///
+    /// An empty cache is left as it is, since it never looks anything up.
+    ///
+    /// Returns an error if the replacement policy isn't LRU.
+    pub fn set_skewed(&mut self) -> Result<(), CacheConfigError> {
+        if self.cache_size == 0 {
+            return Ok(());
+        }
+        if self.replacement != ReplacementPolicy::Lru {
+            return Err(CacheConfigError::SkewedNeedsLru(self.replacement));
+        }
+        self.skewed = true;
+        Ok(())
+    }
+
+    /// Get the row of the cache way `way` of a block is stored at in a skewed cache.
+    ///
+    /// Way 0 uses the conventional index. Every other way XORs the index with a hash of the
+    /// tag: `(tag ^ way) * 0x9e3779b97f4a7c15`, keeping the top bits of its low 64. Each way's
+    /// hash is different, so two blocks with the same conventional index (a conflict) only
+    /// share a row in the other ways by chance. Since the hash only depends on the tag, two
+    /// blocks in the same row of a way with the same tag must also have the same index, so
+    /// comparing tags is still enough to find a block.
+    ///
+    /// ## Arguments
+    /// * `index` - The conventional index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `way` - The way to get the row of.
+    pub fn skewed_index(&self, index: usize, tag: usize, way: usize) -> usize {
+        if way == 0 {
+            return index;
+        }
+        let hash = ((tag ^ way) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32;
+        (index ^ hash as usize) & (self.sets - 1)
+    }
+
+    /// Get where a way of the set a block maps to is stored in `cache`. That's row `index`
+    /// in a conventional cache, and the way's own skewed index in a skewed one.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block, which only matters in a skewed cache.
+    /// * `way` - The way to locate.
+    fn slot(&self, index: usize, tag: usize, way: usize) -> usize {
+        self.row(index, tag, way) * self.assoc + way
+    }
+
+    /// Get the row a way of the set a block maps to is stored in, which is the set the
+    /// replacement state knows it by.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block, which only matters in a skewed cache.
+    /// * `way` - The way to locate.
+    fn row(&self, index: usize, tag: usize, way: usize) -> usize {
+        if self.skewed { self.skewed_index(index, tag, way) } else { index }
+    }
+
+    /// Get the block in a way of the set a block maps to.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `way` - The way to get.
+    fn block(&self, index: usize, tag: usize, way: usize) -> &Block {
+        &self.cache[self.slot(index, tag, way)]
+    }
+
+    /// Get the block in a way of the set a block maps to for modification.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `way` - The way to get.
+    fn block_mut(&mut self, index: usize, tag: usize, way: usize) -> &mut Block {
+        let slot = self.slot(index, tag, way);
+        &mut self.cache[slot]
+    }
+
+    /// Find the way holding a valid block.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    ///
+    /// Returns the way, or `None` if the block isn't resident.
+    fn find_way(&self, index: usize, tag: usize) -> Option<usize> {
+        (0..self.assoc).find(|&way| {
+            let block = self.block(index, tag, way);
+            block.valid && block.tag == tag
+        })
+    }
+
+    /// Get the blocks of a single set.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to get.
+    pub fn set(&self, index: usize) -> &[Block] {
+        &self.cache[index * self.assoc..(index + 1) * self.assoc]
+    }
+
+    /// Get the blocks of a single set for modification.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to get.
+    pub fn set_mut(&mut self, index: usize) -> &mut [Block] {
+        &mut self.cache[index * self.assoc..(index + 1) * self.assoc]
+    }
+
+    /// Start keeping per-set statistics. Until this is called no per-set accounting is done.
+    pub fn enable_set_stats(&mut self) {
+        self.set_stats = vec![SetStats::default(); self.sets];
+    }
+
+    /// Record a demand access to a set if per-set statistics are enabled.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) that was accessed.
+    /// * `hit_or_miss` - Whether the access hit or missed.
+    pub fn record_set_access(&mut self, index: usize, hit_or_miss: &HitOrMiss) {
+        if let Some(set_stats) = self.set_stats.get_mut(index) {
+            set_stats.accesses += 1;
+            if *hit_or_miss == HitOrMiss::MISS {
+                set_stats.misses += 1;
+            }
+        }
+    }
+
+    /// Print out the per-set statistics, if they're enabled.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the statistics, e.g. stdout.
     ///
     /// ## Example
+    /// ```text
+    /// set     accesses     misses  evictions  miss rate
+    ///    0        1024        112         96     0.1094
     /// ```
+    pub fn print_set_stats<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "set     accesses     misses  evictions  miss rate")?;
+        for (i, set_stats) in self.set_stats.iter().enumerate() {
+            let mut miss_rate = set_stats.misses as f32 / set_stats.accesses as f32;
+            if miss_rate.is_nan() {
+                miss_rate = 0.0;
+            }
+            writeln!(
+                out,
+                "{:>4} {:>11} {:>10} {:>10} {:>10.4}",
+                i, set_stats.accesses, set_stats.misses, set_stats.evictions, miss_rate
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the sets with the most evictions, most first, with how many distinct tags they
+    /// evicted. A set that evicts many times but few distinct tags has a handful of blocks
+    /// thrashing in it, as a strided access pattern produces. Ties are broken by index, and
+    /// sets without evictions aren't printed. Needs per-set statistics to be enabled.
+    ///
+    /// ## Arguments
+    /// * `top` - How many sets to print.
+    /// * `out` - Where to write the report, e.g. stdout.
+    ///
+    /// ## Example
+    /// ```text
+    /// set  evictions   distinct tags     misses
+    ///   12       4096               2       4098
+    /// ```
+    pub fn print_top_evicted_sets<W: Write>(&self, top: usize, out: &mut W) -> io::Result<()> {
+        let mut sets: Vec<(usize, &SetStats)> = self.set_stats
+            .iter()
+            .enumerate()
+            .filter(|(_, set_stats)| set_stats.evictions != 0)
+            .collect();
+        sets.sort_by(|(a_index, a), (b_index, b)| b.evictions.cmp(&a.evictions).then(a_index.cmp(b_index)));
+        writeln!(out, "set  evictions   distinct tags     misses")?;
+        for (index, set_stats) in sets.into_iter().take(top) {
+            writeln!(
+                out,
+                "{:>4} {:>10} {:>15} {:>10}",
+                index, set_stats.evictions, set_stats.evicted_tags.len(), set_stats.misses
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print out information for the entire cache, followed by a summary of how many blocks
+    /// are valid and dirty.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the contents, e.g. stdout.
+    ///
+    /// ## Example
+    /// ```text
     /// set    1:   824721 D  948241
     /// set    2:   824721 D  948241
+    /// valid blocks: 4, dirty blocks: 2, occupancy: 100.00%
     /// ```
-    pub fn print_cache_info(&self) {
+    pub fn print_cache_info<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        // right-align set numbers to the widest one, but never narrower than 3 digits
+        let width = self.sets.saturating_sub(1).to_string().len().max(3);
         for i in 0..self.sets {
-            print!("set    ");
-            if i < 100 {
-                print!(" ");
-            }
-            if i < 10 {
-                print!(" ");
-            }
-            print!("{}: ", i);
+            write!(out, "set    {:>width$}: ", i, width = width)?;
 
-            let mut set: Vec<Block> = self.cache[i].clone();
-            set.sort_by(|a, b| a.lru.partial_cmp(&b.lru).unwrap());
+            let mut set: Vec<(usize, &Block)> = self.set(i).iter().enumerate().collect();
+            set.sort_by_key(|&(way, _)| self.recency.rank(i, way));
 
-            for j in 0..self.assoc {
-                print!("  ");
-                print!("{number:>6x}", number=set[j].tag);
-                if set[j].dirty {
-                    print!(" D");
+            for (_, block) in set {
+                write!(out, "  ")?;
+                write!(out, "{number:>6x}", number=block.tag)?;
+                if block.dirty {
+                    write!(out, " D")?;
                 } else {
-                    print!("  ");
+                    write!(out, "  ")?;
                 }
             }
-            println!();
+            writeln!(out)?;
         }
+        let dirty_blocks = self.cache.iter().filter(|block| block.valid && block.dirty).count();
+        writeln!(
+            out,
+            "valid blocks: {}, dirty blocks: {}, occupancy: {:.2}%",
+            self.occupied_blocks(), dirty_blocks, self.occupancy() * 100.0
+        )?;
+        Ok(())
+    }
+
+    /// Print a dense map of what the cache holds, one row per set with one character per
+    /// way, most recently used first: `.` for an empty way, `C` for a clean block, and `D`
+    /// for a dirty one. Unlike `print_cache_info` it leaves out the tags, so even a large
+    /// cache can be taken in at a glance.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the map, e.g. stdout.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::Cache;
+    /// let mut cache = Cache::new(128, 4, 16)?;
+    /// cache.install(0, 1, 0x100);
+    /// cache.install_for_write(0, 2, 0x200);
+    /// cache.install(1, 3, 0x310);
+    /// let mut map = vec![];
+    /// cache.print_occupancy_map(&mut map)?;
+    /// assert_eq!(String::from_utf8(map).unwrap(), "  0 DC..\n  1 C...\n");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn print_occupancy_map<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let width = self.sets.saturating_sub(1).to_string().len().max(3);
+        for i in 0..self.sets {
+            let mut set: Vec<(usize, &Block)> = self.set(i).iter().enumerate().collect();
+            set.sort_by_key(|&(way, _)| self.recency.rank(i, way));
+            let cells: String = set
+                .into_iter()
+                .map(|(_, block)| match (block.valid, block.dirty) {
+                    (false, _) => '.',
+                    (true, false) => 'C',
+                    (true, true) => 'D',
+                })
+                .collect();
+            writeln!(out, "{:>width$} {}", i, cells, width = width)?;
+        }
+        Ok(())
     }
 
     /// Try to read from the cache given the index and tag of the block.
@@ -122,14 +970,38 @@ impl Cache {
     ///
     /// Returns whether the block was in the cache (hit) or not (miss).
     pub fn read(&mut self, index: usize, tag: usize) -> HitOrMiss {
-        for i in 0..self.assoc {
-            // L1 Hit if tags are equal and location is valid
-            if self.cache[index][i].tag == tag && self.cache[index][i].valid {
-                self.update_lru(index, tag);
-                return HitOrMiss::HIT;
-            }
+        // L1 Hit if tags are equal and location is valid
+        if let Some(way) = self.find_way(index, tag) {
+            self.on_hit(index, tag, way);
+            return HitOrMiss::HIT;
         }
-        return HitOrMiss::MISS;
+        self.on_miss(index);
+        HitOrMiss::MISS
+    }
+
+    /// Check whether a block is resident in the cache without touching its recency or dirty
+    /// state, unlike `read`.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the desired block.
+    /// * `tag` - The tag of the desired block.
+    ///
+    /// Returns whether a valid block with the tag is in the set.
+    pub fn contains(&self, index: usize, tag: usize) -> bool {
+        self.find_way(index, tag).is_some()
+    }
+
+    /// Check whether the block holding an address is resident in the cache.
+    /// Like `contains` this is read-only, so it can be used to probe other caches (e.g. the
+    /// other cores' private caches in a coherence protocol) without perturbing their
+    /// replacement order. Pair it with `invalidate` to remove the block once found.
+    ///
+    /// ## Arguments
+    /// * `address` - Any address within the desired block.
+    ///
+    /// Returns whether the block is in the cache.
+    pub fn holds_address(&self, address: usize) -> bool {
+        self.contains(self.index_of(address), self.tag_of(address))
     }
 
     /// Try to write to the cache given the index and tag of the block.
@@ -140,17 +1012,59 @@ impl Cache {
     ///
     /// Returns whether the block was written to in the cache (hit) or not (miss).
     pub fn write(&mut self, index: usize, tag: usize) -> HitOrMiss {
-        let mut written = false;
-        for i in 0..self.assoc {
-            if self.cache[index][i].tag == tag {
-                self.cache[index][i].valid = true;
-                self.update_lru(index, tag);
-                self.cache[index][i].dirty = true;
-                written = true;
-                break;
-            }
+        // like a read, only a valid block with the tag is a hit
+        if let Some(way) = self.find_way(index, tag) {
+            self.on_hit(index, tag, way);
+            self.block_mut(index, tag, way).dirty = true;
+            return HitOrMiss::HIT;
         }
-        return if written { HitOrMiss::HIT } else { HitOrMiss::MISS };
+        self.on_miss(index);
+        HitOrMiss::MISS
+    }
+
+    /// Read or write a block, allocating it on a miss: if the set is full its LRU block is
+    /// evicted, and the block is installed, dirty for a write. The caller only has to deal
+    /// with the evicted block, e.g. by writing it back if it was dirty.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `address` - The address being accessed.
+    /// * `is_write` - Whether the access is a write.
+    ///
+    /// Returns whether the access hit and the block evicted to make room, if any.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::cache::cache::{Cache, HitOrMiss};
+    ///
+    /// // one set of two ways, so the tag is the address over 16
+    /// let mut cache = Cache::new(32, 2, 16)?;
+    /// let first = cache.access(0, 1, 0x10, true);
+    /// assert!(first.hit_or_miss == HitOrMiss::MISS && first.evicted.is_none());
+    /// cache.access(0, 2, 0x20, false);
+    /// assert!(cache.access(0, 2, 0x20, false).hit_or_miss == HitOrMiss::HIT);
+    ///
+    /// // the set is full, so the dirty block at 0x10 makes room
+    /// let outcome = cache.access(0, 3, 0x30, false);
+    /// let evicted = outcome.evicted.unwrap();
+    /// assert!(outcome.hit_or_miss == HitOrMiss::MISS);
+    /// assert_eq!((evicted.evicted_block_address, evicted.evicted_block_was_dirty), (0x10, true));
+    ///
+    /// // and then the clean block at 0x20
+    /// let evicted = cache.access(0, 4, 0x40, true).evicted.unwrap();
+    /// assert_eq!((evicted.evicted_block_address, evicted.evicted_block_was_dirty), (0x20, false));
+    /// assert!(cache.is_dirty(0, 4) && !cache.is_dirty(0, 3));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn access(&mut self, index: usize, tag: usize, address: usize, is_write: bool) -> AccessOutcome {
+        let hit_or_miss = if is_write { self.write(index, tag) } else { self.read(index, tag) };
+        if hit_or_miss == HitOrMiss::HIT {
+            return AccessOutcome { hit_or_miss, evicted: None };
+        }
+        let evicted = self.set_is_full(index, tag).then(|| self.evict_lru_block(index, tag));
+        self.fill_free_way(index, tag, address, is_write);
+        AccessOutcome { hit_or_miss, evicted }
     }
 
     /// Install a block in the cache given the index, tag and address of the block.
@@ -164,87 +1078,755 @@ impl Cache {
     /// This function will panic if there is no room to install in the set.
     /// That should be handled before installing.
     pub fn install(&mut self, index: usize, tag: usize, address: usize) {
-        let mut installed = false;
-        for i in 0..self.assoc {
-            // Found an invalid block, install
-            if !self.cache[index][i].valid {
-                self.cache[index][i].address = address;
-                self.cache[index][i].tag = tag;
-                self.cache[index][i].valid = true;
-                self.update_lru(index, tag);
-                installed = true;
-                break;
+        self.fill_free_way(index, tag, address, false);
+    }
+
+    /// Install a block for a write that missed, leaving it dirty. This is the same as
+    /// `install` followed by `write`, but the block only gets one replacement update, as an
+    /// insertion rather than as an insertion plus a hit.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block to install.
+    /// * `tag` - The tag of the block to install.
+    /// * `address` - The address of the block to install.
+    ///
+    /// ## Throws
+    /// This function will panic if there is no room to install in the set, like `install`.
+    pub fn install_for_write(&mut self, index: usize, tag: usize, address: usize) {
+        self.fill_free_way(index, tag, address, true);
+    }
+
+    /// Fill the first invalid way of a set with a block and update the replacement state
+    /// for the insertion, at the position given by `insertion`.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block to install.
+    /// * `tag` - The tag of the block to install.
+    /// * `address` - The address of the block to install.
+    /// * `dirty` - Whether the block is installed already modified.
+    ///
+    /// Returns the way the block was installed in.
+    fn fill_free_way(&mut self, index: usize, tag: usize, address: usize, dirty: bool) -> usize {
+        // new blocks are predicted to be re-referenced in the "long" interval under RRIP
+        let rrpv = self.rrip_max() - 1;
+
+        // Find an invalid block, install
+        let way = self.allocatable_ways()
+            .find(|&way| !self.block(index, tag, way).valid)
+            .expect("Tried to install where there was no free space.");
+        let block = self.block_mut(index, tag, way);
+        block.address = address;
+        block.tag = tag;
+        block.valid = true;
+        block.dirty = dirty;
+        block.prefetched = false;
+        block.sector_valid = u64::MAX;
+        block.sector_dirty = if dirty { u64::MAX } else { 0 };
+        block.rrpv = rrpv;
+        block.frequency = 0;
+        if self.skewed {
+            self.touch_lru(index, tag, way);
+        } else {
+            self.recency.on_install(index, way);
+        }
+        if self.replacement == ReplacementPolicy::TreePlru {
+            self.touch_plru(index, way);
+        }
+
+        // bimodal insertion leaves most new blocks at the lru position instead
+        if self.replacement == ReplacementPolicy::Dip && self.uses_bip(index) {
+            self.bip_insertions += 1;
+            if !self.bip_insertions.is_multiple_of(BIP_THROTTLE) {
+                self.recency.demote(index, way);
             }
         }
+        let demote = match self.insertion {
+            InsertionPosition::Mru => false,
+            InsertionPosition::Lru => true,
+            InsertionPosition::Bimodal => self.rng.next_fraction() >= self.bimodal_probability,
+        };
+        if demote {
+            self.demote(index, tag, way);
+        }
+        way
+    }
 
-        if !installed {
-            panic!("Tried to install where there was no free space.");
+    /// Make a way the next victim of its set under the current replacement policy.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to update.
+    /// * `tag` - The tag of the block in the way.
+    /// * `way` - The way to demote.
+    fn demote(&mut self, index: usize, tag: usize, way: usize) {
+        if self.skewed {
+            // older than any timestamp
+            let row = self.row(index, tag, way);
+            self.recency.set_rank(row, way, usize::MAX);
+            return;
+        }
+        match self.replacement {
+            ReplacementPolicy::Lru
+            | ReplacementPolicy::Dip
+            | ReplacementPolicy::Random
+            | ReplacementPolicy::Lfu
+            | ReplacementPolicy::Clock => self.recency.demote(index, way),
+            ReplacementPolicy::Rrip => self.set_mut(index)[way].rrpv = self.rrip_max(),
+            ReplacementPolicy::TreePlru => self.point_plru(index, way, true),
         }
     }
 
-    /// Update the recency values of all blocks in a set.
-    /// This is called after reading, writing, and installing.
+    /// Install a block in a sectored cache with only one of its sectors valid.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block to install.
+    /// * `tag` - The tag of the block to install.
+    /// * `address` - The address of the block to install.
+    /// * `sector` - The sector being filled.
+    /// * `dirty` - Whether the sector is installed for a write.
+    ///
+    /// ## Throws
+    /// This function will panic if there is no room to install in the set, like `install`.
+    pub fn install_sector(&mut self, index: usize, tag: usize, address: usize, sector: usize, dirty: bool) {
+        let way = self.fill_free_way(index, tag, address, dirty);
+        let block = self.block_mut(index, tag, way);
+        block.sector_valid = 1 << sector;
+        block.sector_dirty = if dirty { 1 << sector } else { 0 };
+    }
+
+    /// Install a block brought in by a prefetch rather than a demand access, marking it so
+    /// its first demand access can be counted as a useful prefetch.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block to install.
+    /// * `tag` - The tag of the block to install.
+    /// * `address` - The address of the block to install.
+    ///
+    /// ## Throws
+    /// This function will panic if there is no room to install in the set, like `install`.
+    pub fn install_prefetch(&mut self, index: usize, tag: usize, address: usize) {
+        let way = self.fill_free_way(index, tag, address, false);
+        self.block_mut(index, tag, way).prefetched = true;
+    }
+
+    /// Clear the prefetched flag of a resident block, as happens on its first demand access.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    ///
+    /// Returns whether the block was waiting on its first demand access after a prefetch.
+    pub fn take_prefetched(&mut self, index: usize, tag: usize) -> bool {
+        match self.find_way(index, tag) {
+            Some(way) => std::mem::take(&mut self.block_mut(index, tag, way).prefetched),
+            None => false,
+        }
+    }
+
+    /// Update the replacement state of a set after a lookup in it missed.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) that missed.
+    fn on_miss(&mut self, index: usize) {
+        self.age_lfu();
+        if self.replacement != ReplacementPolicy::Dip {
+            return;
+        }
+        match self.dip_role(index) {
+            DipRole::LruSampler => self.psel = (self.psel + 1).min(DIP_PSEL_MAX),
+            DipRole::BipSampler => self.psel = self.psel.saturating_sub(1),
+            DipRole::Follower => {}
+        }
+    }
+
+    /// Get the part a set plays in DIP's set dueling. One set in every `sets / 32` (but at
+    /// least one in every 4) samples LRU insertion, the set halfway between each pair of those
+    /// samples BIP, and the rest follow the policy selector.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to check.
+    fn dip_role(&self, index: usize) -> DipRole {
+        let stride = (self.sets / DIP_SAMPLER_SETS).max(4);
+        match index % stride {
+            0 => DipRole::LruSampler,
+            offset if offset == stride / 2 => DipRole::BipSampler,
+            _ => DipRole::Follower,
+        }
+    }
+
+    /// Check whether a set currently inserts new blocks with BIP under DIP.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to check.
+    fn uses_bip(&self, index: usize) -> bool {
+        match self.dip_role(index) {
+            DipRole::LruSampler => false,
+            DipRole::BipSampler => true,
+            DipRole::Follower => self.psel > DIP_PSEL_MAX / 2,
+        }
+    }
+
+    /// Update the replacement state of a set after a block in it was hit.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) that was hit.
+    /// * `tag` - The tag of the block that was hit.
+    /// * `way` - The way of the block that was hit.
+    fn on_hit(&mut self, index: usize, tag: usize, way: usize) {
+        self.age_lfu();
+        self.touch_lru(index, tag, way);
+        match self.replacement {
+            ReplacementPolicy::Rrip => self.block_mut(index, tag, way).rrpv = 0,
+            ReplacementPolicy::TreePlru => self.touch_plru(index, way),
+            ReplacementPolicy::Lfu => {
+                let block = self.block_mut(index, tag, way);
+                block.frequency = block.frequency.saturating_add(1);
+            }
+            ReplacementPolicy::Lru | ReplacementPolicy::Dip | ReplacementPolicy::Random | ReplacementPolicy::Clock => {}
+        }
+    }
+
+    /// Count an access towards LFU aging, halving every block's counter once the aging
+    /// interval has passed. Does nothing unless LFU replacement ages its counters.
+    fn age_lfu(&mut self) {
+        if self.replacement != ReplacementPolicy::Lfu || self.lfu_aging_interval == 0 {
+            return;
+        }
+        self.lfu_accesses += 1;
+        if self.lfu_accesses == self.lfu_aging_interval {
+            self.lfu_accesses = 0;
+            for block in self.cache.iter_mut() {
+                block.frequency /= 2;
+            }
+        }
+    }
+
+    /// Find the least frequently used way of a set, the least recently used of them if
+    /// several tie.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to pick a victim from.
+    ///
+    /// Returns the way of the victim.
+    fn lfu_victim(&self, index: usize) -> usize {
+        let set = self.set(index);
+        self.allocatable_ways()
+            .min_by_key(|&way| (set[way].frequency, usize::MAX - self.recency.rank(index, way)))
+            .expect("a way mask selects at least one way")
+    }
+
+    /// Point every tree-PLRU node on the path to a way away from it, making it the most
+    /// recently used way as far as the tree can tell.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) that was accessed.
+    /// * `way` - The way that was accessed.
+    fn touch_plru(&mut self, index: usize, way: usize) {
+        self.point_plru(index, way, false);
+    }
+
+    /// Point every tree-PLRU node on the path to a way either at it or away from it.
     ///
     /// ## Arguments
     /// * `index` - The index (or set) to update.
-    /// * `tag` - The tag of the block that was just accessed.
-    pub fn update_lru(&mut self, index: usize, tag: usize) {
-        let mut new_mru_way: usize = 0;
-        for i in 0..self.assoc {
-            if self.cache[index][i].tag == tag {
-                new_mru_way = i;
+    /// * `way` - The way whose path is updated.
+    /// * `at_way` - Whether the nodes should lead the next victim search to the way.
+    fn point_plru(&mut self, index: usize, way: usize, at_way: bool) {
+        let nodes = self.assoc - 1;
+        let tree = &mut self.plru_tree[index * nodes..(index + 1) * nodes];
+        let (mut node, mut first_way, mut ways) = (0, 0, self.assoc);
+        while ways > 1 {
+            ways /= 2;
+            let went_right = way >= first_way + ways;
+            tree[node] = went_right == at_way;
+            node = 2 * node + 1 + went_right as usize;
+            if went_right {
+                first_way += ways;
             }
         }
+    }
 
-        for i in 0..self.assoc {
-            if i != new_mru_way && self.cache[index][i].lru < self.cache[index][new_mru_way].lru {
-                self.cache[index][i].lru += 1;
+    /// Pick the tree-PLRU victim in a full set by following the tree bits from the root.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to pick a victim from.
+    ///
+    /// Returns the way of the victim.
+    fn plru_victim(&self, index: usize) -> usize {
+        let nodes = self.assoc - 1;
+        let tree = &self.plru_tree[index * nodes..(index + 1) * nodes];
+        let (mut node, mut first_way, mut ways) = (0, 0, self.assoc);
+        while ways > 1 {
+            ways /= 2;
+            let go_right = tree[node];
+            node = 2 * node + 1 + go_right as usize;
+            if go_right {
+                first_way += ways;
             }
         }
-        self.cache[index][new_mru_way].lru = 0;
+        first_way
     }
 
-    /// Check whether a set is full.
+    /// Get the largest re-reference prediction value, 2^M - 1, meaning "re-referenced in the
+    /// distant future".
+    fn rrip_max(&self) -> usize {
+        (1 << self.rrip_bits) - 1
+    }
+
+    /// Pick the RRIP victim in a full set: the first block with the largest RRPV. If no block
+    /// has it, every block in the set ages by one and the search repeats.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to pick a victim from.
+    ///
+    /// Returns the way of the victim.
+    fn rrip_victim(&mut self, index: usize) -> usize {
+        let rrip_max = self.rrip_max();
+        let ways: Vec<usize> = self.allocatable_ways().collect();
+        loop {
+            if let Some(&way) = ways.iter().find(|&&way| self.set(index)[way].rrpv >= rrip_max) {
+                return way;
+            }
+            let set = self.set_mut(index);
+            for &way in &ways {
+                set[way].rrpv += 1;
+            }
+        }
+    }
+
+    /// Update the recency values of all blocks in a set, given the tag of the block that was
+    /// accessed. This searches the set for the tag first, so when the way is already known
+    /// `update_lru_way` does the same update without the search.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to update.
+    /// * `tag` - The tag of the valid block that was just accessed.
+    pub fn update_lru(&mut self, index: usize, tag: usize) {
+        match self.find_way(index, tag) {
+            Some(way) => self.touch_lru(index, tag, way),
+            // nothing matched, so the first way is taken as the accessed one
+            None if !self.skewed => self.update_lru_way(index, 0),
+            None => {}
+        }
+    }
+
+    /// Update the recency of a block whose way is already known, stamping it in a skewed
+    /// cache and re-ranking its set otherwise.
+    /// This is called after reading, writing, and installing.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    /// * `way` - The way of the block.
+    fn touch_lru(&mut self, index: usize, tag: usize, way: usize) {
+        // a direct-mapped set only has one way, which is always the most recently used
+        if self.assoc == 1 {
+            return;
+        }
+
+        // the ways a skewed block can go in aren't a fixed set to rank, so stamp it instead,
+        // counting down so the least recently used block still has the largest value
+        if self.skewed {
+            self.lru_clock += 1;
+            let row = self.row(index, tag, way);
+            self.recency.set_rank(row, way, usize::MAX - 1 - self.lru_clock);
+            return;
+        }
+        self.update_lru_way(index, way);
+    }
+
+    /// Make a way the most recently used in its set, without searching the set for it.
+    ///
+    /// The LRU values within a set are always a permutation of `0..assoc`, with 0 being the
+    /// most recently used way and `assoc - 1` the least recently used. `Cache::new` sets up
+    /// that ordering, and this moves the accessed way to 0 while shifting every way that was
+    /// more recent than it down by one, so the invariant holds after every update and
+    /// `evict_lru_block` always has a single unambiguous victim.
+    ///
+    /// A skewed cache has no fixed set to rank, so use `update_lru` there.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to update.
+    /// * `way` - The way that was just accessed.
+    pub fn update_lru_way(&mut self, index: usize, way: usize) {
+        if self.assoc == 1 {
+            return;
+        }
+        self.recency.on_access(index, way);
+        debug_assert!(
+            self.replacement == ReplacementPolicy::Clock || self.lru_is_permutation(index),
+            "LRU values in set {} are not a permutation", index
+        );
+    }
+
+    /// Get where a resident block stands in the recency order of its set, without touching
+    /// it. Under policies other than LRU this is its place in the order the recency state
+    /// keeps, e.g. how soon the clock hand reaches it.
+    ///
+    /// ## Arguments
+    /// * `index` - The index of the block.
+    /// * `tag` - The tag of the block.
+    ///
+    /// Returns the position, 0 for the most recently used way, or `None` if the block isn't
+    /// resident.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::Cache;
+    /// let mut cache = Cache::new(64, 4, 16)?;
+    /// for tag in [1, 2, 3] {
+    ///     cache.install(0, tag, tag * 16);
+    /// }
+    /// cache.read(0, 1);
+    /// let positions: Vec<_> = [1, 2, 3, 4].iter().map(|&tag| cache.lru_position(0, tag)).collect();
+    /// assert_eq!(positions, [Some(0), Some(2), Some(1), None]);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn lru_position(&self, index: usize, tag: usize) -> Option<usize> {
+        let way = self.find_way(index, tag)?;
+        let rank_of = |way| self.recency.rank(self.row(index, tag, way), way);
+        let rank = rank_of(way);
+        Some((0..self.assoc).filter(|&other| rank_of(other) < rank).count())
+    }
+
+    /// Check that the LRU values of a set are a permutation of `0..assoc`.
     ///
     /// ## Arguments
     /// * `index` - The index (or set) to check.
     ///
-    /// Returns whether the set is full.
-    pub fn set_is_full(&self, index:usize) -> bool {
-        for i in 0..self.assoc {
-            if !self.cache[index][i].valid {
+    /// Returns whether every LRU value in the set is distinct and less than `assoc`.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::{Cache, HitOrMiss};
+    /// // a single 4-way set, filled, hit, and evicted from in turn
+    /// let mut cache = Cache::new(64, 4, 16)?;
+    /// assert!(cache.lru_is_permutation(0));
+    /// for tag in [1, 2, 3, 1, 4, 5, 2, 6] {
+    ///     if cache.read(0, tag) == HitOrMiss::MISS {
+    ///         if cache.set_is_full(0, tag) {
+    ///             cache.evict_lru_block(0, tag);
+    ///         }
+    ///         cache.install(0, tag, tag * 16);
+    ///     }
+    ///     assert!(cache.lru_is_permutation(0));
+    /// }
+    /// cache.write(0, 6);
+    /// cache.evict_lru_block(0, 6);
+    /// assert!(cache.lru_is_permutation(0));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn lru_is_permutation(&self, index: usize) -> bool {
+        let mut seen = vec![false; self.assoc];
+        for way in 0..self.assoc {
+            let rank = self.recency.rank(index, way);
+            if rank >= self.assoc || seen[rank] {
                 return false;
             }
+            seen[rank] = true;
+        }
+        true
+    }
+
+    /// Check whether the set a block maps to is full, as far as the ways in
+    /// `allocation_ways` go.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to check.
+    /// * `tag` - The tag of the block needing room, which picks the ways of a skewed set.
+    ///
+    /// Returns whether the set is full.
+    pub fn set_is_full(&self, index: usize, tag: usize) -> bool {
+        self.allocatable_ways().all(|way| self.block(index, tag, way).valid)
+    }
+
+    /// Get the ways in `allocation_ways`, the ones blocks may currently be installed in.
+    fn allocatable_ways(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.assoc).filter(|&way| way >= u64::BITS as usize || self.allocation_ways >> way & 1 == 1)
+    }
+
+    /// Check that a way mask can restrict where this cache installs blocks.
+    ///
+    /// ## Arguments
+    /// * `ways` - The mask, way `w` at bit `w`.
+    ///
+    /// Returns an error if the mask selects no ways or ways the cache doesn't have, or if the
+    /// replacement policy can't pick a victim within some of the ways.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::Cache;
+    /// # use cache_simulator::cache::error::CacheConfigError;
+    /// # use cache_simulator::cache::replacement::ReplacementPolicy;
+    /// let mut cache = Cache::new(1024, 4, 32)?;
+    /// assert_eq!(cache.check_way_mask(0b0011), Ok(()));
+    /// assert_eq!(cache.check_way_mask(0b10000), Err(CacheConfigError::InvalidWayMask { ways: 0b10000, assoc: 4 }));
+    /// cache.set_replacement_policy(ReplacementPolicy::TreePlru)?;
+    /// assert_eq!(cache.check_way_mask(0b0011), Err(CacheConfigError::WayPartitionUnsupported(ReplacementPolicy::TreePlru)));
+    /// # Ok::<(), CacheConfigError>(())
+    /// ```
+    pub fn check_way_mask(&self, ways: u64) -> Result<(), CacheConfigError> {
+        if ways == 0 || (self.assoc < u64::BITS as usize && ways >> self.assoc != 0) {
+            return Err(CacheConfigError::InvalidWayMask { ways, assoc: self.assoc });
+        }
+        if matches!(self.replacement, ReplacementPolicy::TreePlru | ReplacementPolicy::Clock) {
+            return Err(CacheConfigError::WayPartitionUnsupported(self.replacement));
+        }
+        Ok(())
+    }
+
+    /// Check the bookkeeping of every set: the LRU values are a permutation of `0..assoc`,
+    /// no block is resident in two ways, and `set_is_full` agrees with the number of valid
+    /// ways. The recency order is only checked where it is a ranking, so not under clock
+    /// replacement or in a skewed cache, and neither is `set_is_full` in a skewed cache,
+    /// where it depends on the block needing room.
+    ///
+    /// Returns a message naming the first set that breaks an invariant and how.
+    ///
+    /// ## Example
+    /// ```
+    /// # use cache_simulator::cache::cache::Cache;
+    /// let mut cache = Cache::new(64, 2, 16)?;
+    /// for address in [0x110, 0x130] {
+    ///     cache.install(cache.index_of(address), cache.tag_of(address), address);
+    /// }
+    /// assert_eq!(cache.check_invariants(), Ok(()));
+    ///
+    /// // copy way 0 of set 1 over way 1, so the set holds the same block twice
+    /// cache.cache[3] = cache.cache[2].clone();
+    /// assert_eq!(cache.check_invariants(), Err("set 1: ways 0 and 1 both hold block 11".to_string()));
+    ///
+    /// // tie the two ways of set 0 for most recently used
+    /// let mut cache = Cache::new(64, 2, 16)?;
+    /// cache.recency.set_rank(0, 1, 0);
+    /// assert_eq!(cache.check_invariants(), Err("set 0: LRU values [0, 0] aren't a permutation of 0..2".to_string()));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn check_invariants(&self) -> Result<(), String> {
+        for set in 0..self.sets {
+            if !self.skewed && self.replacement != ReplacementPolicy::Clock && !self.lru_is_permutation(set) {
+                let ranks: Vec<usize> = (0..self.assoc).map(|way| self.recency.rank(set, way)).collect();
+                return Err(format!("set {}: LRU values {:?} aren't a permutation of 0..{}", set, ranks, self.assoc));
+            }
+            let blocks = self.set(set);
+            for (way, block) in blocks.iter().enumerate().filter(|(_, block)| block.valid) {
+                let block_address = self.block_address_of(block.address);
+                let duplicate = blocks[way + 1..]
+                    .iter()
+                    .position(|other| other.valid && self.block_address_of(other.address) == block_address);
+                if let Some(offset) = duplicate {
+                    return Err(format!("set {}: ways {} and {} both hold block {:x}", set, way, way + 1 + offset, block_address));
+                }
+            }
+            let valid_ways = blocks.iter().filter(|block| block.valid).count();
+            if !self.skewed && self.set_is_full(set, 0) != (valid_ways == self.assoc) {
+                return Err(format!("set {}: {} of {} ways are valid, but set_is_full disagrees", set, valid_ways, self.assoc));
+            }
         }
-        return true;
+        Ok(())
     }
 
-    /// Evict the block that was accessed least recently.
+    /// Iterate over every valid block with the set and way it's stored in. In a skewed cache
+    /// the set is the row the block is stored at, which is only its index in way 0.
+    ///
+    /// Returns (set, way, block) for each valid block, set by set and way by way.
+    pub fn iter_resident(&self) -> impl Iterator<Item = (usize, usize, &Block)> {
+        let assoc = self.assoc;
+        self.cache
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.valid)
+            .map(move |(i, block)| (i / assoc, i % assoc, block))
+    }
+
+    /// Get the tags of the valid blocks in a set, from most to least recently used.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) to list, a row in a skewed cache.
+    pub fn resident_tags(&self, index: usize) -> Vec<usize> {
+        let mut blocks: Vec<(usize, &Block)> = self.set(index).iter().enumerate().filter(|(_, block)| block.valid).collect();
+        blocks.sort_by_key(|&(way, _)| self.recency.rank(index, way));
+        blocks.iter().map(|(_, block)| block.tag).collect()
+    }
+
+    /// Count the valid blocks across every set.
+    pub fn occupied_blocks(&self) -> usize {
+        self.cache.iter().filter(|block| block.valid).count()
+    }
+
+    /// Get the fraction of the cache's blocks that are valid, e.g. to sample how quickly the
+    /// cache fills during a run.
+    ///
+    /// Returns 0 for an empty cache.
+    pub fn occupancy(&self) -> f32 {
+        if self.cache.is_empty() {
+            return 0.0;
+        }
+        self.occupied_blocks() as f32 / self.cache.len() as f32
+    }
+
+    /// Evict the block that was accessed least recently, or the block chosen by the cache's
+    /// replacement policy if it isn't LRU.
+    /// If the set has an invalid way it is chosen instead, since nothing needs to be removed,
+    /// and the result is clean and isn't counted as an eviction. Callers normally only get
+    /// here once `set_is_full` says there's no free way.
     ///
     /// ## Arguments
     /// * `index` - The index (or set) to evict a block from.
+    /// * `tag` - The tag of the block needing room, which picks the ways of a skewed set.
     ///
     /// Returns an eviction result, containing the evicted block's address and whether
     /// the block was dirty (meaning it needs to be written back).
-    pub fn evict_lru_block(&mut self, index: usize) -> EvictionResult {
-        let mut block_to_evict_index: usize = 0;
-        let mut lru_value: usize = 0;
-        // find least recently used
-        for i in 0..self.assoc {
-            if self.cache[index][i].lru > lru_value {
-                lru_value = self.cache[index][i].lru;
-                block_to_evict_index = i;
+    pub fn evict_lru_block(&mut self, index: usize, tag: usize) -> EvictionResult {
+        let block_to_evict_index = if self.assoc == 1 {
+            // a direct-mapped set only has one way to evict
+            0
+        } else {
+            // prefer the first invalid way, otherwise find least recently used
+            let partitioned = self.allocation_ways != ALL_WAYS;
+            let invalid_way = self.allocatable_ways().find(|&way| !self.block(index, tag, way).valid);
+            match invalid_way {
+                Some(invalid_way) => invalid_way,
+                None => match self.replacement {
+                    ReplacementPolicy::Rrip => self.rrip_victim(index),
+                    ReplacementPolicy::TreePlru => self.plru_victim(index),
+                    ReplacementPolicy::Random => {
+                        let ways: Vec<usize> = self.allocatable_ways().collect();
+                        ways[(self.rng.next_u64() % ways.len() as u64) as usize]
+                    }
+                    ReplacementPolicy::Lfu => self.lfu_victim(index),
+                    // each way of a skewed set is stamped in its own row, so compare across
+                    // the rows, and a partition can only give up its own ways
+                    ReplacementPolicy::Lru | ReplacementPolicy::Dip if self.skewed || partitioned => self
+                        .allocatable_ways()
+                        .max_by_key(|&way| (self.recency.rank(self.row(index, tag, way), way), usize::MAX - way))
+                        .expect("a way mask selects at least one way"),
+                    ReplacementPolicy::Lru | ReplacementPolicy::Dip | ReplacementPolicy::Clock => {
+                        let way = self.recency.victim(index);
+                        self.recency.on_evict(index, way);
+                        way
+                    }
+                },
             }
-        }
+        };
+        let dirty_sectors = self.dirty_sectors(self.block(index, tag, block_to_evict_index));
         // set the valid bit false so we know we can write to it
-        // TODO: Not sure we're supposed to do this, but it should work for my impl
-        self.cache[index][block_to_evict_index].valid = false;
-        let was_dirty = self.cache[index][block_to_evict_index].dirty;
-        self.cache[index][block_to_evict_index].dirty = false;
+        let block = self.block_mut(index, tag, block_to_evict_index);
+        let was_valid = block.valid;
+        block.valid = false;
+        let was_dirty = was_valid && block.dirty;
+        block.dirty = false;
+        let was_prefetched = was_valid && block.prefetched;
+        block.prefetched = false;
+        let evicted_block_address = block.address;
+
+        // a free way was there for the taking, so nothing was evicted from the set
+        if was_valid {
+            let evicted_tag = self.tag_of(evicted_block_address);
+            if let Some(set_stats) = self.set_stats.get_mut(index) {
+                set_stats.evictions += 1;
+                set_stats.evicted_tags.insert(evicted_tag);
+            }
+        }
         // return the evicted block tag
-        return EvictionResult {
-            evicted_block_address: self.cache[index][block_to_evict_index].address,
+        EvictionResult {
+            evicted_block_address,
             evicted_block_was_dirty: was_dirty,
-        };
+            evicted_block_was_prefetched: was_prefetched,
+            evicted_dirty_sectors: dirty_sectors,
+        }
+    }
+
+    /// Write back every dirty block in the cache, leaving the blocks resident but clean,
+    /// sectors included.
+    ///
+    /// Returns an eviction result for every block that was dirty, with the sectors that need
+    /// writing back, so the caller can write them back as it would an evicted block.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::cache::cache::Cache;
+    ///
+    /// // one set of four 32-byte ways in 8-byte sectors. the block at 0x0 has two dirty
+    /// // sectors, the one at 0x20 one, and the one at 0x40 none
+    /// let mut cache = Cache::new(128, 4, 32)?;
+    /// cache.set_sector_size(8)?;
+    /// cache.install_sector(0, 0, 0x0, 0, true);
+    /// cache.fill_sector(0, 0, 2, true);
+    /// cache.install_sector(0, 1, 0x20, 3, true);
+    /// cache.install_sector(0, 2, 0x40, 0, false);
+    ///
+    /// let flushed = cache.flush();
+    /// let written: Vec<_> = flushed.iter().map(|block| (block.evicted_block_address, block.evicted_dirty_sectors)).collect();
+    /// assert_eq!(written, [(0x0, 2), (0x20, 1)]);
+    ///
+    /// // the blocks stay, clean, so there's nothing left to flush
+    /// assert!((0..3).all(|tag| cache.contains(0, tag)));
+    /// assert!(cache.flush().is_empty());
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn flush(&mut self) -> Vec<EvictionResult> {
+        let flushed = self.cache
+            .iter()
+            .filter(|block| block.valid && block.dirty)
+            .map(|block| EvictionResult {
+                evicted_block_address: block.address,
+                evicted_block_was_dirty: true,
+                evicted_block_was_prefetched: false,
+                evicted_dirty_sectors: self.dirty_sectors(block),
+            })
+            .collect();
+        for block in self.cache.iter_mut() {
+            block.dirty = false;
+            block.sector_dirty = 0;
+        }
+        flushed
     }
-}
\ No newline at end of file
+
+    /// Evict a specific block from the cache, freeing its way for `install`.
+    ///
+    /// ## Arguments
+    /// * `index` - The index (or set) of the block to invalidate.
+    /// * `tag` - The tag of the block to invalidate.
+    ///
+    /// Returns an eviction result for the block if it was in the cache, so a dirty block can
+    /// be written back, or `None` if it wasn't.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::cache::cache::{Cache, HitOrMiss};
+    ///
+    /// // one set of two ways, both filled, the one at 0x0 (tag 0) dirty
+    /// let mut cache = Cache::new(32, 2, 16)?;
+    /// cache.install(0, 0, 0x0);
+    /// cache.write(0, 0);
+    /// cache.install(0, 1, 0x10);
+    /// assert!(cache.set_is_full(0, 0));
+    ///
+    /// let evicted = cache.invalidate(0, 0).unwrap();
+    /// assert!(evicted.evicted_block_was_dirty);
+    /// assert!(cache.invalidate(0, 0).is_none());
+    ///
+    /// // the way is free: the block is gone for reads and writes alike, until it's installed again
+    /// assert!(!cache.contains(0, 0) && !cache.set_is_full(0, 0));
+    /// assert!(cache.write(0, 0) == HitOrMiss::MISS);
+    /// assert!(!cache.contains(0, 0) && !cache.set_is_full(0, 0));
+    /// assert!(cache.read(0, 0) == HitOrMiss::MISS);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn invalidate(&mut self, index: usize, tag: usize) -> Option<EvictionResult> {
+        let way = self.find_way(index, tag)?;
+        let dirty_sectors = self.dirty_sectors(self.block(index, tag, way));
+        let block = self.block_mut(index, tag, way);
+        block.valid = false;
+        let was_dirty = block.dirty;
+        block.dirty = false;
+        let was_prefetched = block.prefetched;
+        block.prefetched = false;
+        Some(EvictionResult {
+            evicted_block_address: block.address,
+            evicted_block_was_dirty: was_dirty,
+            evicted_block_was_prefetched: was_prefetched,
+            evicted_dirty_sectors: dirty_sectors,
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use std::error::Error;
+use std::fmt;
+use crate::cache::replacement::ReplacementPolicy;
+
+/// Reasons a cache geometry can be rejected by `Cache::new`.
+#[derive(Debug, PartialEq)]
+pub enum CacheConfigError {
+    /// The block size was 0.
+    ZeroBlockSize,
+    /// The associativity was 0.
+    ZeroAssoc,
+    /// The block size is not a power of two, so the offset bits can't be derived.
+    BlockSizeNotPowerOfTwo(usize),
+    /// The associativity is not a power of two.
+    AssocNotPowerOfTwo(usize),
+    /// The cache size isn't an exact multiple of `assoc * block_size`.
+    SizeNotMultiple {
+        cache_size: usize,
+        assoc: usize,
+        block_size: usize,
+    },
+    /// The associativity is larger than the number of blocks in the cache, leaving no sets.
+    /// This includes a cache smaller than a single block.
+    AssocTooLarge {
+        assoc: usize,
+        block_size: usize,
+        blocks: usize,
+    },
+    /// The resulting number of sets is not a power of two, so the index bits can't be derived.
+    SetsNotPowerOfTwo(usize),
+    /// The given number of index bits doesn't select exactly one of the sets.
+    IndexBitsMismatch {
+        index_bits: usize,
+        sets: usize,
+    },
+    /// The given number of block offset bits doesn't address exactly one block.
+    OffsetBitsMismatch {
+        offset_bits: usize,
+        block_size: usize,
+    },
+    /// The index and block offset bits take up the whole address, leaving no bits for the tag.
+    NoTagBits {
+        index_bits: usize,
+        block_offset_bits: usize,
+        address_bits: usize,
+    },
+    /// Skewed associativity was combined with a replacement policy other than LRU.
+    SkewedNeedsLru(ReplacementPolicy),
+    /// A snapshot being restored was taken from a cache with a different geometry, given here.
+    SnapshotMismatch {
+        cache_size: usize,
+        assoc: usize,
+        block_size: usize,
+    },
+    /// The sector size isn't a power of two that divides the block into at most 64 sectors.
+    InvalidSectorSize {
+        sector_size: usize,
+        block_size: usize,
+    },
+    /// Page coloring needs a power-of-two page size of at least the block size, and room in
+    /// the address above the page offset for the color bits and the bits that recolor them.
+    InvalidPageColoring {
+        page_size: usize,
+        color_bits: usize,
+        block_size: usize,
+    },
+    /// A way mask selects no ways, or ways past the associativity.
+    InvalidWayMask {
+        ways: u64,
+        assoc: usize,
+    },
+    /// Way partitioning was combined with a replacement policy whose state is shared by the
+    /// whole set, so it can't pick a victim within some of the ways.
+    WayPartitionUnsupported(ReplacementPolicy),
+    /// The fraction of blocks bimodal insertion puts at MRU isn't between 0 and 1.
+    InvalidBimodalProbability(f64),
+}
+
+impl fmt::Display for CacheConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheConfigError::ZeroBlockSize => write!(f, "block size must be greater than 0"),
+            CacheConfigError::ZeroAssoc => write!(f, "associativity must be greater than 0"),
+            CacheConfigError::BlockSizeNotPowerOfTwo(block_size) => {
+                write!(f, "block size {} is not a power of two", block_size)
+            }
+            CacheConfigError::AssocNotPowerOfTwo(assoc) => {
+                write!(f, "associativity {} is not a power of two", assoc)
+            }
+            CacheConfigError::SizeNotMultiple { cache_size, assoc, block_size } => write!(
+                f,
+                "cache size {} is not a multiple of assoc * block size ({} * {})",
+                cache_size, assoc, block_size
+            ),
+            CacheConfigError::AssocTooLarge { assoc, block_size, blocks } => write!(
+                f,
+                "cache too small for associativity {} with {}-byte blocks: it only holds {} blocks",
+                assoc, block_size, blocks
+            ),
+            CacheConfigError::SetsNotPowerOfTwo(sets) => {
+                write!(f, "number of sets {} is not a power of two", sets)
+            }
+            CacheConfigError::IndexBitsMismatch { index_bits, sets } => {
+                write!(f, "{} index bits don't select one of {} sets", index_bits, sets)
+            }
+            CacheConfigError::OffsetBitsMismatch { offset_bits, block_size } => {
+                write!(f, "{} block offset bits don't address a {}-byte block", offset_bits, block_size)
+            }
+            CacheConfigError::NoTagBits { index_bits, block_offset_bits, address_bits } => write!(
+                f,
+                "{} index bits and {} block offset bits leave no tag bits in a {}-bit address",
+                index_bits, block_offset_bits, address_bits
+            ),
+            CacheConfigError::SkewedNeedsLru(replacement) => {
+                write!(f, "skewed associativity only supports LRU replacement, not {:?}", replacement)
+            }
+            CacheConfigError::SnapshotMismatch { cache_size, assoc, block_size } => write!(
+                f,
+                "snapshot of a {}-byte {}-way cache with {}-byte blocks doesn't fit this cache",
+                cache_size, assoc, block_size
+            ),
+            CacheConfigError::InvalidSectorSize { sector_size, block_size } => write!(
+                f,
+                "sector size {} must be a power of two dividing block size {} into at most 64 sectors",
+                sector_size, block_size
+            ),
+            CacheConfigError::InvalidPageColoring { page_size, color_bits, block_size } => write!(
+                f,
+                "page coloring with {} color bits needs a power-of-two page size of at least the block size {} \
+                 and {} address bits above the page offset, not {}-byte pages",
+                color_bits, block_size, 2 * color_bits, page_size
+            ),
+            CacheConfigError::InvalidWayMask { ways, assoc } => {
+                write!(f, "way mask {:x} must select at least one of the {} ways and no others", ways, assoc)
+            }
+            CacheConfigError::WayPartitionUnsupported(replacement) => {
+                write!(f, "way partitioning doesn't support {:?} replacement", replacement)
+            }
+            CacheConfigError::InvalidBimodalProbability(probability) => {
+                write!(f, "bimodal insertion probability {} must be between 0 and 1", probability)
+            }
+        }
+    }
+}
+
+impl Error for CacheConfigError {}
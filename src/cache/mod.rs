@@ -1,2 +1,8 @@
 pub mod block;
-pub mod cache;
\ No newline at end of file
+pub mod error;
+pub mod mshr;
+#[allow(clippy::module_inception)]
+pub mod cache;
+pub mod replacement;
+pub mod victim;
+pub mod write_buffer;
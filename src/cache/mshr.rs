@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+
+/// Miss status holding registers: the blocks L1 is still waiting on. The simulator has no
+/// timing, so a miss counts as outstanding for a fixed number of accesses after it's issued.
+/// Another miss to a block that's still outstanding merges into its register as a secondary
+/// miss, and a new miss that finds every register busy stalls until the oldest one is done.
+pub struct MshrFile {
+    pub entries: usize,
+    /// The number of accesses a miss stays outstanding.
+    pub window: usize,
+    /// The block address of every outstanding miss with the access it completes at, oldest
+    /// first.
+    pub outstanding: VecDeque<(usize, usize)>,
+    /// The number of accesses so far.
+    pub now: usize,
+}
+
+impl MshrFile {
+    /// Creates a new MSHR file with nothing outstanding.
+    ///
+    /// ## Arguments
+    /// * `entries` - The number of misses that can be outstanding at once.
+    /// * `window` - The number of accesses a miss stays outstanding.
+    pub fn new(entries: usize, window: usize) -> Self {
+        Self {
+            entries,
+            window,
+            outstanding: VecDeque::with_capacity(entries),
+            now: 0,
+        }
+    }
+
+    /// Advance by one access, retiring every miss whose window has passed.
+    pub fn tick(&mut self) {
+        self.now += 1;
+        while self.outstanding.front().is_some_and(|&(_, completes_at)| completes_at <= self.now) {
+            self.outstanding.pop_front();
+        }
+    }
+
+    /// Check whether a block is still being fetched.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address to look for.
+    pub fn is_outstanding(&self, block_address: usize) -> bool {
+        self.outstanding.iter().any(|&(outstanding, _)| outstanding == block_address)
+    }
+
+    /// Allocate a register for a primary miss, waiting for the oldest outstanding miss to
+    /// finish first if every register is busy.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address that missed.
+    ///
+    /// Returns whether the miss stalled.
+    pub fn allocate(&mut self, block_address: usize) -> bool {
+        let stalled = self.outstanding.len() >= self.entries;
+        if stalled {
+            self.outstanding.pop_front();
+        }
+        self.outstanding.push_back((block_address, self.now + self.window));
+        stalled
+    }
+}
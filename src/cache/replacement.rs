@@ -0,0 +1,280 @@
+use clap::ValueEnum;
+
+/// How a cache picks which block in a full set to evict.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum ReplacementPolicy {
+    /// Evict the least recently used block.
+    Lru,
+    /// Static re-reference interval prediction (SRRIP). Each block has an M-bit re-reference
+    /// prediction value (RRPV): new blocks start at 2^M - 2, hits reset it to 0, and the victim
+    /// is the first block predicted to be re-referenced furthest away (RRPV of 2^M - 1).
+    Rrip,
+    /// Dynamic insertion policy (DIP). Victims are picked by LRU, but a few sampler sets always
+    /// insert at the MRU position (plain LRU) and a few use bimodal insertion (BIP), which
+    /// inserts at the LRU position except for every 32nd fill. Misses in the sampler sets
+    /// steer a saturating policy selector (PSEL), and the other sets follow whichever
+    /// insertion is missing less.
+    Dip,
+    /// Tree pseudo-LRU. Each set keeps a binary tree of `assoc - 1` bits over its ways; every
+    /// access flips the bits on its path to point away from the accessed way, and the victim
+    /// is found by following the bits from the root.
+    TreePlru,
+    /// Evict a random way. The ways are drawn from a seeded generator, so a run with the
+    /// same seed evicts the same blocks.
+    Random,
+    /// Evict the least frequently used block, counting hits since each block was installed
+    /// and breaking ties by LRU. With aging every counter in the cache is halved after a fixed
+    /// number of accesses, so blocks that were hot long ago don't stay forever.
+    Lfu,
+    /// Clock (second chance), a cheap approximation of LRU. Every way has a reference bit,
+    /// set when the block is installed or hit, and every set a hand. The victim is the first
+    /// way from the hand with its bit clear, clearing the bits the hand passes on the way.
+    Clock,
+}
+
+/// Where a newly installed block starts in its set's replacement order.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum InsertionPosition {
+    /// As the most recently used block, the usual insertion.
+    Mru,
+    /// As the next victim, so a block that is never reused again leaves without pushing out
+    /// anything else. Under RRIP that's the distant RRPV, and under tree-PLRU the tree points
+    /// at the new block.
+    Lru,
+    /// At the LRU position, except for a random fraction of blocks (`bimodal_probability`)
+    /// inserted at MRU, so a working set larger than the cache still keeps part of itself.
+    Bimodal,
+}
+
+/// The recency state of a cache and how it picks a victim, kept outside the blocks so a new
+/// policy can be added by implementing this rather than editing `Cache`. Sets and ways are
+/// numbered as in `Cache::cache`, and a set is a row in a skewed cache.
+pub trait Replacement: Send {
+    /// Update the state after a hit on a way.
+    ///
+    /// ## Arguments
+    /// * `set` - The set that was hit.
+    /// * `way` - The way that was hit.
+    fn on_access(&mut self, set: usize, way: usize);
+
+    /// Update the state after a block was installed in a way.
+    ///
+    /// ## Arguments
+    /// * `set` - The set the block was installed in.
+    /// * `way` - The way the block was installed in.
+    fn on_install(&mut self, set: usize, way: usize);
+
+    /// Make a way the next victim of its set.
+    ///
+    /// ## Arguments
+    /// * `set` - The set to update.
+    /// * `way` - The way to demote.
+    fn demote(&mut self, set: usize, way: usize);
+
+    /// Pick the way to evict from a full set.
+    ///
+    /// ## Arguments
+    /// * `set` - The set to pick a victim from.
+    fn victim(&self, set: usize) -> usize;
+
+    /// Update the state after the way `victim` picked was evicted, before a new block is
+    /// installed in it.
+    ///
+    /// ## Arguments
+    /// * `set` - The set the way was evicted from.
+    /// * `way` - The way that was evicted.
+    fn on_evict(&mut self, _set: usize, _way: usize) {}
+
+    /// Get where a way stands in its set's replacement order, larger meaning closer to
+    /// eviction.
+    ///
+    /// ## Arguments
+    /// * `set` - The set of the way.
+    /// * `way` - The way to look up.
+    fn rank(&self, set: usize, way: usize) -> usize;
+
+    /// Overwrite where a way stands in its set's replacement order, e.g. when restoring a
+    /// snapshot.
+    ///
+    /// ## Arguments
+    /// * `set` - The set of the way.
+    /// * `way` - The way to update.
+    /// * `rank` - The new rank, as returned by `rank`.
+    fn set_rank(&mut self, set: usize, way: usize, rank: usize);
+
+    /// Go back to the initial state of an empty cache.
+    fn reset(&mut self);
+}
+
+/// Least recently used replacement. The ranks within a set are always a permutation of
+/// `0..assoc`, with 0 being the most recently used way and `assoc - 1` the least recently
+/// used, so there's always a single unambiguous victim.
+pub struct Lru {
+    assoc: usize,
+    /// The rank of every way, stored set by set like the blocks.
+    ranks: Vec<usize>,
+}
+
+impl Lru {
+    /// Creates the recency state of an empty cache, where way `j` of every set has rank `j`.
+    ///
+    /// ## Arguments
+    /// * `sets` - The number of sets in the cache.
+    /// * `assoc` - The associativity of the cache.
+    pub fn new(sets: usize, assoc: usize) -> Self {
+        Self {
+            assoc,
+            ranks: (0..sets * assoc).map(|i| i % assoc).collect(),
+        }
+    }
+
+    /// Get the ranks of a set for modification.
+    ///
+    /// ## Arguments
+    /// * `set` - The set to get.
+    fn set_mut(&mut self, set: usize) -> &mut [usize] {
+        &mut self.ranks[set * self.assoc..(set + 1) * self.assoc]
+    }
+}
+
+impl Replacement for Lru {
+    fn on_access(&mut self, set: usize, way: usize) {
+        // move the way to 0, shifting every way that was more recent than it down by one
+        let ranks = self.set_mut(set);
+        let old_rank = ranks[way];
+        for rank in ranks.iter_mut() {
+            if *rank < old_rank {
+                *rank += 1;
+            }
+        }
+        ranks[way] = 0;
+    }
+
+    fn on_install(&mut self, set: usize, way: usize) {
+        self.on_access(set, way);
+    }
+
+    fn demote(&mut self, set: usize, way: usize) {
+        // move the way to the end, shifting every way that was less recent than it up by one
+        let assoc = self.assoc;
+        let ranks = self.set_mut(set);
+        let old_rank = ranks[way];
+        for rank in ranks.iter_mut() {
+            if *rank > old_rank {
+                *rank -= 1;
+            }
+        }
+        ranks[way] = assoc - 1;
+    }
+
+    fn victim(&self, set: usize) -> usize {
+        let ranks = &self.ranks[set * self.assoc..(set + 1) * self.assoc];
+        let mut lru_way = 0;
+        for way in 1..self.assoc {
+            if ranks[way] > ranks[lru_way] {
+                lru_way = way;
+            }
+        }
+        lru_way
+    }
+
+    fn rank(&self, set: usize, way: usize) -> usize {
+        self.ranks[set * self.assoc + way]
+    }
+
+    fn set_rank(&mut self, set: usize, way: usize, rank: usize) {
+        self.ranks[set * self.assoc + way] = rank;
+    }
+
+    fn reset(&mut self) {
+        for (i, rank) in self.ranks.iter_mut().enumerate() {
+            *rank = i % self.assoc;
+        }
+    }
+}
+
+/// Clock (second chance) replacement. Each way has a reference bit and each set a hand
+/// pointing at the next way to consider. Eviction sweeps the hand forward, giving every
+/// referenced way a second chance by clearing its bit, and takes the first way whose bit was
+/// already clear.
+pub struct Clock {
+    assoc: usize,
+    /// The reference bit of every way, stored set by set like the blocks.
+    referenced: Vec<bool>,
+    /// The way each set's hand points at.
+    hands: Vec<usize>,
+}
+
+impl Clock {
+    /// Creates the state of an empty cache, with every bit clear and every hand at way 0.
+    ///
+    /// ## Arguments
+    /// * `sets` - The number of sets in the cache.
+    /// * `assoc` - The associativity of the cache.
+    pub fn new(sets: usize, assoc: usize) -> Self {
+        Self {
+            assoc,
+            referenced: vec![false; sets * assoc],
+            hands: vec![0; sets],
+        }
+    }
+}
+
+impl Replacement for Clock {
+    fn on_access(&mut self, set: usize, way: usize) {
+        self.referenced[set * self.assoc + way] = true;
+    }
+
+    fn on_install(&mut self, set: usize, way: usize) {
+        self.on_access(set, way);
+    }
+
+    fn demote(&mut self, set: usize, way: usize) {
+        self.referenced[set * self.assoc + way] = false;
+    }
+
+    fn victim(&self, set: usize) -> usize {
+        // the first clear way from the hand, or the hand itself once a whole turn has cleared
+        // every bit
+        let hand = self.hands[set];
+        (0..self.assoc)
+            .map(|distance| (hand + distance) % self.assoc)
+            .find(|&way| !self.referenced[set * self.assoc + way])
+            .unwrap_or(hand)
+    }
+
+    fn on_evict(&mut self, set: usize, way: usize) {
+        // every way the hand passed on the way to the victim used up its second chance, and a
+        // victim that was still referenced means the hand went all the way round
+        let hand = self.hands[set];
+        let passed = if self.referenced[set * self.assoc + way] {
+            self.assoc
+        } else {
+            (way + self.assoc - hand) % self.assoc
+        };
+        for distance in 0..passed {
+            self.referenced[set * self.assoc + (hand + distance) % self.assoc] = false;
+        }
+        self.hands[set] = (way + 1) % self.assoc;
+    }
+
+    /// Ways are ranked in the order the hand would evict them: clear ways from the hand
+    /// onwards, then referenced ways from the hand onwards. That ranking also records the
+    /// bit and where the hand is, so `set_rank` can restore both.
+    fn rank(&self, set: usize, way: usize) -> usize {
+        let distance = (way + self.assoc - self.hands[set]) % self.assoc;
+        let order = self.referenced[set * self.assoc + way] as usize * self.assoc + distance;
+        2 * self.assoc - 1 - order
+    }
+
+    fn set_rank(&mut self, set: usize, way: usize, rank: usize) {
+        let order = 2 * self.assoc - 1 - rank;
+        self.referenced[set * self.assoc + way] = order >= self.assoc;
+        self.hands[set] = (way + self.assoc - order % self.assoc) % self.assoc;
+    }
+
+    fn reset(&mut self) {
+        self.referenced.fill(false);
+        self.hands.fill(0);
+    }
+}
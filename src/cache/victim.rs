@@ -0,0 +1,109 @@
+use crate::cache::block::Block;
+
+/// A small fully-associative buffer that catches blocks evicted from L1.
+/// Blocks are matched on their block address (the address without the offset bits),
+/// which is stored in the block's `tag`.
+pub struct VictimCache {
+    pub capacity: usize,
+    pub blocks: Vec<Block>,
+}
+
+impl VictimCache {
+    /// Creates a new victim cache holding up to `capacity` blocks.
+    /// A capacity of 0 disables the victim cache.
+    ///
+    /// ## Arguments
+    /// * `capacity` - The number of blocks the victim cache can hold.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // without an l2 the victim cache sits between l1 and main memory. 0x100 and 0x140
+    /// // map to the same set of a direct-mapped l1, so alternating between them always misses
+    /// let run = |capacity| -> Result<_, cache_simulator::cache::error::CacheConfigError> {
+    ///     let l1 = Cache::new(64, 1, 16)?;
+    ///     let mut simulator = Simulator::new(l1, None, VictimCache::new(capacity), Inclusion::Nine);
+    ///     for address in [0x100, 0x140].repeat(8) {
+    ///         simulator.access(AccessType::Read, address);
+    ///     }
+    ///     Ok(simulator.stats)
+    /// };
+    /// let (alone, with_victim) = (run(0)?, run(1)?);
+    ///
+    /// // every access still misses in l1, but all but the first two are caught by the
+    /// // victim cache instead of going to memory
+    /// assert_eq!((alone.l1_read_misses, with_victim.l1_read_misses), (16, 16));
+    /// assert_eq!((alone.victim_hits, with_victim.victim_hits), (0, 14));
+    /// assert_eq!((alone.total_memory_traffic, with_victim.total_memory_traffic), (16, 2));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            blocks: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns whether the victim cache can hold any blocks.
+    pub fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    /// Check whether a block is in the victim cache without removing it.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address of the desired block.
+    pub fn contains(&self, block_address: usize) -> bool {
+        self.blocks
+            .iter()
+            .any(|block| block.valid && block.tag == block_address)
+    }
+
+    /// Remove a block from the victim cache if it's present.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address of the desired block.
+    ///
+    /// Returns the block if it was in the victim cache (hit), or `None` (miss).
+    pub fn take(&mut self, block_address: usize) -> Option<Block> {
+        let position = self.blocks
+            .iter()
+            .position(|block| block.valid && block.tag == block_address)?;
+        let block = self.blocks.remove(position);
+        // close the gap left in the recency order
+        for other in self.blocks.iter_mut() {
+            if other.lru > block.lru {
+                other.lru -= 1;
+            }
+        }
+        Some(block)
+    }
+
+    /// Insert a block as the most recently used entry.
+    ///
+    /// ## Arguments
+    /// * `block` - The block evicted from L1, with its block address as the tag.
+    ///
+    /// Returns the least recently used block if one had to be evicted to make room.
+    pub fn insert(&mut self, block: Block) -> Option<Block> {
+        let mut evicted = None;
+        if self.blocks.len() == self.capacity {
+            let mut lru_position: usize = 0;
+            for i in 0..self.blocks.len() {
+                if self.blocks[i].lru > self.blocks[lru_position].lru {
+                    lru_position = i;
+                }
+            }
+            evicted = Some(self.blocks.remove(lru_position));
+        }
+        for other in self.blocks.iter_mut() {
+            other.lru += 1;
+        }
+        self.blocks.push(Block { lru: 0, ..block });
+        evicted
+    }
+}
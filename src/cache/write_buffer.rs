@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+
+/// A bounded FIFO of writebacks waiting to reach main memory.
+/// One entry drains every `drain_interval` accesses; a writeback that finds the buffer full
+/// stalls until the oldest entry has drained.
+pub struct WriteBuffer {
+    pub depth: usize,
+    pub drain_interval: usize,
+    /// The size in bytes of every pending writeback, oldest first.
+    pub pending: VecDeque<usize>,
+    pub accesses_since_drain: usize,
+}
+
+impl WriteBuffer {
+    /// Creates a new, empty write buffer.
+    ///
+    /// ## Arguments
+    /// * `depth` - The number of writebacks the buffer can hold.
+    /// * `drain_interval` - The number of accesses it takes to drain one writeback.
+    pub fn new(depth: usize, drain_interval: usize) -> Self {
+        Self {
+            depth,
+            drain_interval,
+            pending: VecDeque::with_capacity(depth),
+            accesses_since_drain: 0,
+        }
+    }
+
+    /// Check whether another writeback would have to stall.
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.depth
+    }
+
+    /// Queue a writeback. The caller has to make room first if the buffer is full.
+    ///
+    /// ## Arguments
+    /// * `bytes` - The size of the writeback.
+    pub fn push(&mut self, bytes: usize) {
+        self.pending.push_back(bytes);
+    }
+
+    /// Remove the oldest writeback, e.g. when it reaches memory.
+    ///
+    /// Returns its size in bytes, or `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<usize> {
+        self.pending.pop_front()
+    }
+
+    /// Advance the buffer by one access, draining the oldest writeback once the drain
+    /// interval has passed.
+    ///
+    /// Returns the size in bytes of the writeback that drained, if any.
+    pub fn tick(&mut self) -> Option<usize> {
+        if self.pending.is_empty() {
+            self.accesses_since_drain = 0;
+            return None;
+        }
+        self.accesses_since_drain += 1;
+        if self.accesses_since_drain < self.drain_interval {
+            return None;
+        }
+        self.accesses_since_drain = 0;
+        self.pop()
+    }
+}
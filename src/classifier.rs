@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use crate::cache::cache::Cache;
+use crate::cache::cache::HitOrMiss::MISS;
+use crate::cache::error::CacheConfigError;
+
+/// The three kinds of cache misses.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MissKind {
+    /// The first ever access to a block.
+    Compulsory,
+    /// A miss that a fully-associative cache of the same size would also have.
+    Capacity,
+    /// A miss caused only by the limited associativity of the cache.
+    Conflict,
+}
+
+/// Classifies the misses of a cache into compulsory, capacity, and conflict misses by
+/// remembering every block ever seen and running a fully-associative LRU cache of the
+/// same total size in parallel.
+pub struct MissClassifier {
+    pub seen: HashSet<usize>,
+    pub shadow: Cache,
+}
+
+impl MissClassifier {
+    /// Creates a new classifier for a cache with the given size and block size.
+    ///
+    /// ## Arguments
+    /// * `cache_size` - The total size of the cache being classified.
+    /// * `block_size` - The size of the blocks in the cache being classified.
+    pub fn new(cache_size: usize, block_size: usize) -> Result<Self, CacheConfigError> {
+        Ok(Self {
+            seen: HashSet::new(),
+            shadow: Cache::new(cache_size, cache_size / block_size, block_size)?,
+        })
+    }
+
+    /// Record an access, updating the set of seen blocks and the shadow cache.
+    /// This must be called for every access, hit or miss, to keep the shadow cache accurate.
+    ///
+    /// ## Arguments
+    /// * `address` - The address being accessed.
+    ///
+    /// Returns the kind of miss this access is if the real cache misses on it.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::classifier::{MissClassifier, MissKind};
+    ///
+    /// // a 2-block cache, e.g. direct-mapped with 0x0 and 0x20 in the same set, misses on
+    /// // every one of these accesses
+    /// let mut classifier = MissClassifier::new(32, 16)?;
+    /// let kinds: Vec<_> = [0x0, 0x20, 0x0, 0x10, 0x20].map(|address| classifier.observe(address)).into();
+    /// assert_eq!(
+    ///     kinds,
+    ///     [
+    ///         // the first touch of each block
+    ///         MissKind::Compulsory,
+    ///         MissKind::Compulsory,
+    ///         // the fully-associative shadow still holds both blocks
+    ///         MissKind::Conflict,
+    ///         MissKind::Compulsory,
+    ///         // but a third block pushed 0x20 out of the shadow too
+    ///         MissKind::Capacity,
+    ///     ]
+    /// );
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn observe(&mut self, address: usize) -> MissKind {
+        let block_address = self.shadow.block_address_of(address);
+        let first_touch = self.seen.insert(block_address);
+
+        // the shadow cache has a single set, so the whole block address is the tag
+        let shadow_missed = self.shadow.read(0, block_address) == MISS;
+        if shadow_missed {
+            if self.shadow.set_is_full(0, block_address) {
+                self.shadow.evict_lru_block(0, block_address);
+            }
+            self.shadow.install(0, block_address, address);
+        }
+
+        if first_touch {
+            MissKind::Compulsory
+        } else if shadow_missed {
+            MissKind::Capacity
+        } else {
+            MissKind::Conflict
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashSet;
+use crate::access::AccessType;
+use crate::cache::cache::{Cache, EvictionResult, ALL_WAYS};
+use crate::cache::cache::HitOrMiss::{HIT, MISS};
+use crate::statistics::Statistics;
+
+/// The MSI state of a block in one core's private L1. The state is kept in the block's own
+/// bits: a valid dirty block is Modified, a valid clean block is Shared, and a block that
+/// isn't resident is Invalid. At most one core can hold a block Modified.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MsiState {
+    Modified,
+    Shared,
+    Invalid,
+}
+
+/// Several cores, each with a private L1, sharing one L2 (or main memory if there is no
+/// L2), kept coherent by an MSI protocol. A write takes a block away from every other core,
+/// and a read of a block another core has modified makes that core write it back and
+/// downgrade to Shared. L2 is non-inclusive, so its evictions never touch the L1s.
+///
+/// L1 counters in `stats` are summed over every core.
+pub struct CoherenceController {
+    pub l1s: Vec<Cache>,
+    /// The shared L2, or `None` if the L1s are backed by main memory alone.
+    pub l2: Option<Cache>,
+    pub stats: Statistics,
+    /// The blocks each core lost to another core's write, so the next miss on one of them is
+    /// a coherence miss rather than an ordinary one.
+    invalidated: Vec<HashSet<usize>>,
+    /// The L2 ways each core may allocate into, way `w` at bit `w`, or empty if L2 isn't
+    /// partitioned.
+    pub l2_partitions: Vec<u64>,
+}
+
+impl CoherenceController {
+    /// Creates a controller over empty caches.
+    ///
+    /// ## Arguments
+    /// * `l1s` - One private L1 per core, all with the same block size.
+    /// * `l2` - The shared L2, or `None` for none.
+    ///
+    /// ## Throws
+    /// This function will panic if there are no cores or the block sizes differ.
+    pub fn new(l1s: Vec<Cache>, l2: Option<Cache>) -> Self {
+        assert!(!l1s.is_empty(), "A coherent hierarchy needs at least one core.");
+        let block_size = l1s[0].block_size;
+        assert!(
+            l1s.iter().all(|l1| l1.block_size == block_size) && l2.as_ref().is_none_or(|l2| l2.block_size == block_size),
+            "Every cache in a coherent hierarchy needs the same block size."
+        );
+        let mut stats = Statistics::new();
+        stats.l2_enabled = l2.is_some();
+        stats.coherence = true;
+        Self {
+            invalidated: vec![HashSet::new(); l1s.len()],
+            l1s,
+            l2,
+            stats,
+            l2_partitions: vec![],
+        }
+    }
+
+    /// Partition the shared L2 by way, so the blocks each core brings into L2 only replace
+    /// blocks in its own ways. Every core can still hit on a block in any way. The demand L2
+    /// misses of each core are counted separately.
+    ///
+    /// ## Arguments
+    /// * `partitions` - The ways each core may allocate into, in core order, way `w` at
+    ///   bit `w`. The masks may overlap.
+    ///
+    /// Returns a message saying why the partitions can't be used.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::coherence::CoherenceController;
+    ///
+    /// // two cores with one-block l1s share a single 4-way l2 set. core 0 alternates between
+    /// // two blocks, while core 1 streams through two new blocks for each of core 0's accesses
+    /// let run = |partitions: Option<Vec<u64>>| -> Result<_, Box<dyn std::error::Error>> {
+    ///     let l1s = vec![Cache::new(16, 1, 16)?, Cache::new(16, 1, 16)?];
+    ///     let mut controller = CoherenceController::new(l1s, Some(Cache::new(64, 4, 16)?));
+    ///     if let Some(partitions) = partitions {
+    ///         controller.partition_l2(partitions)?;
+    ///     }
+    ///     for round in 0..8 {
+    ///         controller.access(0, AccessType::Read, if round % 2 == 0 { 0x100 } else { 0x200 });
+    ///         controller.access(1, AccessType::Read, 0x1000 + round * 0x20);
+    ///         controller.access(1, AccessType::Read, 0x1010 + round * 0x20);
+    ///     }
+    ///     Ok(controller.stats)
+    /// };
+    ///
+    /// // sharing l2, the stream pushes out core 0's blocks before they come back around, but
+    /// // with two ways of its own core 0 only takes its two compulsory misses
+    /// assert_eq!(run(None)?.l2_read_misses, 24);
+    /// let partitioned = run(Some(vec![0b0011, 0b1100]))?;
+    /// assert_eq!(partitioned.core_l2_read_misses, [2, 16]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn partition_l2(&mut self, partitions: Vec<u64>) -> Result<(), String> {
+        let Some(l2) = &self.l2 else {
+            return Err("Partitioning L2 needs an L2.".to_string());
+        };
+        if partitions.len() != self.l1s.len() {
+            return Err(format!("{} L2 partitions given for {} cores.", partitions.len(), self.l1s.len()));
+        }
+        for &ways in &partitions {
+            l2.check_way_mask(ways).map_err(|e| format!("Invalid L2 partition: {}", e))?;
+        }
+        self.stats.core_l2_read_misses = vec![0; partitions.len()];
+        self.l2_partitions = partitions;
+        Ok(())
+    }
+
+    /// Get the MSI state of a block in a core's L1.
+    ///
+    /// ## Arguments
+    /// * `core` - The core whose L1 to look in.
+    /// * `address` - Any address within the block.
+    pub fn state(&self, core: usize, address: usize) -> MsiState {
+        let l1 = &self.l1s[core];
+        let (index, tag) = (l1.index_of(address), l1.tag_of(address));
+        if !l1.contains(index, tag) {
+            MsiState::Invalid
+        } else if l1.is_dirty(index, tag) {
+            MsiState::Modified
+        } else {
+            MsiState::Shared
+        }
+    }
+
+    /// Simulate a single access by one core, updating the statistics. Instruction fetches
+    /// are reads as far as coherence goes.
+    ///
+    /// ## Arguments
+    /// * `core` - The core making the access.
+    /// * `access_type` - Whether the access is a read, a write, or an instruction fetch.
+    /// * `address` - The address being accessed.
+    ///
+    /// ## Throws
+    /// This function will panic if `core` isn't one of the simulated cores.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType::{Read, Write};
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::coherence::{CoherenceController, MsiState};
+    ///
+    /// // two cores with a single 2-way l1 set each, sharing a 256-byte l2
+    /// let l1s = vec![Cache::new(32, 2, 16)?, Cache::new(32, 2, 16)?];
+    /// let mut controller = CoherenceController::new(l1s, Some(Cache::new(256, 2, 16)?));
+    ///
+    /// // both cores read 0x100 and share it, then core 0 writes it and takes it from core 1
+    /// controller.access(0, Read, 0x100);
+    /// controller.access(1, Read, 0x100);
+    /// controller.access(0, Write, 0x100);
+    /// assert_eq!((controller.state(0, 0x100), controller.state(1, 0x100)), (MsiState::Modified, MsiState::Invalid));
+    ///
+    /// // core 1's read is a coherence miss that makes core 0 write back and downgrade
+    /// controller.access(1, Read, 0x100);
+    /// assert_eq!((controller.state(0, 0x100), controller.state(1, 0x100)), (MsiState::Shared, MsiState::Shared));
+    ///
+    /// // the same again the other way around
+    /// controller.access(1, Write, 0x100);
+    /// controller.access(0, Read, 0x100);
+    /// assert_eq!((controller.state(0, 0x100), controller.state(1, 0x100)), (MsiState::Shared, MsiState::Shared));
+    ///
+    /// // core 0 pushes its clean copy out of l1 with blocks in other l2 sets, and l2 keeps
+    /// // the data last written back
+    /// controller.access(0, Read, 0x210);
+    /// controller.access(0, Read, 0x320);
+    /// assert_eq!(controller.state(0, 0x100), MsiState::Invalid);
+    /// let stats = &controller.stats;
+    /// assert_eq!((stats.coherence_invalidations, stats.coherence_downgrades, stats.coherence_misses), (2, 2, 2));
+    /// assert_eq!((stats.l1_write_backs, stats.l2_writes), (2, 2));
+    /// let l2 = controller.l2.as_ref().unwrap();
+    /// assert!(l2.is_dirty(l2.index_of(0x100), l2.tag_of(0x100)));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn access(&mut self, core: usize, access_type: AccessType, address: usize) {
+        assert!(core < self.l1s.len(), "Record for core {} but only {} cores are simulated.", core, self.l1s.len());
+        let write = access_type == AccessType::Write;
+        let state = self.state(core, address);
+        let l1 = &mut self.l1s[core];
+        let (index, tag) = (l1.index_of(address), l1.tag_of(address));
+        let outcome = l1.access(index, tag, address, write);
+        self.count_l1_access(access_type);
+        if outcome.hit_or_miss == HIT {
+            // a write to a shared block has to take it away from every other core first
+            if write && state == MsiState::Shared {
+                self.invalidate_others(core, address);
+            }
+            return;
+        }
+
+        // we missed in this core's l1
+        match access_type {
+            AccessType::Read => self.stats.l1_read_misses += 1,
+            AccessType::Write => self.stats.l1_write_misses += 1,
+            AccessType::InstructionFetch => self.stats.l1_instruction_fetch_misses += 1,
+        }
+        let block_address = self.l1s[core].block_address_of(address);
+        if self.invalidated[core].remove(&block_address) {
+            self.stats.coherence_misses += 1;
+        }
+        if write {
+            self.invalidate_others(core, address);
+        } else {
+            self.downgrade_others(core, address);
+        }
+
+        // any modified copy is in l2 by now, so fetch from there
+        if let Some(evicted) = outcome.evicted {
+            self.retire_l1_block(core, evicted);
+        }
+        self.fetch(core, address);
+    }
+
+    /// Invalidate a block in every L1 but one, writing back a modified copy first.
+    ///
+    /// ## Arguments
+    /// * `core` - The core that is about to write the block.
+    /// * `address` - Any address within the block.
+    fn invalidate_others(&mut self, core: usize, address: usize) {
+        for other in (0..self.l1s.len()).filter(|&other| other != core) {
+            let l1 = &mut self.l1s[other];
+            let (index, tag) = (l1.index_of(address), l1.tag_of(address));
+            if let Some(evicted) = l1.invalidate(index, tag) {
+                self.stats.coherence_invalidations += 1;
+                self.invalidated[other].insert(l1.block_address_of(address));
+                if evicted.evicted_block_was_dirty {
+                    self.write_back_to_l2(other, evicted.evicted_block_address);
+                }
+            }
+        }
+    }
+
+    /// Make any other core holding a block Modified write it back and keep it Shared.
+    ///
+    /// ## Arguments
+    /// * `core` - The core that is about to read the block.
+    /// * `address` - Any address within the block.
+    fn downgrade_others(&mut self, core: usize, address: usize) {
+        for other in (0..self.l1s.len()).filter(|&other| other != core) {
+            if self.state(other, address) == MsiState::Modified {
+                let l1 = &mut self.l1s[other];
+                l1.mark_clean(l1.index_of(address), l1.tag_of(address));
+                self.stats.coherence_downgrades += 1;
+                self.write_back_to_l2(other, address);
+            }
+        }
+    }
+
+    /// Fetch a block that missed in L1 from L2, installing it in L2 from main memory if L2
+    /// misses too, or straight from main memory if there is no L2.
+    ///
+    /// ## Arguments
+    /// * `core` - The core the block is fetched for.
+    /// * `address` - The address being accessed.
+    fn fetch(&mut self, core: usize, address: usize) {
+        let ways = self.l2_partitions.get(core).copied().unwrap_or(ALL_WAYS);
+        let Some(l2) = self.l2.as_mut() else {
+            self.count_memory_traffic();
+            return;
+        };
+        let (l2_index, l2_tag) = (l2.index_of(address), l2.tag_of(address));
+        l2.allocation_ways = ways;
+        let outcome = l2.access(l2_index, l2_tag, address, false);
+        l2.allocation_ways = ALL_WAYS;
+        self.stats.l2_reads += 1;
+        if outcome.hit_or_miss == MISS {
+            self.stats.l2_read_misses += 1;
+            if let Some(misses) = self.stats.core_l2_read_misses.get_mut(core) {
+                *misses += 1;
+            }
+            if let Some(evicted) = outcome.evicted {
+                self.retire_l2_block(evicted);
+            }
+            self.count_memory_traffic();
+        }
+    }
+
+    /// Count a block evicted from a core's L1 to make room, writing it back if it was
+    /// Modified.
+    ///
+    /// ## Arguments
+    /// * `core` - The core whose L1 the block was evicted from.
+    /// * `evicted` - The evicted block.
+    fn retire_l1_block(&mut self, core: usize, evicted: EvictionResult) {
+        self.stats.l1_evictions += 1;
+        if evicted.evicted_block_was_dirty {
+            self.write_back_to_l2(core, evicted.evicted_block_address);
+        } else {
+            self.stats.l1_clean_evictions += 1;
+        }
+    }
+
+    /// Write a modified L1 block back to L2, or to main memory if there is no L2. A block
+    /// that misses in a partitioned L2 goes in the ways of the core writing it back.
+    ///
+    /// ## Arguments
+    /// * `core` - The core whose L1 held the block.
+    /// * `address` - The address of the block.
+    fn write_back_to_l2(&mut self, core: usize, address: usize) {
+        self.stats.l1_write_backs += 1;
+        let ways = self.l2_partitions.get(core).copied().unwrap_or(ALL_WAYS);
+        let Some(l2) = self.l2.as_mut() else {
+            self.stats.write_back_bytes += self.l1s[0].block_size;
+            self.count_memory_traffic();
+            return;
+        };
+        let (l2_index, l2_tag) = (l2.index_of(address), l2.tag_of(address));
+        // the whole block is being written, so there's nothing to fetch on a miss
+        l2.allocation_ways = ways;
+        let outcome = l2.access(l2_index, l2_tag, address, true);
+        l2.allocation_ways = ALL_WAYS;
+        self.stats.l2_writes += 1;
+        if outcome.hit_or_miss == MISS {
+            self.stats.l2_write_misses += 1;
+            if let Some(evicted) = outcome.evicted {
+                self.retire_l2_block(evicted);
+            }
+        }
+    }
+
+    /// Count a block evicted from L2 to make room, writing it back to main memory if it was
+    /// dirty.
+    ///
+    /// ## Arguments
+    /// * `evicted` - The evicted block.
+    fn retire_l2_block(&mut self, evicted: EvictionResult) {
+        self.stats.l2_evictions += 1;
+        if evicted.evicted_block_was_dirty {
+            self.stats.l2_write_backs += 1;
+            self.stats.write_back_bytes += self.l1s[0].block_size;
+            self.count_memory_traffic();
+        } else {
+            self.stats.l2_clean_evictions += 1;
+        }
+    }
+
+    /// Write back every dirty block, L1s first, as happens when a program finishes.
+    pub fn flush(&mut self) {
+        for core in 0..self.l1s.len() {
+            for l1_flushed in self.l1s[core].flush() {
+                self.write_back_to_l2(core, l1_flushed.evicted_block_address);
+            }
+        }
+        let Some(l2) = self.l2.as_mut() else {
+            return;
+        };
+        let l2_write_backs = l2.flush().len();
+        self.stats.l2_write_backs += l2_write_backs;
+        self.stats.total_memory_traffic += l2_write_backs;
+        self.stats.memory_traffic_bytes += l2_write_backs * l2.block_size;
+        self.stats.write_back_bytes += l2_write_backs * l2.block_size;
+    }
+
+    /// Stop warming up the caches: zero the statistics while keeping everything the caches
+    /// hold.
+    pub fn end_warmup(&mut self) {
+        self.stats.reset();
+    }
+
+    /// Count one block transferred to or from main memory.
+    fn count_memory_traffic(&mut self) {
+        self.stats.total_memory_traffic += 1;
+        self.stats.memory_traffic_bytes += self.l1s[0].block_size;
+    }
+
+    /// Count a completed access to an L1.
+    fn count_l1_access(&mut self, access_type: AccessType) {
+        self.stats.total_accesses += 1;
+        match access_type {
+            AccessType::Read => self.stats.l1_reads += 1,
+            AccessType::Write => self.stats.l1_writes += 1,
+            AccessType::InstructionFetch => self.stats.l1_instruction_fetches += 1,
+        }
+    }
+}
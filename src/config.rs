@@ -0,0 +1,300 @@
+use crate::cache::cache::{Cache, DEFAULT_BIMODAL_PROBABILITY};
+use crate::cache::replacement::{InsertionPosition, ReplacementPolicy};
+use crate::cache::victim::VictimCache;
+use crate::prefetch::PrefetchPlacement;
+use crate::regions::{MemoryRegion, MemoryRegions};
+use crate::simulator::{Inclusion, Simulator};
+use crate::statistics::EnergyModel;
+use crate::streaming::StreamingAction;
+use crate::sweep::Geometry;
+use crate::tlb::Tlb;
+
+/// Everything that affects the measurements of a simulation: the geometry, the options of
+/// each level, the optional structures around them, and how the trace is run. Options that
+/// only add output, like verbose logging, aren't part of it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Config {
+    pub geometry: Geometry,
+    pub replacement: ReplacementPolicy,
+    pub rrip_bits: usize,
+    /// The seed random replacement and bimodal insertion draw with, the same for every level.
+    pub seed: u64,
+    /// The number of accesses between halvings of the LFU counters, 0 for no aging.
+    pub lfu_aging_interval: usize,
+    pub skewed: bool,
+    /// Where each level inserts new blocks in its replacement order.
+    pub l1_insertion: InsertionPosition,
+    pub l2_insertion: InsertionPosition,
+    /// The fraction of blocks a level with bimodal insertion inserts at MRU.
+    pub bimodal_probability: f64,
+    /// The number of page number bits recolored before indexing, with `page_size` pages.
+    pub page_color_bits: usize,
+    /// The number of L1 index bits, checked against the geometry instead of derived from it.
+    pub index_bits: Option<usize>,
+    /// The number of block offset bits, checked against the block size instead of derived.
+    pub offset_bits: Option<usize>,
+    /// The L1 sector size in bytes, if L1 is sectored.
+    pub sector_size: Option<usize>,
+    /// Whether L1 write misses allocate without fetching (write-validate).
+    pub write_validate: bool,
+    /// The number of blocks in the victim cache, 0 for none.
+    pub victim_size: usize,
+    pub inclusion: Inclusion,
+    pub l1_latency: usize,
+    pub l2_latency: usize,
+    pub memory_latency: usize,
+    /// The regions of main memory slower than the rest, empty for uniform memory.
+    pub memory_regions: Vec<MemoryRegion>,
+    /// The per-event energies, if the dynamic energy should be estimated.
+    pub energy: Option<EnergyModel>,
+    pub classify_misses: bool,
+    /// Whether L1 and L2 are shadowed by fully-associative caches to count conflict misses.
+    pub conflict_shadow: bool,
+    pub stride_prefetch: bool,
+    /// The number of blocks prefetched into L1 after each L1 miss, 0 for none.
+    pub l1_prefetch_degree: usize,
+    /// The number of blocks prefetched into L2 after each L2 demand miss, 0 for none.
+    pub l2_prefetch_degree: usize,
+    /// The level the stride and L1 next-line prefetchers install their blocks in.
+    pub prefetch_placement: PrefetchPlacement,
+    /// The number of consecutive L1 misses that signal a stream, if streams are detected.
+    pub streaming_threshold: Option<usize>,
+    pub streaming_action: StreamingAction,
+    /// The number of stream buffers beside L1, 0 for none.
+    pub stream_buffers: usize,
+    pub stream_buffer_depth: usize,
+    /// The number of entries in the write buffer, 0 for none.
+    pub write_buffer_depth: usize,
+    pub write_buffer_drain_interval: usize,
+    /// The number of L1 banks to count conflicts between, 0 for none.
+    pub banks: usize,
+    /// How many of the previous accesses an access can have a bank conflict with.
+    pub bank_window: usize,
+    /// The number of MSHRs bounding outstanding L1 misses, 0 for unbounded.
+    pub mshrs: usize,
+    /// The number of accesses an L1 miss stays outstanding in an MSHR.
+    pub mshr_window: usize,
+    /// The number of accesses whose misses overlap, 0 for no overlap estimate.
+    pub overlap_window: usize,
+    /// The number of TLB entries, 0 for no TLB.
+    pub tlb_entries: usize,
+    pub tlb_assoc: usize,
+    pub page_size: usize,
+    pub tlb_miss_penalty: usize,
+    /// The number of records at the start of the trace that only warm up the caches.
+    pub warmup: usize,
+    /// Whether every dirty block is written back at the end of the trace.
+    pub flush_at_end: bool,
+    /// The number of accesses between flushes of every dirty block, 0 for none.
+    pub flush_interval: usize,
+}
+
+impl Config {
+    /// Create a configuration for a geometry with every other option at the same default as
+    /// the command line: LRU caches and no extra structures.
+    ///
+    /// ## Arguments
+    /// * `geometry` - The cache sizes to simulate.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::config::Config;
+    /// use cache_simulator::sweep::Geometry;
+    ///
+    /// let geometry = Geometry { block_size: 32, l1_size: 8192, l1_assoc: 4, l2_size: 0, l2_assoc: 0 };
+    /// let config = Config { victim_size: 4, ..Config::new(geometry) };
+    /// assert!(config.build_simulator().is_ok());
+    /// ```
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            geometry,
+            replacement: ReplacementPolicy::Lru,
+            rrip_bits: 2,
+            seed: 0,
+            lfu_aging_interval: 0,
+            skewed: false,
+            l1_insertion: InsertionPosition::Mru,
+            l2_insertion: InsertionPosition::Mru,
+            bimodal_probability: DEFAULT_BIMODAL_PROBABILITY,
+            page_color_bits: 0,
+            index_bits: None,
+            offset_bits: None,
+            sector_size: None,
+            write_validate: false,
+            victim_size: 0,
+            inclusion: Inclusion::Nine,
+            l1_latency: 1,
+            l2_latency: 10,
+            memory_latency: 100,
+            memory_regions: vec![],
+            energy: None,
+            classify_misses: false,
+            conflict_shadow: false,
+            stride_prefetch: false,
+            l1_prefetch_degree: 0,
+            l2_prefetch_degree: 0,
+            prefetch_placement: PrefetchPlacement::L1,
+            streaming_threshold: None,
+            streaming_action: StreamingAction::LruInsert,
+            stream_buffers: 0,
+            stream_buffer_depth: 4,
+            write_buffer_depth: 0,
+            write_buffer_drain_interval: 1,
+            banks: 0,
+            bank_window: 1,
+            mshrs: 0,
+            mshr_window: 10,
+            overlap_window: 0,
+            tlb_entries: 0,
+            tlb_assoc: 4,
+            page_size: 4096,
+            tlb_miss_penalty: 30,
+            warmup: 0,
+            flush_at_end: false,
+            flush_interval: 0,
+        }
+    }
+
+    /// Create one level of cache with the replacement, insertion, and sector options applied.
+    ///
+    /// ## Arguments
+    /// * `name` - The name of the level, for error messages.
+    /// * `size` - The total size of the cache in bytes.
+    /// * `assoc` - The associativity of the cache.
+    /// * `index_bits` - The number of index bits, if it isn't to be derived.
+    /// * `sector_size` - The size of a sector in bytes, if the cache is sectored.
+    /// * `insertion` - Where the level inserts new blocks.
+    ///
+    /// Returns the cache, or a message saying why the configuration is invalid.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::{Access, AccessType};
+    /// use cache_simulator::cache::replacement::InsertionPosition;
+    /// use cache_simulator::config::Config;
+    /// use cache_simulator::simulator::simulate;
+    /// use cache_simulator::sweep::Geometry;
+    ///
+    /// // 48 hot blocks fit in the 64-block l2 but not the 16-block l1, and between every
+    /// // pass over them comes a scan of 64 blocks that are never seen again
+    /// let geometry = Geometry { block_size: 16, l1_size: 256, l1_assoc: 2, l2_size: 1024, l2_assoc: 4 };
+    /// let mut trace = vec![];
+    /// for round in 0..8 {
+    ///     trace.extend((0x1000..0x1300).step_by(0x10));
+    ///     trace.extend((0..64).map(|block| 0x10000 + (round * 64 + block) * 0x10));
+    /// }
+    /// let trace = trace.into_iter().map(|address| Access { rw: AccessType::Read, address, size: 1, timestamp: None });
+    ///
+    /// // l1 misses on everything either way, but with bimodal insertion the scans mostly stay
+    /// // at the lru end of l2 and the hot blocks survive them
+    /// let mru = simulate(&Config::new(geometry), trace.clone());
+    /// let bimodal = simulate(&Config { l2_insertion: InsertionPosition::Bimodal, ..Config::new(geometry) }, trace);
+    /// assert_eq!((mru.l1_read_misses, bimodal.l1_read_misses), (896, 896));
+    /// assert_eq!((mru.l2_read_misses, bimodal.l2_read_misses), (896, 591));
+    /// ```
+    pub fn build_cache(
+        &self,
+        name: &str,
+        size: usize,
+        assoc: usize,
+        index_bits: Option<usize>,
+        sector_size: Option<usize>,
+        insertion: InsertionPosition,
+    ) -> Result<Cache, String> {
+        let invalid = |e| format!("Invalid {} configuration: {}", name, e);
+        let mut cache = Cache::with_address_bits(size, assoc, self.geometry.block_size, index_bits, self.offset_bits)
+            .map_err(invalid)?;
+        cache.set_replacement_policy(self.replacement).map_err(invalid)?;
+        cache.rrip_bits = self.rrip_bits;
+        cache.set_seed(self.seed);
+        cache.lfu_aging_interval = self.lfu_aging_interval;
+        cache.set_insertion(insertion, self.bimodal_probability).map_err(invalid)?;
+        if self.skewed {
+            cache.set_skewed().map_err(invalid)?;
+        }
+        if self.page_color_bits != 0 {
+            cache.set_page_coloring(self.page_size, self.page_color_bits).map_err(invalid)?;
+        }
+        if let Some(sector_size) = sector_size {
+            cache.set_sector_size(sector_size).map_err(invalid)?;
+        }
+        Ok(cache)
+    }
+
+    /// Create the caches and a simulator over them, with every option applied.
+    ///
+    /// Returns the simulator, or a message saying why the configuration is invalid.
+    ///
+    /// ## Throws
+    /// This function will panic if the options can't be combined, like `Simulator::new`.
+    pub fn build_simulator(&self) -> Result<Simulator, String> {
+        let geometry = &self.geometry;
+        let l1 = self.build_cache("L1", geometry.l1_size, geometry.l1_assoc, self.index_bits, self.sector_size, self.l1_insertion)?;
+        // an l2 size of 0 means there's no l2
+        let l2 = (geometry.l2_size != 0)
+            .then(|| self.build_cache("L2", geometry.l2_size, geometry.l2_assoc, None, None, self.l2_insertion))
+            .transpose()?;
+        let victim = VictimCache::new(self.victim_size);
+        let mut simulator = Simulator::new(l1, l2, victim, self.inclusion);
+        simulator.stats.l1_latency = self.l1_latency;
+        simulator.stats.l2_latency = self.l2_latency;
+        simulator.stats.memory_latency = self.memory_latency;
+        if let Some(energy) = self.energy {
+            simulator.stats.energy_enabled = true;
+            simulator.stats.energy = energy;
+        }
+        if self.classify_misses {
+            simulator.enable_miss_classification();
+        }
+        if self.conflict_shadow {
+            simulator.enable_conflict_shadow().map_err(|e| format!("Invalid conflict shadow configuration: {}", e))?;
+        }
+        if self.write_validate {
+            simulator.enable_write_validate();
+        }
+        if self.stride_prefetch {
+            simulator.enable_stride_prefetcher();
+        }
+        if self.l1_prefetch_degree != 0 {
+            simulator.enable_l1_next_line_prefetch(self.l1_prefetch_degree);
+        }
+        if self.l2_prefetch_degree != 0 {
+            simulator.enable_l2_next_line_prefetch(self.l2_prefetch_degree);
+        }
+        if self.prefetch_placement != PrefetchPlacement::L1 {
+            simulator.set_prefetch_placement(self.prefetch_placement)?;
+        }
+        if let Some(threshold) = self.streaming_threshold {
+            simulator.enable_streaming_detection(threshold, self.streaming_action);
+        }
+        if self.stream_buffers != 0 {
+            simulator.enable_stream_buffers(self.stream_buffers, self.stream_buffer_depth);
+        }
+        if self.tlb_entries != 0 {
+            let tlb = Tlb::new(self.tlb_entries, self.tlb_assoc, self.page_size)
+                .map_err(|e| format!("Invalid TLB configuration: {}", e))?;
+            simulator.enable_tlb(tlb, self.tlb_miss_penalty);
+        }
+        if self.flush_interval != 0 {
+            simulator.enable_periodic_flush(self.flush_interval);
+        }
+        if self.write_buffer_depth != 0 {
+            simulator.enable_write_buffer(self.write_buffer_depth, self.write_buffer_drain_interval);
+        }
+        if self.banks != 0 {
+            simulator.enable_banks(self.banks, self.bank_window);
+        }
+        if self.mshrs != 0 {
+            simulator.enable_mshrs(self.mshrs, self.mshr_window);
+        }
+        if !self.memory_regions.is_empty() {
+            let regions = MemoryRegions::new(self.memory_regions.clone())
+                .map_err(|e| format!("Invalid memory regions: {}", e))?;
+            simulator.enable_memory_regions(regions);
+        }
+        if self.overlap_window != 0 {
+            simulator.enable_overlap_window(self.overlap_window);
+        }
+        Ok(simulator)
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use crate::access::Access;
+
+/// The addresses a trace touches, independent of any cache: the range of bytes it spans and
+/// the distinct blocks within it. The number of distinct blocks times the block size is the
+/// working set, the smallest fully-associative cache that would only take compulsory misses.
+///
+/// Every distinct block gets an entry, so this costs memory proportional to the footprint.
+pub struct Footprint {
+    pub block_size: usize,
+    /// The number of trace records seen.
+    pub accesses: usize,
+    /// The lowest and highest byte accessed, `None` until the first record.
+    pub range: Option<(usize, usize)>,
+    pub blocks: HashSet<usize>,
+}
+
+impl Footprint {
+    /// Creates a new footprint that hasn't seen any accesses.
+    ///
+    /// ## Arguments
+    /// * `block_size` - The size of the blocks to count, in bytes.
+    ///
+    /// ## Throws
+    /// This function will panic if the block size is 0.
+    pub fn new(block_size: usize) -> Self {
+        assert!(block_size != 0, "The block size must be at least 1 byte.");
+        Self {
+            block_size,
+            accesses: 0,
+            range: None,
+            blocks: HashSet::new(),
+        }
+    }
+
+    /// Record a trace record, with every block its bytes span.
+    ///
+    /// ## Arguments
+    /// * `access` - The record to add.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::footprint::Footprint;
+    /// use cache_simulator::trace::{read_trace, AddressRadix, TraceFormat};
+    ///
+    /// // the 8-byte write at 0x13c spans the blocks at 0x120 and 0x140
+    /// let trace = "r 100\nw 13c 8\nr 104\nr 1ff\nw 120\n";
+    /// let mut footprint = Footprint::new(32);
+    /// for access in read_trace(trace.as_bytes(), TraceFormat::Legacy, AddressRadix::Hex).unwrap() {
+    ///     footprint.observe(&access);
+    /// }
+    /// assert_eq!(footprint.range, Some((0x100, 0x1ff)));
+    /// assert_eq!(footprint.span(), 0x100);
+    /// assert_eq!(footprint.distinct_blocks(), 4);
+    /// assert_eq!(footprint.working_set_bytes(), 128);
+    /// ```
+    pub fn observe(&mut self, access: &Access) {
+        self.accesses += 1;
+        let last = access.address.saturating_add(access.size.max(1) - 1);
+        self.range = Some(match self.range {
+            Some((lowest, highest)) => (lowest.min(access.address), highest.max(last)),
+            None => (access.address, last),
+        });
+        for address in access.lookups(self.block_size) {
+            self.blocks.insert(address / self.block_size * self.block_size);
+        }
+    }
+
+    /// Get the number of distinct blocks touched.
+    pub fn distinct_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Get the size in bytes of every distinct block touched.
+    pub fn working_set_bytes(&self) -> usize {
+        self.blocks.len() * self.block_size
+    }
+
+    /// Get the number of bytes from the lowest address touched to the highest, inclusive,
+    /// or 0 if nothing was touched.
+    pub fn span(&self) -> usize {
+        self.range.map_or(0, |(lowest, highest)| highest - lowest + 1)
+    }
+
+    /// Print out the footprint.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the footprint, e.g. stdout.
+    pub fn print<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "accesses:                      {}", self.accesses)?;
+        if let Some((lowest, highest)) = self.range {
+            writeln!(out, "lowest address:                {:x}", lowest)?;
+            writeln!(out, "highest address:               {:x}", highest)?;
+        }
+        writeln!(out, "address span (bytes):          {}", self.span())?;
+        writeln!(out, "distinct blocks:               {}", self.distinct_blocks())?;
+        writeln!(out, "working set (bytes):           {}", self.working_set_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// How often a single block was accessed and how often it hit in L1.
+#[derive(Clone, Copy, Default)]
+pub struct BlockCount {
+    pub accesses: usize,
+    pub hits: usize,
+}
+
+/// Counts the accesses to every block address in the trace to find the hottest blocks.
+/// Every distinct block gets an entry, so this costs memory proportional to the footprint.
+pub struct HotBlocks {
+    pub counts: HashMap<usize, BlockCount>,
+}
+
+impl HotBlocks {
+    /// Creates a new table that hasn't seen any accesses.
+    pub fn new() -> Self {
+        Self {
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Record an access to a block.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address being accessed.
+    /// * `hit` - Whether the access hit in L1.
+    pub fn observe(&mut self, block_address: usize, hit: bool) {
+        let count = self.counts.entry(block_address).or_default();
+        count.accesses += 1;
+        if hit {
+            count.hits += 1;
+        }
+    }
+
+    /// Print the most frequently accessed blocks, hottest first. Ties are broken by block
+    /// address so the report is the same on every run.
+    ///
+    /// ## Arguments
+    /// * `top` - How many blocks to print.
+    /// * `out` - Where to write the report, e.g. stdout.
+    pub fn print_top<W: Write>(&self, top: usize, out: &mut W) -> io::Result<()> {
+        let mut hottest: Vec<(&usize, &BlockCount)> = self.counts.iter().collect();
+        hottest.sort_by(|(a_address, a), (b_address, b)| {
+            b.accesses.cmp(&a.accesses).then(a_address.cmp(b_address))
+        });
+        writeln!(out, "{:>16} {:>10} {:>9}", "block address", "accesses", "hit rate")?;
+        for (block_address, count) in hottest.into_iter().take(top) {
+            let hit_rate = count.hits as f32 / count.accesses as f32;
+            writeln!(out, "{:>16x} {:>10} {:>9.4}", block_address, count.accesses, hit_rate)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for HotBlocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,24 @@
+pub mod access;
+pub mod banks;
+pub mod cache;
+pub mod classifier;
+pub mod coherence;
+pub mod config;
+pub mod footprint;
+pub mod hot_blocks;
+pub mod overlap;
+pub mod prefetch;
+pub mod progress;
+pub mod regions;
+pub mod replacement_log;
+pub mod reuse;
+pub mod rng;
+pub mod shadow;
+pub mod simulator;
+pub mod statistics;
+pub mod streaming;
+pub mod sweep;
+pub mod tlb;
+pub mod trace;
+pub mod trace_gen;
+pub mod windows;
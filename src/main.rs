@@ -1,267 +1,962 @@
-use std::env;
-use crate::cache::cache::{Cache, HitOrMiss};
-use crate::HitOrMiss::HIT;
-use crate::HitOrMiss::MISS;
-use crate::statistics::Statistics;
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
+use clap::Parser;
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+use cache_simulator::access::Access;
+use cache_simulator::cache::cache::{CacheSnapshot, DEFAULT_BIMODAL_PROBABILITY};
+use cache_simulator::cache::replacement::{InsertionPosition, ReplacementPolicy};
+use cache_simulator::coherence::CoherenceController;
+use cache_simulator::config::Config;
+use cache_simulator::footprint::Footprint;
+use cache_simulator::prefetch::PrefetchPlacement;
+use cache_simulator::progress::Progress;
+use cache_simulator::regions::MemoryRegion;
+use cache_simulator::simulator::Inclusion;
+use cache_simulator::statistics::{EnergyModel, Statistics, StatsStyle};
+use cache_simulator::streaming::StreamingAction;
+use cache_simulator::sweep::{parse_sweep, run_sweep, Geometry};
+use cache_simulator::trace::{parse_core_trace, parse_trace, AddressRadix, TraceFormat, TraceLineError};
 
-mod cache;
-mod statistics;
-
-const READ: char = 'r';
-const WRITE: char = 'w';
-
-fn main() {
-    // get command line arguments and fail if insufficient
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 7 {
-        panic!("Expected 6 arguments and got {}", args.len());
-    }
+/// Trace path that means "read the trace from standard input".
+const STDIN_TRACE: &str = "-";
 
+fn main() -> io::Result<()> {
     // parse command line arguments
-    let args = Args {
-        block_size: args[1].parse().unwrap(),
-        l1_size: args[2].parse().unwrap(),
-        l1_assoc: args[3].parse().unwrap(),
-        l2_size: args[4].parse().unwrap(),
-        l2_assoc: args[5].parse().unwrap(),
-        trace: args[6].clone(),
+    let args = Args::parse();
+    let sweep = match &args.sweep {
+        Some(sweep) => Some(parse_sweep(&fs::read_to_string(sweep)?).unwrap_or_else(exit_with_error)),
+        None => None,
     };
+    if args.check_config {
+        let geometries = sweep.unwrap_or_else(|| vec![geometry_from_args(&args)]);
+        process::exit(if check_configuration(&args, &geometries)? { 0 } else { 1 });
+    }
+    if let Some(geometries) = sweep {
+        return run_sweep_mode(&args, &geometries);
+    }
+    if args.analyze {
+        return run_analyze_mode(&args);
+    }
+    let geometry = geometry_from_args(&args);
+    if !args.l2_partition.is_empty() && args.cores < 2 {
+        return exit_with_error("Partitioning L2 needs --cores 2 or more.".to_string());
+    }
+    if args.cores > 1 {
+        return run_coherence_mode(&args, &geometry);
+    }
 
     // print simulator configuration
     println!("===== Simulator configuration =====");
-    println!("BLOCK SIZE:  {}", args.block_size);
-    println!("L1_SIZE:     {}", args.l1_size);
-    println!("L1_ASSOC:    {}", args.l1_assoc);
-    println!("L2_SIZE:     {}", args.l2_size);
-    println!("L2_ASSOC:    {}", args.l2_assoc);
-    println!("trace_file:  {}", args.trace);
-
-    // initialize statistics
-    let mut stats = Statistics::new();
+    print_configuration(&args, &geometry, &mut io::stdout())?;
 
     // create caches
-    let mut l1 = Cache::new(args.l1_size, args.l1_assoc, args.block_size);
-    let mut l2 = Cache::new(args.l2_size, args.l2_assoc, args.block_size);
-
-    // read every line from trace file
-    let contents = std::fs::read_to_string("trace.txt").expect("File not found!");
-    for line in contents.lines() {
-        // get instruction and address
-        let parts: Vec<String> = line.split(" ").map(|s| s.to_string()).collect();
-        let rw: char = parts.get(0).unwrap().chars().next().unwrap();
-        let address = parts.get(1).unwrap();
-        let address_usize: usize = usize::from_str_radix(address, 16).unwrap();
-        let address_binary_string = format!("{:032b}", address_usize);
-
-        // if it's not read or write, fail
-        if rw != READ && rw != WRITE {
-            panic!("Unknown action {}", rw);
+    let config = config_from_args(&args, &geometry);
+    let mut simulator = config.build_simulator().unwrap_or_else(exit_with_error);
+    if args.reuse_histogram {
+        simulator.enable_reuse_histogram();
+    }
+    if args.top_blocks != 0 {
+        simulator.enable_hot_blocks();
+    }
+    if let Some(window) = args.window_stats {
+        simulator.enable_time_windows(window);
+    }
+    if args.verbose {
+        simulator.enable_verbose_log();
+    }
+    if args.self_check {
+        simulator.enable_self_check();
+    }
+    let mut replacement_trace = match &args.trace_replacement {
+        Some(path) => {
+            simulator.enable_replacement_log();
+            Some(BufWriter::new(File::create(path)?))
         }
-
-        // get the index and tag for l1 cache
-        let l1_index: usize = usize::from_str_radix(
-            &address_binary_string
-                .chars()
-                .skip(l1.tag_bits)
-                .take(l1.index_bits)
-                .collect::<String>(),
-            2
-        ).unwrap();
-        let l1_tag: usize = usize::from_str_radix(
-            &address_binary_string
-                .chars()
-                .take(l1.tag_bits)
-                .collect::<String>(),
-            2
-        ).unwrap();
-        //println!("l1_tag={}", l1_tag);
-
-        // get the index and tag for l2 cache
-        let l2_index: usize = usize::from_str_radix(
-            &address_binary_string
-                .chars()
-                .skip(l2.tag_bits)
-                .take(l2.index_bits)
-                .collect::<String>(),
-            2
-        ).unwrap_or(0);
-        let l2_tag: usize = usize::from_str_radix(
-            &address_binary_string
-                .chars()
-                .take(l2.tag_bits)
-                .collect::<String>(),
-            2
-        ).unwrap_or(0);
-
-        // try to read from l1
-        let l1_hit_or_miss =
-            if rw == READ {
-                l1.read(l1_index, l1_tag)
-            } else {
-                l1.write(l1_index, l1_tag)
-            };
-        if l1_hit_or_miss == HIT {
-            // we hit in l1
-            if rw == READ {
-                stats.l1_reads += 1;
-            } else if rw == WRITE {
-                stats.l1_writes += 1;
+        None => None,
+    };
+    if args.set_stats || args.top_evicted != 0 {
+        simulator.l1.enable_set_stats();
+        if let Some(l2) = simulator.l2.as_mut() {
+            l2.enable_set_stats();
+        }
+    }
+    if let Some(path) = &args.load_state {
+        let state: SavedState = serde_json::from_str(&fs::read_to_string(path)?)
+            .unwrap_or_else(|e| exit_with_error(format!("Invalid state file {}: {}", path, e)));
+        simulator.l1
+            .try_restore(&state.l1)
+            .unwrap_or_else(|e| exit_with_error(format!("Invalid L1 state: {}", e)));
+        match (simulator.l2.as_mut(), &state.l2) {
+            (Some(l2), Some(l2_state)) => {
+                l2.try_restore(l2_state).unwrap_or_else(|e| exit_with_error(format!("Invalid L2 state: {}", e)))
             }
-        } else if l1_hit_or_miss == MISS {
-            // we missed in l1
-            if rw == READ {
-                stats.l1_read_misses += 1;
-            } else if rw == WRITE {
-                stats.l1_write_misses += 1;
+            // older state files hold an empty snapshot when there's no l2
+            (None, None) => {}
+            (None, Some(l2_state)) if l2_state.cache_size == 0 => {}
+            _ => exit_with_error("Invalid L2 state: the state file and the configuration disagree on whether there is an L2"),
+        }
+    }
+
+    // read the trace files in order, or stdin for a trace of "-", up to the access limit
+    let paths = trace_paths(&args);
+    let max_accesses = args.max_accesses.unwrap_or(usize::MAX);
+    let skipped = Cell::new(0);
+    let (accesses, total, file_ends): (Box<dyn Iterator<Item = Access>>, Option<usize>, Vec<usize>) = if args.stream {
+        // parse each record just before simulating it, without holding the trace in memory
+        let accesses = paths
+            .iter()
+            .flat_map(|path| valid_records(&args, path, parse_trace(open_trace(path), args.trace_format, args.address_radix), &skipped))
+            .take(max_accesses);
+        (Box::new(accesses), None, vec![])
+    } else {
+        let mut trace = vec![];
+        let mut file_ends = vec![];
+        for path in paths {
+            if trace.len() == max_accesses {
+                break;
             }
+            let remaining = max_accesses - trace.len();
+            let records = parse_trace(open_trace(path), args.trace_format, args.address_radix);
+            trace.extend(valid_records(&args, path, records, &skipped).take(remaining));
+            file_ends.push(trace.len());
+        }
+        let total = trace.len();
+        (Box::new(trace.into_iter()), Some(total), file_ends)
+    };
+    let mut progress = args.progress.then(|| Progress::new(total));
 
-            // check if we need to evict a block before inserting
-            if l1.set_is_full(l1_index) {
-                let l1_evicted_result = l1.evict_lru_block(l1_index);
-
-                // check if we have an l2
-                if l2.cache_size != 0 {
-                    // if the block was dirty we need to perform a write back
-                    if l1_evicted_result.evicted_block_was_dirty {
-                        let evicted_block_address = format!(
-                            "{:032b}",
-                            l1_evicted_result.evicted_block_address
-                        );
-                        let l2_write_back_index: usize = usize::from_str_radix(
-                            &evicted_block_address
-                                .chars()
-                                .skip(l2.tag_bits)
-                                .take(l2.index_bits)
-                                .collect::<String>(),
-                            2
-                        ).unwrap_or(0);
-                        let l2_write_back_tag: usize = usize::from_str_radix(
-                            &evicted_block_address
-                                .chars()
-                                .take(l2.tag_bits)
-                                .collect::<String>(),
-                            2
-                        ).unwrap_or(0);
-
-                        // try to write back to l2
-                        let l2_hit_or_miss = l2
-                            .write(
-                                l2_write_back_index,
-                                l2_write_back_tag
-                            );
-                        if l2_hit_or_miss == MISS {
-                            stats.l2_write_misses += 1;
-
-                            // check if we need to evict a block from l2 before installing
-                            if l2.set_is_full(l2_write_back_index) {
-                                let l2_evicted_result = l2
-                                    .evict_lru_block(l2_write_back_index);
-
-                                // write evicted block to main memory if it was dirty
-                                if l2_evicted_result.evicted_block_was_dirty {
-                                    stats.l2_write_backs += 1;
-                                    stats.total_memory_traffic += 1;
-                                }
-                            }
-
-                            l2.install(
-                                l2_write_back_index,
-                                l2_write_back_tag,
-                                address_usize
-                            );
-                            stats.total_memory_traffic += 1;
-                        }
-
-                        stats.l1_write_backs += 1;
-                        stats.l2_writes += 1;
-                    }
-                } else {
-                    // no l2, write back to main memory if dirty
-                    if l1_evicted_result.evicted_block_was_dirty {
-                        stats.l1_write_backs += 1;
-                        stats.total_memory_traffic += 1;
-                    }
-                }
+    let mut out = io::stdout().lock();
+    let mut records = 0;
+    let mut file = 0;
+    let mut totals: Option<Statistics> = None;
+    simulator.run(accesses, config.warmup, config.flush_at_end, |simulator| {
+        if let Some(progress) = &mut progress {
+            progress.tick();
+        }
+        simulator.write_log(&mut out)?;
+        if let Some(replacement_trace) = &mut replacement_trace {
+            simulator.write_replacement_log(replacement_trace)?;
+        }
+
+        // every file but the last gets its measurements here, the last after the flush
+        records += 1;
+        if args.per_file_stats && file + 1 < file_ends.len() && records == file_ends[file] {
+            if !args.contents_only {
+                writeln!(out, "===== Trace {} =====", paths[file])?;
+                simulator.stats.print_stats(args.stats_style, &mut out)?;
+            }
+            match totals.as_mut() {
+                Some(totals) => totals.merge(&simulator.stats),
+                None => totals = Some(simulator.stats.clone()),
             }
+            simulator.stats.reset();
+            file += 1;
+        }
+        Ok(())
+    })?;
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+    simulator.stats.skipped_lines = skipped.get();
+    if let Some(mut replacement_trace) = replacement_trace {
+        replacement_trace.flush()?;
+    }
 
-            // check if we have an l2
-            if l2.cache_size != 0 {
-                // try to read block from l2
-                let l2_hit_or_miss = l2.read(l2_index, l2_tag);
-                if l2_hit_or_miss == HIT {
-                    stats.l2_reads += 1;
-
-                    // not in l1 but is in l2, install it in l1
-                    l1.install(l1_index, l1_tag, address_usize);
-                    if rw == READ {
-                        stats.l1_reads += 1;
-                    } else if rw == WRITE {
-                        l1.write(l1_index, l1_tag);
-                        stats.l1_writes += 1;
-                    }
-                } else if l2_hit_or_miss == MISS {
-                    stats.l2_read_misses += 1;
-
-                    // check if we need to evict a block before installing
-                    if l2.set_is_full(l2_index) {
-                        let l2_evicted_result = l2.evict_lru_block(l2_index);
-
-                        // write evicted block back to main memory if it was dirty
-                        if l2_evicted_result.evicted_block_was_dirty {
-                            stats.l2_write_backs += 1;
-                            stats.total_memory_traffic += 1;
-                        }
-                    }
-
-                    // install in l2
-                    l2.install(l2_index, l2_tag, address_usize);
-                    stats.total_memory_traffic += 1;
-                    stats.l2_reads += 1;
-
-                    // install in l1
-                    l1.install(l1_index, l1_tag, address_usize);
-                    if rw == READ {
-                        stats.l1_reads += 1;
-                    } else {
-                        l1.write(l1_index, l1_tag);
-                        stats.l1_writes += 1;
-                    }
-                }
-            } else {
-                // install block from main memory
-                l1.install(l1_index, l1_tag, address_usize);
-                if rw == WRITE {
-                    l1.write(l1_index, l1_tag);
-                }
-                stats.total_memory_traffic += 1;
-                if rw == READ {
-                    stats.l1_reads += 1;
-                } else if rw == WRITE {
-                    stats.l1_writes += 1;
+    if let Some(path) = &args.dump_state {
+        let state = SavedState { l1: simulator.l1.snapshot(), l2: simulator.l2.as_ref().map(|l2| l2.snapshot()) };
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, &state)?;
+        file.flush()?;
+    }
+
+    // print results
+    if !args.quiet {
+        writeln!(out, "===== L1 contents =====")?;
+        simulator.l1.print_cache_info(&mut out)?;
+
+        if let Some(l2) = &simulator.l2 {
+            writeln!(out, "===== L2 contents =====")?;
+            l2.print_cache_info(&mut out)?;
+        }
+    }
+
+    if args.occupancy_map {
+        writeln!(out, "===== L1 occupancy =====")?;
+        simulator.l1.print_occupancy_map(&mut out)?;
+        if let Some(l2) = &simulator.l2 {
+            writeln!(out, "===== L2 occupancy =====")?;
+            l2.print_occupancy_map(&mut out)?;
+        }
+    }
+
+    if args.set_stats {
+        writeln!(out, "===== L1 set statistics =====")?;
+        simulator.l1.print_set_stats(&mut out)?;
+        if let Some(l2) = &simulator.l2 {
+            writeln!(out, "===== L2 set statistics =====")?;
+            l2.print_set_stats(&mut out)?;
+        }
+    }
+
+    if args.top_evicted != 0 {
+        writeln!(out, "===== L1 top {} evicted sets =====", args.top_evicted)?;
+        simulator.l1.print_top_evicted_sets(args.top_evicted, &mut out)?;
+        if let Some(l2) = &simulator.l2 {
+            writeln!(out, "===== L2 top {} evicted sets =====", args.top_evicted)?;
+            l2.print_top_evicted_sets(args.top_evicted, &mut out)?;
+        }
+    }
+
+    if let Some(reuse_histogram) = &simulator.reuse_histogram {
+        writeln!(out, "===== Reuse distance histogram =====")?;
+        reuse_histogram.print_histogram(&mut out)?;
+    }
+
+    if let Some(hot_blocks) = &simulator.hot_blocks {
+        writeln!(out, "===== Top {} blocks =====", args.top_blocks)?;
+        hot_blocks.print_top(args.top_blocks, &mut out)?;
+    }
+
+    if let Some(time_windows) = &simulator.time_windows {
+        writeln!(out, "===== Misses per {} cycles =====", time_windows.window)?;
+        time_windows.print(&mut out)?;
+    }
+
+    if args.contents_only {
+        return Ok(());
+    }
+    if args.per_file_stats {
+        // the access limit may have stopped the run before the last file
+        writeln!(out, "===== Trace {} =====", paths[file_ends.len().max(1) - 1])?;
+    }
+    simulator.stats.print_stats(args.stats_style, &mut out)?;
+    if let Some(mut totals) = totals {
+        totals.merge(&simulator.stats);
+        writeln!(out, "===== All traces =====")?;
+        totals.print_stats(args.stats_style, &mut out)?;
+    }
+    Ok(())
+}
+
+/// Characterize the trace without simulating it: the range of addresses it touches and
+/// the distinct blocks within it.
+///
+/// ## Arguments
+/// * `args` - The command line arguments, with the block size and trace.
+fn run_analyze_mode(args: &Args) -> io::Result<()> {
+    let block_size = args.block_size.expect("clap requires the block size without a sweep");
+    let mut footprint = Footprint::new(block_size);
+    let skipped = Cell::new(0);
+    let accesses = trace_paths(args)
+        .iter()
+        .flat_map(|path| valid_records(args, path, parse_trace(open_trace(path), args.trace_format, args.address_radix), &skipped))
+        .take(args.max_accesses.unwrap_or(usize::MAX));
+    for access in accesses {
+        footprint.observe(&access);
+    }
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "===== Trace analysis =====")?;
+    writeln!(out, "BLOCK SIZE:  {}", block_size)?;
+    if skipped.get() != 0 {
+        writeln!(out, "SKIPPED LINES: {}", skipped.get())?;
+    }
+    footprint.print(&mut out)
+}
+
+/// Get the geometry given by the size flags.
+///
+/// ## Arguments
+/// * `args` - The command line arguments, without a sweep.
+fn geometry_from_args(args: &Args) -> Geometry {
+    Geometry {
+        block_size: args.block_size.expect("clap requires the block size without a sweep"),
+        l1_size: args.l1_size.expect("clap requires the L1 size without a sweep"),
+        l1_assoc: args.l1_assoc.expect("clap requires the L1 associativity without a sweep"),
+        l2_size: args.l2_size,
+        l2_assoc: args.l2_assoc,
+    }
+}
+
+/// Build the caches of every geometry without running a trace, printing the address split
+/// of each level or the reason it's invalid.
+///
+/// ## Arguments
+/// * `args` - The command line arguments, applied to every geometry.
+/// * `geometries` - The geometries to check.
+///
+/// Returns whether every geometry is valid, or the error writing to stdout.
+fn check_configuration(args: &Args, geometries: &[Geometry]) -> io::Result<bool> {
+    let mut all_valid = true;
+    for geometry in geometries {
+        println!("===== Configuration check =====");
+        print_configuration(args, geometry, &mut io::stdout())?;
+        let config = config_from_args(args, geometry);
+        let levels = [
+            ("L1", geometry.l1_size, geometry.l1_assoc, args.index_bits, args.sector_size, args.l1_insert),
+            ("L2", geometry.l2_size, geometry.l2_assoc, None, None, args.l2_insert),
+        ];
+        // an l2 size of 0 means there's no l2 to describe
+        for (name, size, assoc, index_bits, sector_size, insertion) in levels.into_iter().filter(|&(_, size, ..)| size != 0) {
+            match config.build_cache(name, size, assoc, index_bits, sector_size, insertion) {
+                Ok(cache) => println!(
+                    "{}: sets {}, index bits {}, block offset bits {}, tag bits {}",
+                    name, cache.sets, cache.index_bits, cache.block_offset_bits, cache.tag_bits
+                ),
+                Err(e) => {
+                    println!("{}", e);
+                    all_valid = false;
                 }
             }
         }
     }
+    Ok(all_valid)
+}
 
-    // print results
+/// Simulate every geometry of a sweep in parallel over the same trace, printing one
+/// configuration and measurements block per geometry in sweep order.
+///
+/// ## Arguments
+/// * `args` - The command line arguments, applied to every geometry.
+/// * `geometries` - The geometries in the sweep file.
+fn run_sweep_mode(args: &Args, geometries: &[Geometry]) -> io::Result<()> {
+    // the geometry in the shared config is replaced by each geometry of the sweep
+    let Some(first_geometry) = geometries.first() else {
+        return Ok(());
+    };
+
+    // parse the trace once and share it between every simulation
+    let skipped = Cell::new(0);
+    let trace: Vec<Access> = trace_paths(args)
+        .iter()
+        .flat_map(|path| valid_records(args, path, parse_trace(open_trace(path), args.trace_format, args.address_radix), &skipped))
+        .take(args.max_accesses.unwrap_or(usize::MAX))
+        .collect();
+
+    // run_sweep panics on an invalid geometry, so exit with the reason before starting
+    for geometry in geometries {
+        if let Err(e) = config_from_args(args, geometry).build_simulator() {
+            exit_with_error(e)
+        }
+    }
+    let mut all_stats = run_sweep(&config_from_args(args, first_geometry), geometries, &trace);
+    for stats in &mut all_stats {
+        stats.skipped_lines = skipped.get();
+    }
+
+    let mut out = io::stdout().lock();
+    for (geometry, stats) in geometries.iter().zip(&all_stats) {
+        writeln!(out, "===== Simulator configuration =====")?;
+        print_configuration(args, geometry, &mut out)?;
+        stats.print_stats(args.stats_style, &mut out)?;
+    }
+    Ok(())
+}
+
+/// Print the configuration of a run, leaving out options that are at their defaults.
+///
+/// ## Arguments
+/// * `args` - The command line arguments.
+/// * `geometry` - The cache sizes being simulated.
+/// * `out` - Where to write the configuration, e.g. stdout.
+fn print_configuration<W: Write>(args: &Args, geometry: &Geometry, out: &mut W) -> io::Result<()> {
+    writeln!(out, "BLOCK SIZE:  {}", geometry.block_size)?;
+    writeln!(out, "L1_SIZE:     {}", geometry.l1_size)?;
+    writeln!(out, "L1_ASSOC:    {}", geometry.l1_assoc)?;
+    writeln!(out, "L2_SIZE:     {}", geometry.l2_size)?;
+    writeln!(out, "L2_ASSOC:    {}", geometry.l2_assoc)?;
+    if args.inclusion != Inclusion::Nine {
+        writeln!(out, "INCLUSION:   {:?}", args.inclusion)?;
+    }
+    if args.victim_size != 0 {
+        writeln!(out, "VICTIM_SIZE: {}", args.victim_size)?;
+    }
+    if args.replacement != ReplacementPolicy::Lru {
+        writeln!(out, "REPLACEMENT: {:?}", args.replacement)?;
+    }
+    if args.replacement == ReplacementPolicy::Random {
+        writeln!(out, "SEED:        {}", args.seed)?;
+    }
+    if args.skewed {
+        writeln!(out, "SKEWED:      true")?;
+    }
+    if args.l1_insert != InsertionPosition::Mru {
+        writeln!(out, "L1_INSERT:   {:?}", args.l1_insert)?;
+    }
+    if args.l2_insert != InsertionPosition::Mru {
+        writeln!(out, "L2_INSERT:   {:?}", args.l2_insert)?;
+    }
+    if args.l1_insert == InsertionPosition::Bimodal || args.l2_insert == InsertionPosition::Bimodal {
+        writeln!(out, "BIMODAL_PROBABILITY: {}", args.bimodal_probability)?;
+    }
+    if let Some(index_bits) = args.index_bits {
+        writeln!(out, "INDEX_BITS:  {}", index_bits)?;
+    }
+    if let Some(offset_bits) = args.offset_bits {
+        writeln!(out, "OFFSET_BITS: {}", offset_bits)?;
+    }
+    if let Some(sector_size) = args.sector_size {
+        writeln!(out, "SECTOR_SIZE: {}", sector_size)?;
+    }
+    if args.write_validate {
+        writeln!(out, "WRITE_VALIDATE: true")?;
+    }
+    if args.prefetch_into != PrefetchPlacement::L1 {
+        writeln!(out, "PREFETCH_INTO: {:?}", args.prefetch_into)?;
+    }
+    if args.tlb_entries != 0 {
+        writeln!(out, "TLB_ENTRIES: {}", args.tlb_entries)?;
+        writeln!(out, "TLB_ASSOC:   {}", args.tlb_assoc)?;
+    }
+    if args.tlb_entries != 0 || args.page_color_bits != 0 {
+        writeln!(out, "PAGE_SIZE:   {}", args.page_size)?;
+    }
+    if args.page_color_bits != 0 {
+        writeln!(out, "PAGE_COLOR_BITS: {}", args.page_color_bits)?;
+    }
+    if args.warmup != 0 {
+        writeln!(out, "WARMUP:      {}", args.warmup)?;
+    }
+    for region in &args.memory_region {
+        writeln!(out, "MEMORY_REGION: {:x} x{}", region.start, region.latency_multiplier)?;
+    }
+    if args.overlap_window != 0 {
+        writeln!(out, "OVERLAP_WINDOW: {}", args.overlap_window)?;
+    }
+    if args.flush_interval != 0 {
+        writeln!(out, "FLUSH_INTERVAL: {}", args.flush_interval)?;
+    }
+    if let Some(max_accesses) = args.max_accesses {
+        writeln!(out, "MAX_ACCESSES: {}", max_accesses)?;
+    }
+    if !args.trace.is_empty() {
+        writeln!(out, "trace_file:  {}", args.trace.join(" "))?;
+    }
+    Ok(())
+}
+
+/// Collect every option that affects the measurements into a configuration.
+///
+/// ## Arguments
+/// * `args` - The command line arguments.
+/// * `geometry` - The cache sizes to simulate.
+fn config_from_args(args: &Args, geometry: &Geometry) -> Config {
+    Config {
+        geometry: *geometry,
+        replacement: args.replacement,
+        rrip_bits: args.rrip_bits as usize,
+        seed: args.seed,
+        lfu_aging_interval: args.lfu_aging_interval,
+        skewed: args.skewed,
+        l1_insertion: args.l1_insert,
+        l2_insertion: args.l2_insert,
+        bimodal_probability: args.bimodal_probability,
+        page_color_bits: args.page_color_bits,
+        index_bits: args.index_bits,
+        offset_bits: args.offset_bits,
+        sector_size: args.sector_size,
+        write_validate: args.write_validate,
+        victim_size: args.victim_size,
+        inclusion: args.inclusion,
+        l1_latency: args.l1_latency,
+        l2_latency: args.l2_latency,
+        memory_latency: args.memory_latency,
+        memory_regions: args.memory_region.clone(),
+        energy: args.estimate_energy.then_some(EnergyModel {
+            l1_read: args.l1_read_energy,
+            l1_write: args.l1_write_energy,
+            l2_read: args.l2_read_energy,
+            l2_write: args.l2_write_energy,
+            memory_access: args.memory_access_energy,
+        }),
+        classify_misses: args.classify_misses,
+        conflict_shadow: args.conflict_shadow,
+        stride_prefetch: args.stride_prefetch,
+        l1_prefetch_degree: args.l1_prefetch,
+        l2_prefetch_degree: args.l2_prefetch,
+        prefetch_placement: args.prefetch_into,
+        streaming_threshold: args.streaming_threshold,
+        streaming_action: args.streaming_action,
+        stream_buffers: args.stream_buffers,
+        stream_buffer_depth: args.stream_buffer_depth,
+        write_buffer_depth: args.write_buffer_depth,
+        write_buffer_drain_interval: args.write_buffer_drain_interval as usize,
+        banks: args.banks,
+        bank_window: args.bank_window as usize,
+        mshrs: args.mshrs,
+        mshr_window: args.mshr_window as usize,
+        overlap_window: args.overlap_window,
+        tlb_entries: args.tlb_entries,
+        tlb_assoc: args.tlb_assoc,
+        page_size: args.page_size,
+        tlb_miss_penalty: args.tlb_miss_penalty,
+        warmup: args.warmup,
+        flush_at_end: args.flush_at_end,
+        flush_interval: args.flush_interval,
+    }
+}
+
+/// Simulate several cores with private L1s sharing L2, kept coherent by MSI, and print the
+/// contents of every cache and the measurements summed over the cores.
+///
+/// ## Arguments
+/// * `args` - The command line arguments.
+/// * `geometry` - The cache sizes, with the L1 sizes used for every core.
+fn run_coherence_mode(args: &Args, geometry: &Geometry) -> io::Result<()> {
+    println!("===== Simulator configuration =====");
+    print_configuration(args, geometry, &mut io::stdout())?;
+    println!("CORES:       {}", args.cores);
+    for (core, ways) in args.l2_partition.iter().enumerate() {
+        println!("L2_PARTITION: core {} ways {:x}", core, ways);
+    }
+
+    let config = config_from_args(args, geometry);
+    let l1s = (0..args.cores)
+        .map(|_| config.build_cache("L1", geometry.l1_size, geometry.l1_assoc, args.index_bits, None, args.l1_insert))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(exit_with_error);
+    let l2 = (geometry.l2_size != 0)
+        .then(|| config.build_cache("L2", geometry.l2_size, geometry.l2_assoc, None, None, args.l2_insert))
+        .transpose()
+        .unwrap_or_else(exit_with_error);
+    let mut controller = CoherenceController::new(l1s, l2);
+    if !args.l2_partition.is_empty() {
+        controller.partition_l2(args.l2_partition.clone()).unwrap_or_else(exit_with_error);
+    }
+    controller.stats.l1_latency = config.l1_latency;
+    controller.stats.l2_latency = config.l2_latency;
+    controller.stats.memory_latency = config.memory_latency;
+
+    let skipped = Cell::new(0);
+    let trace: Vec<(usize, Access)> = trace_paths(args)
+        .iter()
+        .flat_map(|path| valid_records(args, path, parse_core_trace(open_trace(path), args.trace_format, args.address_radix), &skipped))
+        .take(args.max_accesses.unwrap_or(usize::MAX))
+        .collect();
+    for (records, (core, access)) in trace.iter().enumerate() {
+        let mut lookups = 0;
+        for address in access.lookups(geometry.block_size) {
+            controller.access(*core, access.rw, address);
+            lookups += 1;
+        }
+        if lookups > 1 {
+            controller.stats.split_accesses += 1;
+        }
+
+        // only measure what comes after the warmup records
+        if records + 1 == args.warmup {
+            controller.end_warmup();
+        }
+    }
+    if trace.len() < args.warmup {
+        controller.end_warmup();
+    }
+    if args.flush_at_end {
+        controller.flush();
+    }
+    controller.stats.skipped_lines = skipped.get();
 
-    println!("===== L1 contents =====");
-    l1.print_cache_info();
+    let mut out = io::stdout().lock();
+    if !args.quiet {
+        for (core, l1) in controller.l1s.iter().enumerate() {
+            writeln!(out, "===== Core {} L1 contents =====", core)?;
+            l1.print_cache_info(&mut out)?;
+        }
+        if let Some(l2) = &controller.l2 {
+            writeln!(out, "===== L2 contents =====")?;
+            l2.print_cache_info(&mut out)?;
+        }
+    }
+    if args.contents_only {
+        return Ok(());
+    }
+    controller.stats.print_stats(args.stats_style, &mut out)
+}
+
+/// Get the trace paths, at least one of which clap requires unless only checking the
+/// configuration.
+///
+/// ## Arguments
+/// * `args` - The command line arguments.
+fn trace_paths(args: &Args) -> &[String] {
+    assert!(!args.trace.is_empty(), "clap requires a trace without --check-config");
+    &args.trace
+}
+
+/// Open a trace for reading line by line, decompressing it if it's gzipped.
+///
+/// ## Arguments
+/// * `path` - The path to the trace file, or "-" for stdin.
+fn open_trace(path: &str) -> Box<dyn BufRead> {
+    let mut reader: Box<dyn BufRead> = if path == STDIN_TRACE {
+        Box::new(BufReader::new(io::stdin().lock()))
+    } else {
+        Box::new(BufReader::new(File::open(path).expect("File not found!")))
+    };
 
-    if l2.cache_size != 0 {
-        println!("===== L2 contents =====");
-        l2.print_cache_info();
+    // gzip streams start with the magic bytes 1f 8b
+    let gzipped = reader.fill_buf().expect("Failed to read trace").starts_with(&[0x1f, 0x8b]);
+    if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        reader
     }
+}
+
+/// Parse an L2 way mask given on the command line.
+///
+/// ## Arguments
+/// * `mask` - The mask in hex, with or without a `0x` prefix.
+fn parse_way_mask(mask: &str) -> Result<u64, String> {
+    let digits = mask.strip_prefix("0x").unwrap_or(mask);
+    u64::from_str_radix(digits, 16).map_err(|_| format!("Invalid way mask {}, expected hex", mask))
+}
 
-    stats.print_stats();
+/// The cache contents written by `--dump-state` and read by `--load-state`.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    l1: CacheSnapshot,
+    #[serde(default)]
+    l2: Option<CacheSnapshot>,
 }
 
 /// Command line arguments needed to run the simulator.
+#[derive(Parser)]
+#[command(about = "Simulate an L1 cache with optional L2 backing over a memory trace")]
 struct Args {
-    block_size: usize,
-    l1_size: usize,
-    l1_assoc: usize,
+    /// Size of a cache block in bytes, shared by every level.
+    #[arg(long, required_unless_present = "sweep")]
+    block_size: Option<usize>,
+    /// Total size of the L1 cache in bytes.
+    #[arg(long, required_unless_present_any = ["sweep", "analyze"])]
+    l1_size: Option<usize>,
+    /// Associativity of the L1 cache.
+    #[arg(long, required_unless_present_any = ["sweep", "analyze"])]
+    l1_assoc: Option<usize>,
+    /// Total size of the L2 cache in bytes, 0 for no L2.
+    #[arg(long, default_value_t = 0)]
     l2_size: usize,
+    /// Associativity of the L2 cache.
+    #[arg(long, default_value_t = 0)]
     l2_assoc: usize,
-    trace: String,
+    /// Number of blocks in the victim cache between L1 and L2, or between L1 and main memory
+    /// without an L2, 0 for no victim cache.
+    #[arg(long, default_value_t = 0)]
+    victim_size: usize,
+    /// Number of L1 index bits, instead of deriving it from the L1 size and associativity.
+    /// It must still select exactly one of the sets.
+    #[arg(long)]
+    index_bits: Option<usize>,
+    /// Number of block offset bits in L1 and L2, instead of deriving it from the block size.
+    /// It must still address exactly one block.
+    #[arg(long)]
+    offset_bits: Option<usize>,
+    /// Split L1 blocks into sectors of this many bytes, fetching only the sector that missed.
+    #[arg(long)]
+    sector_size: Option<usize>,
+    /// Allocate the block of an L1 write miss without fetching it, with only the written
+    /// sector (or the whole block, if L1 isn't sectored) valid. Not supported with an
+    /// inclusive L2.
+    #[arg(long)]
+    write_validate: bool,
+    /// Inclusion policy between L1 and L2.
+    #[arg(long, value_enum, default_value_t = Inclusion::Nine)]
+    inclusion: Inclusion,
+    /// Replacement policy used by both L1 and L2.
+    #[arg(long, value_enum, default_value_t = ReplacementPolicy::Lru)]
+    replacement: ReplacementPolicy,
+    /// Bits of re-reference prediction per block under RRIP replacement.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..=16))]
+    rrip_bits: u8,
+    /// Seed for random replacement. The same seed always evicts the same blocks.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Halve every LFU counter after this many accesses to a cache, 0 to never age them.
+    #[arg(long, default_value_t = 0)]
+    lfu_aging_interval: usize,
+    /// Index each way of L1 and L2 with its own hash of the address (skewed associativity).
+    /// Only supported with LRU replacement.
+    #[arg(long)]
+    skewed: bool,
+    /// Where L1 inserts new blocks in its replacement order. Bimodal inserts at LRU except
+    /// for a random fraction of blocks, given by --bimodal-probability, inserted at MRU.
+    #[arg(long, value_enum, default_value_t = InsertionPosition::Mru)]
+    l1_insert: InsertionPosition,
+    /// Where L2 inserts new blocks in its replacement order, like --l1-insert.
+    #[arg(long, value_enum, default_value_t = InsertionPosition::Mru)]
+    l2_insert: InsertionPosition,
+    /// Fraction of blocks a level with bimodal insertion inserts at MRU, between 0 and 1.
+    #[arg(long, default_value_t = DEFAULT_BIMODAL_PROBABILITY)]
+    bimodal_probability: f64,
+    /// Number of writebacks the write buffer in front of main memory holds, 0 for none.
+    #[arg(long, default_value_t = 0)]
+    write_buffer_depth: usize,
+    /// Number of accesses it takes to drain one writeback from the write buffer.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    write_buffer_drain_interval: u64,
+    /// Number of banks L1 is divided into by the low bits of the index, to count accesses to
+    /// the same bank in quick succession. 0 for no banks. Doesn't change hits or misses.
+    #[arg(long, default_value_t = 0)]
+    banks: usize,
+    /// Number of previous accesses an access can have a bank conflict with.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    bank_window: u64,
+    /// Number of MSHRs, bounding how many L1 misses can be outstanding at once, 0 for no
+    /// bound. Misses to a block that is still outstanding are merged and counted as secondary.
+    #[arg(long, default_value_t = 0)]
+    mshrs: usize,
+    /// Number of accesses an L1 miss stays outstanding in its MSHR.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    mshr_window: u64,
+    /// Estimate stall cycles with the misses of every this many consecutive accesses
+    /// overlapping, each level's latency charged once per window, 0 for no estimate.
+    #[arg(long, default_value_t = 0, conflicts_with = "cores")]
+    overlap_window: usize,
+    /// Number of translations in the TLB in front of L1, 0 for no TLB.
+    #[arg(long, default_value_t = 0)]
+    tlb_entries: usize,
+    /// Associativity of the TLB.
+    #[arg(long, default_value_t = 4)]
+    tlb_assoc: usize,
+    /// Size of a page in bytes, used by the TLB and page coloring.
+    #[arg(long, default_value_t = 4096)]
+    page_size: usize,
+    /// Recolor this many low bits of each page number before L1 and L2 index with it, to
+    /// model an operating system that colors its pages. 0 for no page coloring.
+    #[arg(long, default_value_t = 0)]
+    page_color_bits: usize,
+    /// Cycles added to an access whose translation misses in the TLB.
+    #[arg(long, default_value_t = 30)]
+    tlb_miss_penalty: usize,
+    /// Cycles taken by an L1 access.
+    #[arg(long, default_value_t = 1)]
+    l1_latency: usize,
+    /// Cycles taken by an L2 access.
+    #[arg(long, default_value_t = 10)]
+    l2_latency: usize,
+    /// Cycles taken by a main memory access.
+    #[arg(long, default_value_t = 100)]
+    memory_latency: usize,
+    /// A region of main memory from a hex start address up to the next region, and how many
+    /// times the memory latency a fetch from it takes, e.g. "80000000:3". Repeat for more
+    /// regions. Traffic is counted per region.
+    #[arg(long, conflicts_with = "cores")]
+    memory_region: Vec<MemoryRegion>,
+    /// Report an estimate of the dynamic energy from the event counts.
+    #[arg(long)]
+    estimate_energy: bool,
+    /// Energy of an L1 read or instruction fetch, in arbitrary units.
+    #[arg(long, default_value_t = EnergyModel::default().l1_read)]
+    l1_read_energy: f32,
+    /// Energy of an L1 write.
+    #[arg(long, default_value_t = EnergyModel::default().l1_write)]
+    l1_write_energy: f32,
+    /// Energy of an L2 read.
+    #[arg(long, default_value_t = EnergyModel::default().l2_read)]
+    l2_read_energy: f32,
+    /// Energy of an L2 write.
+    #[arg(long, default_value_t = EnergyModel::default().l2_write)]
+    l2_write_energy: f32,
+    /// Energy of a block transferred to or from memory.
+    #[arg(long, default_value_t = EnergyModel::default().memory_access)]
+    memory_access_energy: f32,
+    /// Classify L1 misses as compulsory, capacity, or conflict misses (slower).
+    #[arg(long)]
+    classify_misses: bool,
+    /// Count the L1 and L2 misses a fully-associative cache of the same size and replacement
+    /// would have hit, and report them as a fraction of all misses.
+    #[arg(long)]
+    conflict_shadow: bool,
+    /// Prefetch into L1 along strides detected between consecutive L1 misses.
+    #[arg(long)]
+    stride_prefetch: bool,
+    /// Prefetch this many blocks after every L1 miss into L1 (next-line prefetching), 0 for none.
+    #[arg(long, default_value_t = 0)]
+    l1_prefetch: usize,
+    /// Prefetch this many blocks after every L2 demand miss from memory into L2, 0 for none.
+    #[arg(long, default_value_t = 0)]
+    l2_prefetch: usize,
+    /// The level --stride-prefetch and --l1-prefetch install their blocks in. With l2, L1
+    /// only gets a prefetched block once it's demanded.
+    #[arg(long, value_enum, default_value_t = PrefetchPlacement::L1)]
+    prefetch_into: PrefetchPlacement,
+    /// Treat this many consecutive L1 misses as a stream, whose blocks are handled by
+    /// --streaming-action instead of being inserted at MRU.
+    #[arg(long)]
+    streaming_threshold: Option<usize>,
+    /// What L1 does with the blocks of a detected stream.
+    #[arg(long, value_enum, default_value_t = StreamingAction::LruInsert)]
+    streaming_action: StreamingAction,
+    /// Number of stream buffers beside L1, 0 for none.
+    #[arg(long, default_value_t = 0)]
+    stream_buffers: usize,
+    /// Number of blocks each stream buffer prefetches ahead.
+    #[arg(long, default_value_t = 4)]
+    stream_buffer_depth: usize,
+    /// How to lay out the measurements: the lettered lines of the course assignment, or
+    /// plain "key: value" lines grouped into sections.
+    #[arg(long, value_enum, default_value_t = StatsStyle::Assignment)]
+    stats_style: StatsStyle,
+    /// Don't print the contents of the caches, only the measurements and any other reports
+    /// asked for.
+    #[arg(long, conflicts_with = "sweep")]
+    quiet: bool,
+    /// Print the contents of the caches without the measurements.
+    #[arg(long, conflicts_with_all = ["quiet", "sweep"])]
+    contents_only: bool,
+    /// Print what happened at each level for every access (very long for large traces).
+    #[arg(long)]
+    verbose: bool,
+    /// Check the LRU, tag, and valid bookkeeping of every set after every access, and stop
+    /// at the first access that breaks it (slow, for debugging the simulator).
+    #[arg(long, conflicts_with = "cores")]
+    self_check: bool,
+    /// Write every L1 and L2 eviction to this file, one line each of
+    /// "level,set,evicted_tag,installed_tag,access" with the tags in hex, to diff the
+    /// replacement order against a reference simulator.
+    #[arg(long, conflicts_with_all = ["sweep", "cores"])]
+    trace_replacement: Option<String>,
+    /// Print a histogram of reuse distances at the L1 block size (slow and memory-heavy for
+    /// traces with many distinct blocks).
+    #[arg(long)]
+    reuse_histogram: bool,
+    /// Print the N most accessed block addresses with their L1 hit rates, 0 for none.
+    #[arg(long, default_value_t = 0)]
+    top_blocks: usize,
+    /// Print the accesses and misses in every window of this many cycles, by the `@<cycle>`
+    /// timestamps of the trace records, or by record index for records without one.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["cores", "per_file_stats"])]
+    window_stats: Option<u64>,
+    /// Print a map of each level with one character per way: "." empty, "C" clean, "D" dirty.
+    #[arg(long)]
+    occupancy_map: bool,
+    /// Print access, miss, and eviction counts for every set.
+    #[arg(long)]
+    set_stats: bool,
+    /// Print the N sets of each level with the most evictions, with how many distinct tags
+    /// each evicted, 0 for none.
+    #[arg(long, default_value_t = 0)]
+    top_evicted: usize,
+    /// Number of trace records that only warm up the caches and aren't counted in the results.
+    #[arg(long, default_value_t = 0)]
+    warmup: usize,
+    /// Stop after this many trace records, across every trace file, and report on that
+    /// prefix. Warmup records count towards the limit.
+    #[arg(long)]
+    max_accesses: Option<usize>,
+    /// Write back every dirty block at the end of the trace and count the traffic.
+    #[arg(long)]
+    flush_at_end: bool,
+    /// Write back and clean every dirty block after every this many accesses, counting the
+    /// traffic, 0 for no periodic flush.
+    #[arg(long, default_value_t = 0, conflicts_with = "cores")]
+    flush_interval: usize,
+    /// Parse and simulate the trace one record at a time instead of reading it all up front,
+    /// for traces too large to hold in memory.
+    #[arg(long, conflicts_with = "sweep")]
+    stream: bool,
+    /// Report progress on stderr every million records, with a percentage and estimated time
+    /// remaining unless streaming.
+    #[arg(long, conflicts_with = "sweep")]
+    progress: bool,
+    /// Layout of the records in the trace.
+    #[arg(long, value_enum, default_value_t = TraceFormat::Legacy)]
+    trace_format: TraceFormat,
+    /// Base of the addresses in the trace. Addresses starting with "0x" are always hex.
+    #[arg(long, value_enum, default_value_t = AddressRadix::Hex)]
+    address_radix: AddressRadix,
+    /// Skip trace lines that can't be parsed, and report how many were skipped, instead of
+    /// stopping at the first one.
+    #[arg(long)]
+    skip_malformed: bool,
+    /// Simulate every geometry in this file in parallel instead of the one given by the size
+    /// flags. Each line is "<block_size> <l1_size> <l1_assoc> <l2_size> <l2_assoc>".
+    #[arg(long, conflicts_with_all = ["verbose", "occupancy_map", "set_stats", "top_evicted", "top_blocks", "reuse_histogram", "window_stats", "self_check"])]
+    sweep: Option<String>,
+    /// Simulate this many cores, each with its own L1 of the given size, sharing L2 and kept
+    /// coherent by MSI. Trace records may start with a core id, e.g. "1 w 1f00", and default
+    /// to core 0. Only the geometry, replacement, latency, warmup, and flush options apply,
+    /// and the others are rejected.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = [
+            "sweep", "victim_size", "sector_size", "write_validate", "inclusion", "prefetch_into",
+            "stride_prefetch", "l1_prefetch", "l2_prefetch", "streaming_threshold", "streaming_action",
+            "stream_buffers", "stream_buffer_depth", "write_buffer_depth", "write_buffer_drain_interval",
+            "banks", "bank_window", "mshrs", "mshr_window", "tlb_entries", "tlb_assoc", "tlb_miss_penalty",
+            "estimate_energy", "classify_misses", "conflict_shadow", "verbose", "reuse_histogram",
+            "top_blocks", "occupancy_map", "set_stats", "top_evicted", "stream", "progress", "dump_state",
+            "load_state",
+        ],
+    )]
+    cores: u64,
+    /// The L2 ways a core may allocate into as a hex mask, way w at bit w, e.g. 0f for the
+    /// first four. Give one per core, in core order; every core still hits in any way. Needs
+    /// --cores 2 or more.
+    #[arg(long, value_parser = parse_way_mask, conflicts_with = "sweep")]
+    l2_partition: Vec<u64>,
+    /// Write the final contents of L1 and L2 (tags, valid and dirty bits, and replacement
+    /// state) to this file as JSON.
+    #[arg(long, conflicts_with = "sweep")]
+    dump_state: Option<String>,
+    /// Start from the L1 and L2 contents in this file, written by --dump-state for caches
+    /// with the same geometry, instead of empty caches.
+    #[arg(long, conflicts_with = "sweep")]
+    load_state: Option<String>,
+    /// Check that the configuration (or every geometry of a sweep) is valid and print the
+    /// address split of each level, without running a trace. Exits with status 1 if any
+    /// configuration is invalid.
+    #[arg(long)]
+    check_config: bool,
+    /// Print the address range, distinct blocks, and working set of the trace at the block
+    /// size, without simulating any cache.
+    #[arg(long, conflicts_with_all = ["sweep", "cores", "check_config"])]
+    analyze: bool,
+    /// Paths to the trace files, simulated in order as one continuous trace, or "-" to read
+    /// the trace from stdin. Gzipped files are decompressed.
+    #[arg(long, num_args = 1.., required_unless_present = "check_config")]
+    trace: Vec<String>,
+    /// Print the measurements after each trace file and start counting again for the next,
+    /// keeping what the caches hold.
+    #[arg(long, conflicts_with_all = ["stream", "sweep", "cores"])]
+    per_file_stats: bool,
+}
+
+/// Print why the options can't be simulated and exit with status 1.
+///
+/// ## Arguments
+/// * `message` - What's wrong with the options.
+fn exit_with_error<T, E: fmt::Display>(message: E) -> T {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// Take the records of a trace file that could be parsed. A malformed record is skipped and
+/// counted with --skip-malformed, and otherwise stops the run with its file, line, and error.
+///
+/// ## Arguments
+/// * `args` - The command line arguments.
+/// * `path` - The path to the trace file the records are from.
+/// * `records` - The parsed records of the file.
+/// * `skipped` - The number of malformed records skipped so far.
+fn valid_records<'a, T: 'a>(
+    args: &'a Args,
+    path: &'a str,
+    records: impl Iterator<Item = Result<T, TraceLineError>> + 'a,
+    skipped: &'a Cell<usize>,
+) -> impl Iterator<Item = T> + 'a {
+    records.filter_map(move |record| match record {
+        Ok(record) => Some(record),
+        Err(_) if args.skip_malformed => {
+            skipped.set(skipped.get() + 1);
+            None
+        }
+        Err(e) => exit_with_error(format!("{}, {}", if path == STDIN_TRACE { "stdin" } else { path }, e)),
+    })
 }
@@ -0,0 +1,61 @@
+/// A coarse model of misses overlapping in time. The trace is cut into windows of
+/// consecutive accesses, and misses in the same window are serviced together, so each level
+/// below L1 charges its latency at most once per window instead of once per miss.
+pub struct OverlapWindow {
+    /// The number of accesses in each window.
+    pub window: usize,
+    /// The number of accesses seen so far in the current window.
+    pub accesses: usize,
+    /// Whether the current window has already been charged for an L1 miss.
+    pub l1_miss_charged: bool,
+    /// Whether the current window has already been charged for an L2 miss.
+    pub l2_miss_charged: bool,
+}
+
+impl OverlapWindow {
+    /// Creates a new model that hasn't seen any accesses.
+    ///
+    /// ## Arguments
+    /// * `window` - The number of accesses in each window, at least 1.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            accesses: 0,
+            l1_miss_charged: false,
+            l2_miss_charged: false,
+        }
+    }
+
+    /// Record an access and whether it missed at each level.
+    ///
+    /// ## Arguments
+    /// * `l1_miss` - Whether the access missed in L1.
+    /// * `l2_miss` - Whether the access missed in L2.
+    ///
+    /// Returns whether the L1 miss and the L2 miss are the first of their window, and so
+    /// pay their latency.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::overlap::OverlapWindow;
+    ///
+    /// // the second miss of each window of 2 overlaps the first
+    /// let mut overlap = OverlapWindow::new(2);
+    /// assert_eq!(overlap.observe(true, true), (true, true));
+    /// assert_eq!(overlap.observe(true, false), (false, false));
+    /// assert_eq!(overlap.observe(false, false), (false, false));
+    /// assert_eq!(overlap.observe(true, true), (true, true));
+    /// ```
+    pub fn observe(&mut self, l1_miss: bool, l2_miss: bool) -> (bool, bool) {
+        if self.accesses == self.window {
+            self.accesses = 0;
+            self.l1_miss_charged = false;
+            self.l2_miss_charged = false;
+        }
+        self.accesses += 1;
+        let charges = (l1_miss && !self.l1_miss_charged, l2_miss && !self.l2_miss_charged);
+        self.l1_miss_charged |= l1_miss;
+        self.l2_miss_charged |= l2_miss;
+        charges
+    }
+}
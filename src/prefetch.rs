@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use clap::ValueEnum;
+
+/// The level the L1 prefetchers install their blocks in.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum PrefetchPlacement {
+    /// Install them in L1, where they can be hit right away but push out demanded blocks.
+    L1,
+    /// Install them only in L2, so L1 holds nothing until it's demanded.
+    L2,
+}
+
+/// Detects a constant stride between consecutive L1 miss block addresses and prefetches
+/// ahead along it. A pure address trace has no instruction addresses to separate streams
+/// by, so this follows the single stream of misses: A, A+s, A+2s, ... and once the stride
+/// `s` has repeated twice in a row, every further miss on the stride prefetches the next block.
+/// The prefetched block won't miss, so the prefetcher treats it as the last miss seen and
+/// expects the stream to continue one stride after it.
+pub struct StridePrefetcher {
+    pub last_block_address: Option<usize>,
+    pub stride: isize,
+    pub confirmations: usize,
+}
+
+impl StridePrefetcher {
+    /// Number of times a stride has to repeat before prefetching starts.
+    pub const CONFIRMATIONS_NEEDED: usize = 2;
+
+    /// Creates a new stride prefetcher that hasn't seen any misses.
+    pub fn new() -> Self {
+        Self {
+            last_block_address: None,
+            stride: 0,
+            confirmations: 0,
+        }
+    }
+
+    /// Record a demand miss and train the stride on it.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address that missed.
+    ///
+    /// Returns the block address to prefetch if the stride is confirmed.
+    pub fn observe_miss(&mut self, block_address: usize) -> Option<usize> {
+        let last_block_address = self.last_block_address.replace(block_address)?;
+        let stride = block_address as isize - last_block_address as isize;
+        if stride == self.stride {
+            self.confirmations += 1;
+        } else {
+            self.stride = stride;
+            self.confirmations = 0;
+        }
+
+        if self.stride == 0 || self.confirmations < Self::CONFIRMATIONS_NEEDED {
+            return None;
+        }
+        let prefetch_block_address = block_address.checked_add_signed(self.stride)?;
+        self.last_block_address = Some(prefetch_block_address);
+        Some(prefetch_block_address)
+    }
+}
+
+impl Default for StridePrefetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A FIFO of prefetched consecutive blocks following one stream of misses.
+struct StreamBuffer {
+    /// Block addresses in the buffer, the head first.
+    blocks: VecDeque<usize>,
+    /// The block address the buffer fetches next.
+    next_block_address: usize,
+    /// When the buffer was last allocated or hit, for LRU replacement between buffers.
+    last_used: usize,
+}
+
+/// Jouppi-style stream buffers beside L1. A miss that finds its block at the head of a
+/// buffer moves the block into L1 and the buffer prefetches one more block at its tail. A miss
+/// that hits no buffer reallocates the least recently used one to the blocks following it.
+pub struct StreamBuffers {
+    buffers: Vec<StreamBuffer>,
+    depth: usize,
+    clock: usize,
+}
+
+impl StreamBuffers {
+    /// Creates stream buffers that don't hold any blocks yet.
+    ///
+    /// ## Arguments
+    /// * `count` - The number of stream buffers.
+    /// * `depth` - The number of blocks each buffer prefetches ahead.
+    pub fn new(count: usize, depth: usize) -> Self {
+        let buffers = (0..count)
+            .map(|_| StreamBuffer {
+                blocks: VecDeque::with_capacity(depth),
+                next_block_address: 0,
+                last_used: 0,
+            })
+            .collect();
+        Self { buffers, depth, clock: 0 }
+    }
+
+    /// Look for a block that missed in L1 at the head of every buffer. On a hit the block
+    /// leaves its buffer, which advances to prefetch the block after its tail.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address that missed.
+    ///
+    /// Returns the block address the hit buffer has to fetch, or `None` if no buffer hit.
+    pub fn take(&mut self, block_address: usize) -> Option<usize> {
+        self.clock += 1;
+        let buffer = self.buffers
+            .iter_mut()
+            .find(|buffer| buffer.blocks.front() == Some(&block_address))?;
+        buffer.blocks.pop_front();
+        let refill = buffer.next_block_address;
+        buffer.blocks.push_back(refill);
+        buffer.next_block_address += 1;
+        buffer.last_used = self.clock;
+        Some(refill)
+    }
+
+    /// Reallocate the least recently used buffer to the blocks following a block that missed
+    /// in L1 and every buffer. Whatever the buffer held is discarded.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address that missed.
+    ///
+    /// Returns the block addresses the buffer has to fetch.
+    pub fn allocate(&mut self, block_address: usize) -> Range<usize> {
+        let depth = self.depth;
+        let Some(buffer) = self.buffers.iter_mut().min_by_key(|buffer| buffer.last_used) else {
+            return 0..0;
+        };
+        let blocks = block_address + 1..block_address + 1 + depth;
+        buffer.blocks.clear();
+        buffer.blocks.extend(blocks.clone());
+        buffer.next_block_address = blocks.end;
+        buffer.last_used = self.clock;
+        blocks
+    }
+
+    /// Drop a block from every buffer, e.g. when an inclusive L2 evicts it.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address to drop.
+    pub fn invalidate(&mut self, block_address: usize) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.blocks.retain(|&block| block != block_address);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Instant;
+
+/// Number of records between progress reports. Checking a counter this rarely keeps the
+/// reporting out of the simulation's hot loop.
+pub const PROGRESS_INTERVAL: usize = 1_000_000;
+
+/// Reports how far a simulation has got on stderr, so it doesn't mix with the results.
+pub struct Progress {
+    /// Number of records in the trace, if it's known up front.
+    total: Option<usize>,
+    done: usize,
+    start: Instant,
+}
+
+impl Progress {
+    /// Start tracking a simulation.
+    ///
+    /// ## Arguments
+    /// * `total` - The number of records in the trace, or `None` when streaming.
+    pub fn new(total: Option<usize>) -> Self {
+        Progress {
+            total,
+            done: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Count a simulated record, reporting progress every `PROGRESS_INTERVAL` records.
+    pub fn tick(&mut self) {
+        self.done += 1;
+        if self.done.is_multiple_of(PROGRESS_INTERVAL) {
+            self.report();
+        }
+    }
+
+    /// Report the records simulated so far, with a percentage and estimated time remaining
+    /// when the length of the trace is known.
+    fn report(&self) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        match self.total {
+            Some(total) if total != 0 => {
+                let fraction = self.done as f64 / total as f64;
+                let remaining = elapsed / fraction - elapsed;
+                eprintln!(
+                    "progress: {}/{} records ({:.1}%), {:.0}s remaining",
+                    self.done, total, fraction * 100.0, remaining
+                );
+            }
+            _ => eprintln!("progress: {} records, {:.0}s elapsed", self.done, elapsed),
+        }
+    }
+
+    /// Report that the trace is done.
+    pub fn finish(&self) {
+        eprintln!("progress: done, {} records in {:.1}s", self.done, self.start.elapsed().as_secs_f64());
+    }
+}
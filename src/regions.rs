@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+/// A range of main memory that is slower than the rest, e.g. the memory of another NUMA
+/// node. It runs from its start up to the start of the next region.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MemoryRegion {
+    pub start: usize,
+    /// How many times the memory latency it takes to read from the region.
+    pub latency_multiplier: usize,
+}
+
+impl FromStr for MemoryRegion {
+    type Err = String;
+
+    /// Parse a region as `<hex start>:<latency multiplier>`, e.g. `0x80000000:3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid memory region {}, expected <hex start>:<latency multiplier>", s);
+        let (start, multiplier) = s.split_once(':').ok_or_else(invalid)?;
+        let start = start.strip_prefix("0x").unwrap_or(start);
+        Ok(MemoryRegion {
+            start: usize::from_str_radix(start, 16).map_err(|_| invalid())?,
+            latency_multiplier: multiplier.parse().ok().filter(|&multiplier| multiplier > 0).ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// Splits main memory into regions by address. Region 0 is everything below the first
+/// configured start, at the plain memory latency, and region `i` starts at the `i`th
+/// configured start.
+pub struct MemoryRegions {
+    /// The configured regions, by start address.
+    pub regions: Vec<MemoryRegion>,
+}
+
+impl MemoryRegions {
+    /// Creates the regions for a set of boundaries, in any order.
+    ///
+    /// ## Arguments
+    /// * `regions` - Where each region above region 0 starts, and its latency multiplier.
+    ///
+    /// Returns the regions, or a message saying why they overlap.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::regions::{MemoryRegion, MemoryRegions};
+    ///
+    /// let far = "0x8000:4".parse::<MemoryRegion>()?;
+    /// let regions = MemoryRegions::new(vec![far])?;
+    /// assert_eq!((regions.region_of(0x7fff), regions.region_of(0x8000)), (0, 1));
+    /// assert_eq!(regions.latency_multipliers(), [1, 4]);
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn new(mut regions: Vec<MemoryRegion>) -> Result<Self, String> {
+        regions.sort_by_key(|region| region.start);
+        if let Some(pair) = regions.windows(2).find(|pair| pair[0].start == pair[1].start) {
+            return Err(format!("Two memory regions start at {:x}", pair[0].start));
+        }
+        Ok(Self { regions })
+    }
+
+    /// Get the region an address is in.
+    ///
+    /// ## Arguments
+    /// * `address` - Any address within the block being transferred.
+    pub fn region_of(&self, address: usize) -> usize {
+        self.regions.partition_point(|region| region.start <= address)
+    }
+
+    /// Get the latency multiplier of every region, starting with region 0.
+    pub fn latency_multipliers(&self) -> Vec<usize> {
+        std::iter::once(1).chain(self.regions.iter().map(|region| region.latency_multiplier)).collect()
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+use std::io::{self, Write};
+
+/// A block evicted to make room for another, as recorded in a replacement log.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReplacementEvent {
+    /// The cache level the eviction happened in, 1 or 2.
+    pub level: usize,
+    pub set: usize,
+    pub evicted_tag: usize,
+    pub installed_tag: usize,
+    /// The number of the access that caused the eviction, counting from 1.
+    pub access_number: usize,
+}
+
+impl fmt::Display for ReplacementEvent {
+    /// Format the event as one comma-separated line, `level,set,evicted_tag,installed_tag,access_number`,
+    /// with the tags in hex, so logs from two simulators can be diffed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "L{},{},{:x},{:x},{}", self.level, self.set, self.evicted_tag, self.installed_tag, self.access_number)
+    }
+}
+
+/// Every eviction since the log was last written, in the order they happened, to check the
+/// replacement order against a reference simulator.
+pub struct ReplacementLog {
+    /// The number of accesses simulated since the log was started, including warmup.
+    pub accesses: usize,
+    pub events: Vec<ReplacementEvent>,
+}
+
+impl ReplacementLog {
+    /// Creates a new log that hasn't seen any accesses.
+    pub fn new() -> Self {
+        Self {
+            accesses: 0,
+            events: vec![],
+        }
+    }
+
+    /// Record an eviction during the current access.
+    ///
+    /// ## Arguments
+    /// * `level` - The cache level the eviction happened in.
+    /// * `set` - The set the block was evicted from.
+    /// * `evicted_tag` - The tag of the evicted block.
+    /// * `installed_tag` - The tag of the block that needed the way.
+    pub fn record(&mut self, level: usize, set: usize, evicted_tag: usize, installed_tag: usize) {
+        self.events.push(ReplacementEvent { level, set, evicted_tag, installed_tag, access_number: self.accesses });
+    }
+
+    /// Write out and clear the events recorded so far, one per line.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the events, e.g. a file.
+    pub fn write<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        for event in self.events.drain(..) {
+            writeln!(out, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for ReplacementLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+
+/// Measures the reuse (LRU stack) distance of every access: the number of distinct blocks
+/// touched since the last access to the same block. A fully-associative LRU cache of `n`
+/// blocks hits exactly the accesses with a distance below `n`, so the histogram shows how
+/// the hit rate would grow with cache size, independent of the configured caches.
+///
+/// The stack holds every distinct block the trace has touched and is searched linearly, so
+/// this is slow and memory-heavy for traces with many distinct blocks.
+pub struct ReuseHistogram {
+    /// Every block address seen so far, least recently used first.
+    pub stack: Vec<usize>,
+    /// Bucket 0 counts a distance of 0, and bucket `k` counts distances in `[2^(k-1), 2^k)`.
+    pub buckets: Vec<usize>,
+    /// Accesses that were the first touch of their block, with an infinite distance.
+    pub first_touches: usize,
+}
+
+impl ReuseHistogram {
+    /// Creates a new histogram that hasn't seen any accesses.
+    pub fn new() -> Self {
+        Self {
+            stack: vec![],
+            buckets: vec![],
+            first_touches: 0,
+        }
+    }
+
+    /// Record an access, moving its block to the top of the stack.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address being accessed.
+    pub fn observe(&mut self, block_address: usize) {
+        match self.stack.iter().rposition(|&seen| seen == block_address) {
+            Some(position) => {
+                let distance = self.stack.len() - 1 - position;
+                let bucket = (usize::BITS - distance.leading_zeros()) as usize;
+                if self.buckets.len() <= bucket {
+                    self.buckets.resize(bucket + 1, 0);
+                }
+                self.buckets[bucket] += 1;
+                self.stack.remove(position);
+            }
+            None => self.first_touches += 1,
+        }
+        self.stack.push(block_address);
+    }
+
+    /// Zero the histogram while keeping the stack, so later distances still see every
+    /// block touched before.
+    pub fn reset_counts(&mut self) {
+        self.buckets.clear();
+        self.first_touches = 0;
+    }
+
+    /// Print the number of accesses at each power-of-two range of reuse distances.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the histogram, e.g. stdout.
+    pub fn print_histogram<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            let (low, high) = if bucket == 0 { (0, 1) } else { (1 << (bucket - 1), 1 << bucket) };
+            writeln!(out, "{:>20}: {}", format!("[{}, {})", low, high), count)?;
+        }
+        writeln!(out, "{:>20}: {}", "infinite", self.first_touches)
+    }
+}
+
+impl Default for ReuseHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// A SplitMix64 pseudo-random number generator. It's tiny and fully determined by its seed,
+/// so anything random in a run (synthetic traces, random replacement) repeats exactly for
+/// the same seed.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SplitMix64 {
+    pub state: u64,
+}
+
+impl SplitMix64 {
+    /// Create a generator.
+    ///
+    /// ## Arguments
+    /// * `seed` - The seed that determines every number drawn.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Draw the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Draw a random number uniformly from [0, 1).
+    pub fn next_fraction(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
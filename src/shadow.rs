@@ -0,0 +1,52 @@
+use crate::cache::cache::Cache;
+use crate::cache::cache::HitOrMiss::MISS;
+use crate::cache::error::CacheConfigError;
+
+/// A fully-associative cache with the same capacity and replacement policy as a real one,
+/// fed the same lookups. A real miss that hits in the shadow is a conflict miss: the block
+/// would still have been there if the cache didn't split its blocks into sets.
+///
+/// Unlike `MissClassifier` this doesn't remember every block ever seen, so it only tells
+/// conflict misses apart from the rest.
+pub struct ShadowCache {
+    pub shadow: Cache,
+}
+
+impl ShadowCache {
+    /// Creates an empty shadow for a cache.
+    ///
+    /// ## Arguments
+    /// * `cache` - The cache to shadow, with its replacement policy already selected.
+    ///
+    /// Returns an error if the replacement policy can't run on a single set of that many
+    /// ways, like tree-PLRU over a number of blocks that isn't a power of two.
+    pub fn new(cache: &Cache) -> Result<Self, CacheConfigError> {
+        let mut shadow = Cache::new(cache.cache_size, cache.cache_size / cache.block_size, cache.block_size)?;
+        shadow.set_replacement_policy(cache.replacement)?;
+        shadow.rrip_bits = cache.rrip_bits;
+        shadow.set_seed(cache.seed);
+        shadow.lfu_aging_interval = cache.lfu_aging_interval;
+        shadow.set_insertion(cache.insertion, cache.bimodal_probability)?;
+        Ok(Self { shadow })
+    }
+
+    /// Look up a block in the shadow, installing it if it missed, as the real cache does.
+    /// This must be called for every lookup in the real cache, hit or miss.
+    ///
+    /// ## Arguments
+    /// * `address` - The address being looked up.
+    ///
+    /// Returns whether the shadow hit.
+    pub fn observe(&mut self, address: usize) -> bool {
+        // the shadow cache has a single set, so the whole block address is the tag
+        let block_address = self.shadow.block_address_of(address);
+        if self.shadow.read(0, block_address) == MISS {
+            if self.shadow.set_is_full(0, block_address) {
+                self.shadow.evict_lru_block(0, block_address);
+            }
+            self.shadow.install(0, block_address, address);
+            return false;
+        }
+        true
+    }
+}
@@ -0,0 +1,1544 @@
+use std::io::{self, Write};
+use clap::ValueEnum;
+use crate::access::{Access, AccessType};
+use crate::banks::BankConflicts;
+use crate::cache::block::Block;
+use crate::cache::error::CacheConfigError;
+use crate::cache::mshr::MshrFile;
+use crate::cache::cache::{Cache, EvictionResult, SetStats};
+use crate::cache::cache::HitOrMiss::{HIT, MISS};
+use crate::cache::replacement::{InsertionPosition, ReplacementPolicy};
+use crate::cache::victim::VictimCache;
+use crate::cache::write_buffer::WriteBuffer;
+use crate::classifier::{MissClassifier, MissKind};
+use crate::config::Config;
+use crate::hot_blocks::HotBlocks;
+use crate::overlap::OverlapWindow;
+use crate::prefetch::{PrefetchPlacement, StreamBuffers, StridePrefetcher};
+use crate::regions::MemoryRegions;
+use crate::replacement_log::ReplacementLog;
+use crate::reuse::ReuseHistogram;
+use crate::shadow::ShadowCache;
+use crate::statistics::Statistics;
+use crate::streaming::{MruBaseline, StreamingAction, StreamingDetector};
+use crate::tlb::Tlb;
+use crate::windows::TimeWindows;
+
+/// How the contents of L1 relate to the contents of L2.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum Inclusion {
+    /// Non-inclusive non-exclusive, L2 evictions never touch L1.
+    Nine,
+    /// Every block in L1 is also in L2, L2 evictions back-invalidate L1.
+    Inclusive,
+    /// A block is in at most one of L1 and L2.
+    Exclusive,
+}
+
+/// An L1 cache with an optional victim cache and L2 behind it.
+pub struct Simulator {
+    pub l1: Cache,
+    /// The L2 cache, or `None` if L1 is backed by main memory alone.
+    pub l2: Option<Cache>,
+    pub victim: VictimCache,
+    pub inclusion: Inclusion,
+    pub classifier: Option<MissClassifier>,
+    /// Fully-associative shadows of L1 and L2 for counting conflict misses.
+    pub l1_shadow: Option<ShadowCache>,
+    pub l2_shadow: Option<ShadowCache>,
+    pub stride_prefetcher: Option<StridePrefetcher>,
+    pub stream_buffers: Option<StreamBuffers>,
+    /// How many blocks after an L1 miss are prefetched into L1, 0 for no next-line prefetch.
+    pub l1_prefetch_degree: usize,
+    /// How many blocks after an L2 demand miss are prefetched into L2, 0 for none.
+    pub l2_prefetch_degree: usize,
+    /// Where the stride and L1 next-line prefetchers install their blocks.
+    pub prefetch_placement: PrefetchPlacement,
+    /// Whether L1 write misses allocate their block without fetching it (write-validate).
+    pub write_validate: bool,
+    /// The number of accesses between flushes of every dirty block, 0 for no periodic flush.
+    pub flush_interval: usize,
+    accesses_since_flush: usize,
+    /// Whether the invariants of every cache are checked after every access.
+    pub self_check: bool,
+    checked_accesses: usize,
+    pub streaming_detector: Option<StreamingDetector>,
+    /// A copy of L1 without streaming detection, to measure what the detection changes.
+    pub streaming_baseline: Option<MruBaseline>,
+    pub reuse_histogram: Option<ReuseHistogram>,
+    pub hot_blocks: Option<HotBlocks>,
+    pub time_windows: Option<TimeWindows>,
+    pub bank_conflicts: Option<BankConflicts>,
+    pub write_buffer: Option<WriteBuffer>,
+    pub mshrs: Option<MshrFile>,
+    pub overlap: Option<OverlapWindow>,
+    pub memory_regions: Option<MemoryRegions>,
+    pub tlb: Option<Tlb>,
+    pub stats: Statistics,
+    /// What happened during the accesses since the log was last written, recorded only when
+    /// verbose logging is enabled.
+    pub log: Option<Vec<String>>,
+    /// Every eviction since the log was last written, recorded only when replacement tracing
+    /// is enabled.
+    pub replacement_log: Option<ReplacementLog>,
+}
+
+impl Simulator {
+    /// Creates a new simulator over the given caches.
+    ///
+    /// ## Arguments
+    /// * `l1` - The L1 cache.
+    /// * `l2` - The L2 cache, or `None` for an L1-only hierarchy.
+    /// * `victim` - The victim cache between L1 and L2 (or main memory without an L2), or a
+    ///   disabled one.
+    /// * `inclusion` - The inclusion policy between L1 and L2.
+    ///
+    /// ## Throws
+    /// This function will panic if a sectored L1 is combined with a victim cache or an
+    /// exclusive hierarchy, which only move whole blocks.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // without an l2 every miss, writeback, and prefetch goes straight to main memory
+    /// let l1 = Cache::new(64, 2, 16)?;
+    /// let mut simulator = Simulator::new(l1, None, VictimCache::new(2), Inclusion::Exclusive);
+    /// simulator.enable_l1_next_line_prefetch(1);
+    /// simulator.enable_l2_next_line_prefetch(1);
+    /// simulator.enable_stream_buffers(1, 2);
+    /// for address in (0x100..0x400).step_by(0x30) {
+    ///     simulator.access(AccessType::Write, address);
+    ///     simulator.access(AccessType::Read, address + 0x8);
+    /// }
+    /// simulator.flush();
+    /// let stats = &simulator.stats;
+    /// assert!(stats.l1_write_backs > 0 && stats.l1_prefetches > 0);
+    /// assert_eq!((stats.l2_reads, stats.l2_writes, stats.l2_prefetches, stats.l2_write_backs), (0, 0, 0, 0));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn new(l1: Cache, l2: Option<Cache>, victim: VictimCache, inclusion: Inclusion) -> Self {
+        if l1.is_sectored() && victim.is_enabled() {
+            panic!("A sectored L1 can't be combined with a victim cache.");
+        }
+        if l1.is_sectored() && inclusion == Inclusion::Exclusive {
+            panic!("A sectored L1 can't be combined with an exclusive hierarchy.");
+        }
+        let mut stats = Statistics::new();
+        stats.victim_cache_enabled = victim.is_enabled();
+        stats.inclusive = inclusion == Inclusion::Inclusive;
+        stats.exclusive = inclusion == Inclusion::Exclusive && l2.is_some();
+        stats.l2_enabled = l2.is_some();
+        stats.sectored = l1.is_sectored();
+        stats.dip_enabled = l1.replacement == ReplacementPolicy::Dip;
+        Self {
+            l1,
+            l2,
+            victim,
+            inclusion,
+            classifier: None,
+            l1_shadow: None,
+            l2_shadow: None,
+            stride_prefetcher: None,
+            stream_buffers: None,
+            l1_prefetch_degree: 0,
+            l2_prefetch_degree: 0,
+            prefetch_placement: PrefetchPlacement::L1,
+            write_validate: false,
+            flush_interval: 0,
+            accesses_since_flush: 0,
+            self_check: false,
+            checked_accesses: 0,
+            streaming_detector: None,
+            streaming_baseline: None,
+            reuse_histogram: None,
+            hot_blocks: None,
+            time_windows: None,
+            bank_conflicts: None,
+            write_buffer: None,
+            mshrs: None,
+            overlap: None,
+            memory_regions: None,
+            tlb: None,
+            log: None,
+            replacement_log: None,
+            stats,
+        }
+    }
+
+    /// Classify every L1 miss as compulsory, capacity, or conflict.
+    /// This keeps a shadow fully-associative cache, so it slows the simulation down.
+    pub fn enable_miss_classification(&mut self) {
+        self.classifier = Some(
+            MissClassifier::new(self.l1.cache_size, self.l1.block_size)
+                .expect("L1 geometry was already validated")
+        );
+        self.stats.classify_misses = true;
+    }
+
+    /// Count the misses in L1 and L2 that a fully-associative cache of the same size and
+    /// replacement policy would have hit. Each level gets a shadow cache, so this slows the
+    /// simulation down, though less than classifying every miss.
+    ///
+    /// Returns an error if a level's replacement policy can't be used fully-associatively.
+    pub fn enable_conflict_shadow(&mut self) -> Result<(), CacheConfigError> {
+        self.l1_shadow = Some(ShadowCache::new(&self.l1)?);
+        if let Some(l2) = &self.l2 {
+            self.l2_shadow = Some(ShadowCache::new(l2)?);
+        }
+        self.stats.conflict_shadow = true;
+        Ok(())
+    }
+
+    /// Prefetch into L1 along strides detected between consecutive L1 misses.
+    pub fn enable_stride_prefetcher(&mut self) {
+        self.stride_prefetcher = Some(StridePrefetcher::new());
+        self.stats.prefetching = true;
+    }
+
+    /// Prefetch the next blocks into L1 after every L1 miss.
+    ///
+    /// ## Arguments
+    /// * `degree` - The number of consecutive blocks after the missing one to prefetch.
+    pub fn enable_l1_next_line_prefetch(&mut self, degree: usize) {
+        self.l1_prefetch_degree = degree;
+        self.stats.prefetching = true;
+    }
+
+    /// Prefetch the next blocks from main memory into L2 after every demand miss in L2.
+    /// Does nothing without an L2.
+    ///
+    /// ## Arguments
+    /// * `degree` - The number of consecutive blocks after the missing one to prefetch.
+    pub fn enable_l2_next_line_prefetch(&mut self, degree: usize) {
+        if self.l2.is_none() {
+            return;
+        }
+        self.l2_prefetch_degree = degree;
+        self.stats.l2_prefetching = true;
+    }
+
+    /// Choose the level the stride and L1 next-line prefetchers install their blocks in.
+    /// Prefetching into L2 leaves L1 exactly as it would be without prefetching, and a
+    /// demand access that misses L1 but finds a prefetched block in L2 is an ordinary L2 hit,
+    /// counted as a useful L2 prefetch. Stream buffers keep their own blocks either way.
+    ///
+    /// ## Arguments
+    /// * `placement` - The level prefetched blocks go into.
+    ///
+    /// Returns an error if the blocks should go into L2 but there is no L2.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::prefetch::PrefetchPlacement;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // read through 256 consecutive blocks once each
+    /// let run = |prefetch: bool| -> Result<_, Box<dyn std::error::Error>> {
+    ///     let l2 = Cache::new(1024, 4, 16)?;
+    ///     let mut simulator = Simulator::new(Cache::new(64, 2, 16)?, Some(l2), VictimCache::new(0), Inclusion::Nine);
+    ///     if prefetch {
+    ///         simulator.enable_l1_next_line_prefetch(1);
+    ///         simulator.set_prefetch_placement(PrefetchPlacement::L2)?;
+    ///     }
+    ///     for address in (0x1000..0x2000).step_by(0x10) {
+    ///         simulator.access(AccessType::Read, address);
+    ///     }
+    ///     Ok(simulator.stats)
+    /// };
+    /// let (plain, prefetched) = (run(false)?, run(true)?);
+    ///
+    /// // l1 misses on every block either way, but only the first block misses in l2
+    /// assert_eq!((plain.l1_read_misses, prefetched.l1_read_misses), (256, 256));
+    /// assert_eq!(prefetched.l1_prefetches, 0);
+    /// assert_eq!((plain.l2_read_misses, prefetched.l2_read_misses), (256, 1));
+    /// assert_eq!((prefetched.l2_prefetches, prefetched.l2_useful_prefetches), (256, 255));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_prefetch_placement(&mut self, placement: PrefetchPlacement) -> Result<(), String> {
+        if placement == PrefetchPlacement::L2 {
+            if self.l2.is_none() {
+                return Err("Prefetching into L2 needs an L2.".to_string());
+            }
+            self.stats.l2_prefetching = true;
+        }
+        self.prefetch_placement = placement;
+        Ok(())
+    }
+
+    /// Put stream buffers beside L1. An L1 miss at the head of a buffer is served from it,
+    /// and a miss in every buffer starts a new stream in the least recently used one.
+    ///
+    /// ## Arguments
+    /// * `count` - The number of stream buffers.
+    /// * `depth` - The number of blocks each buffer prefetches ahead.
+    pub fn enable_stream_buffers(&mut self, count: usize, depth: usize) {
+        self.stream_buffers = Some(StreamBuffers::new(count, depth));
+        self.stats.stream_buffers_enabled = true;
+    }
+
+    /// Detect streaming through L1 as a run of consecutive misses, and keep the blocks of a
+    /// stream from pushing out blocks that are being reused. A copy of L1 that inserts every
+    /// block at MRU runs beside it to count the misses this avoids and adds.
+    ///
+    /// ## Arguments
+    /// * `threshold` - The number of consecutive L1 misses that signal a stream.
+    /// * `action` - Whether streamed blocks are inserted at LRU or bypass L1.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    /// use cache_simulator::streaming::StreamingAction;
+    ///
+    /// // a 16-block l1 reuses 8 hot blocks between scans of 32 blocks it never sees again
+    /// let mut simulator = Simulator::new(Cache::new(256, 4, 16)?, None, VictimCache::new(0), Inclusion::Nine);
+    /// simulator.enable_streaming_detection(4, StreamingAction::Bypass);
+    /// let mut scan = 0x1000;
+    /// for _ in 0..4 {
+    ///     for hot in (0..0x80).step_by(0x10).cycle().take(16) {
+    ///         simulator.access(AccessType::Read, hot);
+    ///     }
+    ///     for _ in 0..32 {
+    ///         simulator.access(AccessType::Read, scan);
+    ///         scan += 0x10;
+    ///     }
+    /// }
+    ///
+    /// // all but the first 3 misses of each scan bypass l1, and so do some hot blocks, whose
+    /// // misses carry a stream on until a hit ends it. keeping the hot blocks saves 22 misses
+    /// // and bypassing them costs 5, next to the 160 misses of always-MRU insertion
+    /// let stats = &simulator.stats;
+    /// assert_eq!(stats.bypasses, 126);
+    /// assert_eq!((stats.streaming_misses_avoided, stats.streaming_misses_added), (22, 5));
+    /// assert_eq!(stats.l1_read_misses, 160 - 22 + 5);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn enable_streaming_detection(&mut self, threshold: usize, action: StreamingAction) {
+        self.streaming_detector = Some(StreamingDetector::new(threshold, action));
+        self.streaming_baseline = Some(MruBaseline::new(&self.l1).expect("L1 geometry was already validated"));
+        self.stats.streaming_detection = true;
+    }
+
+    /// Measure the reuse distance of every access, at the L1 block size.
+    /// This keeps every distinct block in a stack, so it is slow and memory-heavy.
+    pub fn enable_reuse_histogram(&mut self) {
+        self.reuse_histogram = Some(ReuseHistogram::new());
+    }
+
+    /// Count the accesses and L1 hits of every block address, to report the hottest blocks.
+    /// This keeps an entry for every distinct block, so it costs memory.
+    pub fn enable_hot_blocks(&mut self) {
+        self.hot_blocks = Some(HotBlocks::new());
+    }
+
+    /// Check the bookkeeping of L1 and L2 after every access with `Cache::check_invariants`,
+    /// to catch a bug at the access that caused it. This goes over every set of both caches
+    /// each time, so it slows the simulation down a lot.
+    ///
+    /// ## Throws
+    /// `access` will panic with the access number and the broken set once an invariant breaks.
+    ///
+    /// ## Example
+    /// ```should_panic
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// let mut simulator = Simulator::new(Cache::new(64, 2, 16)?, None, VictimCache::new(0), Inclusion::Nine);
+    /// simulator.enable_self_check();
+    /// simulator.access(AccessType::Read, 0x110);
+    /// simulator.access(AccessType::Read, 0x130);
+    ///
+    /// // corrupt the replacement state behind the simulator's back, then touch another set
+    /// simulator.l1.recency.set_rank(1, 0, 0);
+    /// simulator.access(AccessType::Read, 0x100); // panics: access 3, L1 set 1
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn enable_self_check(&mut self) {
+        self.self_check = true;
+    }
+
+    /// Count the accesses and misses in every window of time of `run`, by the timestamps of
+    /// the trace records, or by their index in the trace for records without one.
+    ///
+    /// ## Arguments
+    /// * `window` - The number of cycles in each window.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::config::Config;
+    /// use cache_simulator::sweep::Geometry;
+    /// use cache_simulator::trace::{read_trace, AddressRadix, TraceFormat};
+    /// use cache_simulator::windows::WindowCounts;
+    ///
+    /// // a burst of misses at cycles 1000-1003, then hits until 2500
+    /// let trace = "r 100 @1000\nr 200 @1001\nr 300 @1002\nr 400 @1003\nr 100 @2100\nr 400 @2500\n";
+    /// let trace = read_trace(trace.as_bytes(), TraceFormat::Legacy, AddressRadix::Hex).unwrap();
+    /// let geometry = Geometry { block_size: 16, l1_size: 256, l1_assoc: 4, l2_size: 0, l2_assoc: 0 };
+    /// let mut simulator = Config::new(geometry).build_simulator()?;
+    /// simulator.enable_time_windows(1000);
+    /// simulator.run(trace, 0, false, |_| Ok(())).unwrap();
+    /// let windows = &simulator.time_windows.as_ref().unwrap().windows;
+    /// assert_eq!(windows, &[
+    ///     WindowCounts { start: 1000, accesses: 4, l1_misses: 4, l2_misses: 0 },
+    ///     WindowCounts { start: 2000, accesses: 2, l1_misses: 0, l2_misses: 0 },
+    /// ]);
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn enable_time_windows(&mut self, window: u64) {
+        self.time_windows = Some(TimeWindows::new(window));
+    }
+
+    /// Divide L1 into banks by the low bits of the index and count accesses that go to the
+    /// same bank as one of the accesses just before them.
+    ///
+    /// ## Arguments
+    /// * `banks` - The number of banks.
+    /// * `window` - How many of the previous accesses an access can conflict with.
+    pub fn enable_banks(&mut self, banks: usize, window: usize) {
+        self.bank_conflicts = Some(BankConflicts::new(banks, window));
+        self.stats.banks_enabled = true;
+    }
+
+    /// Allocate the block of an L1 write miss without fetching it, with only the written
+    /// sub-block valid (write-validate). The sub-block is the sector in a sectored L1 and the
+    /// whole block otherwise, so in an unsectored L1 a write is taken to cover its block.
+    ///
+    /// ## Throws
+    /// This function will panic if L2 is inclusive, since the block would never reach L2.
+    pub fn enable_write_validate(&mut self) {
+        if self.inclusion == Inclusion::Inclusive && self.l2.is_some() {
+            panic!("Write-validate can't be combined with an inclusive hierarchy.");
+        }
+        self.write_validate = true;
+        self.stats.write_validate = true;
+    }
+
+    /// Write back and clean every dirty block in the hierarchy after every `interval`
+    /// accesses, as a system that flushes on a timer would.
+    ///
+    /// ## Arguments
+    /// * `interval` - The number of accesses between flushes.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::{Access, AccessType};
+    /// use cache_simulator::config::Config;
+    /// use cache_simulator::simulator::simulate;
+    /// use cache_simulator::sweep::Geometry;
+    ///
+    /// // four blocks dirtied and flushed after the fourth write, then one dirtied again
+    /// let geometry = Geometry { block_size: 16, l1_size: 64, l1_assoc: 2, l2_size: 0, l2_assoc: 0 };
+    /// let trace = [0x100, 0x110, 0x120, 0x130, 0x100, 0x104]
+    ///     .map(|address| Access { rw: AccessType::Write, address, size: 1, timestamp: None });
+    /// let config = Config { flush_interval: 4, ..Config::new(geometry) };
+    /// let stats = simulate(&config, trace.into_iter());
+    /// assert_eq!((stats.periodic_flushes, stats.l1_write_backs, stats.total_memory_traffic), (1, 4, 8));
+    ///
+    /// // in a sectored l1 the flush cleans every sector: three sectors of 0x100 are written
+    /// // back by the flush, and only the one written after it when 0x300 evicts the block
+    /// let geometry = Geometry { block_size: 32, l1_size: 64, l1_assoc: 2, l2_size: 0, l2_assoc: 0 };
+    /// let trace = [(AccessType::Write, 0x100), (AccessType::Write, 0x108), (AccessType::Write, 0x110),
+    ///     (AccessType::Write, 0x118), (AccessType::Read, 0x200), (AccessType::Read, 0x300)]
+    ///     .map(|(rw, address)| Access { rw, address, size: 1, timestamp: None });
+    /// let config = Config { flush_interval: 3, sector_size: Some(8), ..Config::new(geometry) };
+    /// let stats = simulate(&config, trace.into_iter());
+    /// assert_eq!((stats.l1_write_backs, stats.write_back_bytes), (2, 4 * 8));
+    /// ```
+    pub fn enable_periodic_flush(&mut self, interval: usize) {
+        self.flush_interval = interval;
+        self.stats.periodic_flush = true;
+    }
+
+    /// Send writebacks to main memory through a bounded write buffer instead of counting
+    /// them as memory traffic straight away.
+    ///
+    /// ## Arguments
+    /// * `depth` - The number of writebacks the buffer can hold.
+    /// * `drain_interval` - The number of accesses it takes to drain one writeback.
+    pub fn enable_write_buffer(&mut self, depth: usize, drain_interval: usize) {
+        self.write_buffer = Some(WriteBuffer::new(depth, drain_interval));
+        self.stats.write_buffer_enabled = true;
+    }
+
+    /// Translate every access through a TLB before it reaches L1. TLB misses add
+    /// `miss_penalty` cycles to the average access time but don't change what the caches do.
+    ///
+    /// ## Arguments
+    /// * `tlb` - The TLB to translate through.
+    /// * `miss_penalty` - Cycles taken to fill the TLB on a miss.
+    pub fn enable_tlb(&mut self, tlb: Tlb, miss_penalty: usize) {
+        self.tlb = Some(tlb);
+        self.stats.tlb_enabled = true;
+        self.stats.tlb_miss_penalty = miss_penalty;
+    }
+
+    /// Bound the number of L1 misses outstanding at once with miss status holding registers.
+    /// Misses to a block that is still outstanding merge into its register instead of taking
+    /// a new one. The counts of misses and traffic don't change, the merges and stalls are
+    /// counted on the side.
+    ///
+    /// ## Arguments
+    /// * `entries` - The number of registers.
+    /// * `window` - The number of accesses a miss stays outstanding.
+    pub fn enable_mshrs(&mut self, entries: usize, window: usize) {
+        self.mshrs = Some(MshrFile::new(entries, window));
+        self.stats.mshrs_enabled = true;
+    }
+
+    /// Treat the misses of each window of consecutive accesses as overlapping, and estimate
+    /// the stall cycles with each level's latency charged once per window instead of once
+    /// per miss. The counts of misses and traffic don't change.
+    ///
+    /// ## Arguments
+    /// * `window` - The number of accesses in each window.
+    pub fn enable_overlap_window(&mut self, window: usize) {
+        self.overlap = Some(OverlapWindow::new(window));
+        self.stats.overlap_enabled = true;
+    }
+
+    /// Split main memory into regions by address, counting the traffic to each and charging
+    /// demand fetches from each its own multiple of the memory latency.
+    ///
+    /// ## Arguments
+    /// * `regions` - The regions main memory is split into.
+    pub fn enable_memory_regions(&mut self, regions: MemoryRegions) {
+        let multipliers = regions.latency_multipliers();
+        self.stats.region_traffic_bytes = vec![0; multipliers.len()];
+        self.stats.region_memory_reads = vec![0; multipliers.len()];
+        self.stats.region_latency_multipliers = multipliers;
+        self.memory_regions = Some(regions);
+    }
+
+    /// Drain every writeback still in the write buffer to main memory, as happens when a
+    /// program finishes. Does nothing without a write buffer.
+    pub fn drain_write_buffer(&mut self) {
+        while let Some(bytes) = self.write_buffer.as_mut().and_then(|write_buffer| write_buffer.pop()) {
+            self.count_memory_traffic(bytes);
+        }
+    }
+
+    /// Record what happens at each level during every access, to be written out with
+    /// `write_log`. This doesn't change any statistics.
+    pub fn enable_verbose_log(&mut self) {
+        self.log = Some(vec![]);
+    }
+
+    /// Record every eviction from L1 and L2, with the set, the evicted and installed tags, and
+    /// the number of the access that caused it, to be written out with
+    /// `write_replacement_log`. This doesn't change any statistics.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // one set of two ways, so the tag is the address over 16
+    /// let l1 = Cache::new(32, 2, 16)?;
+    /// let mut simulator = Simulator::new(l1, None, VictimCache::new(0), Inclusion::Nine);
+    /// simulator.enable_replacement_log();
+    /// for address in [0x10, 0x20, 0x10, 0x30, 0x20, 0x40] {
+    ///     simulator.access(AccessType::Read, address);
+    /// }
+    /// let mut out = vec![];
+    /// simulator.write_replacement_log(&mut out).unwrap();
+    /// assert_eq!(String::from_utf8(out).unwrap(), "L1,0,2,3,4\nL1,0,1,2,5\nL1,0,3,4,6\n");
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn enable_replacement_log(&mut self) {
+        self.replacement_log = Some(ReplacementLog::new());
+    }
+
+    /// Write out and clear the replacement log. Does nothing if replacement tracing isn't
+    /// enabled.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the log, e.g. a file.
+    pub fn write_replacement_log<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self.replacement_log.as_mut() {
+            Some(replacement_log) => replacement_log.write(out),
+            None => Ok(()),
+        }
+    }
+
+    /// Write out and clear the verbose log. Does nothing if verbose logging isn't enabled.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the log, e.g. stdout.
+    pub fn write_log<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if let Some(log) = self.log.as_mut() {
+            for event in log.drain(..) {
+                writeln!(out, "{}", event)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record an event in the verbose log. The event is only formatted if logging is enabled.
+    ///
+    /// ## Arguments
+    /// * `event` - Builds the description of the event.
+    fn log_event(&mut self, event: impl FnOnce() -> String) {
+        if let Some(log) = self.log.as_mut() {
+            log.push(event());
+        }
+    }
+
+    /// Stop warming up the caches: zero the statistics and per-set counters so that only
+    /// accesses from now on are measured, while keeping everything the caches hold.
+    /// Blocks prefetched during warmup are no longer tracked, so their later hits don't count
+    /// as useful prefetches of the measured region.
+    pub fn end_warmup(&mut self) {
+        self.stats.reset();
+        for cache in std::iter::once(&mut self.l1).chain(self.l2.as_mut()) {
+            for set_stats in cache.set_stats.iter_mut() {
+                *set_stats = SetStats::default();
+            }
+        }
+        for block in self.l1.cache.iter_mut() {
+            block.prefetched = false;
+        }
+        if let Some(reuse_histogram) = self.reuse_histogram.as_mut() {
+            reuse_histogram.reset_counts();
+        }
+        if let Some(hot_blocks) = self.hot_blocks.as_mut() {
+            hot_blocks.counts.clear();
+        }
+        if let Some(time_windows) = self.time_windows.as_mut() {
+            time_windows.reset();
+        }
+    }
+
+    /// Simulate a whole trace: every access, the end of the warmup, and the flush and write
+    /// buffer drain at the end. An access that spans several L1 blocks looks each of them up
+    /// as an access of its own.
+    ///
+    /// ## Arguments
+    /// * `accesses` - The records of the trace.
+    /// * `warmup` - The number of records that only warm up the caches.
+    /// * `flush_at_end` - Whether to write back every dirty block after the last record.
+    /// * `after_access` - Called after every record, e.g. to write out the verbose log.
+    ///
+    /// Returns the first error from `after_access`, which stops the simulation.
+    pub fn run<I, F>(&mut self, accesses: I, warmup: usize, flush_at_end: bool, mut after_access: F) -> io::Result<()>
+    where
+        I: IntoIterator<Item = Access>,
+        F: FnMut(&mut Self) -> io::Result<()>,
+    {
+        let mut records = 0;
+        for access in accesses {
+            if let Some(time_windows) = self.time_windows.as_mut() {
+                time_windows.observe(access.timestamp.unwrap_or(records as u64), &self.stats);
+            }
+            let mut lookups = 0;
+            for address in access.lookups(self.l1.block_size) {
+                self.access(access.rw, address);
+                lookups += 1;
+            }
+            if lookups > 1 {
+                self.stats.split_accesses += 1;
+            }
+            after_access(self)?;
+
+            // only measure what comes after the warmup records
+            records += 1;
+            if records == warmup {
+                self.end_warmup();
+            }
+        }
+        if records < warmup {
+            self.end_warmup();
+        }
+        if let Some(time_windows) = self.time_windows.as_mut() {
+            time_windows.finish(&self.stats);
+        }
+        if flush_at_end {
+            self.flush();
+        }
+        self.drain_write_buffer();
+        Ok(())
+    }
+
+    /// Simulate a single access through the hierarchy, updating the statistics, and flush
+    /// the hierarchy if the access completes a flush interval.
+    ///
+    /// ## Arguments
+    /// * `access_type` - Whether the access is a read, a write, or an instruction fetch.
+    /// * `address` - The address being accessed.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // a single 2-way set in l1, and a direct-mapped l2 where 0x100, 0x120, and 0x140
+    /// // all map to set 0
+    /// let (l1, l2) = (Cache::new(32, 2, 16)?, Cache::new(32, 1, 16)?);
+    /// let mut simulator = Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Nine);
+    ///
+    /// // l1 and l2 miss, l2 fetches 0x100 (1 transfer) and l1 holds it dirty
+    /// simulator.access(AccessType::Write, 0x100);
+    /// // l1 and l2 miss, l2 evicts its clean copy of 0x100 and fetches 0x120 (2 transfers)
+    /// simulator.access(AccessType::Read, 0x120);
+    /// // l1 misses and evicts dirty 0x100, whose writeback misses l2: l2 evicts clean 0x120
+    /// // without a writeback and fetches 0x100 (3 transfers), leaving it dirty. the read of
+    /// // 0x140 then misses l2, which evicts that dirty 0x100, writing it back to memory
+    /// // (4 transfers), and fetches 0x140 (5 transfers)
+    /// simulator.access(AccessType::Read, 0x140);
+    ///
+    /// let stats = &simulator.stats;
+    /// assert_eq!((stats.l1_write_backs, stats.l2_writes, stats.l2_write_misses), (1, 1, 1));
+    /// assert_eq!((stats.l2_evictions, stats.l2_clean_evictions, stats.l2_write_backs), (3, 2, 1));
+    /// assert_eq!(stats.total_memory_traffic, 5);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn access(&mut self, access_type: AccessType, address: usize) {
+        if let Some(replacement_log) = self.replacement_log.as_mut() {
+            replacement_log.accesses += 1;
+        }
+        let misses = (self.stats.l1_misses(), self.stats.l2_read_misses);
+        self.access_hierarchy(access_type, address);
+        if self.self_check {
+            self.check_invariants();
+        }
+        if let Some(overlap) = self.overlap.as_mut() {
+            let (l1_charged, l2_charged) =
+                overlap.observe(self.stats.l1_misses() > misses.0, self.stats.l2_read_misses > misses.1);
+            self.stats.overlapped_l1_misses += usize::from(l1_charged);
+            self.stats.overlapped_l2_misses += usize::from(l2_charged);
+        }
+        if self.flush_interval == 0 {
+            return;
+        }
+        self.accesses_since_flush += 1;
+        if self.accesses_since_flush == self.flush_interval {
+            self.accesses_since_flush = 0;
+            self.stats.periodic_flushes += 1;
+            self.log_event(|| "  periodic flush".to_string());
+            self.flush();
+        }
+    }
+
+    /// Simulate a single access through the caches and the structures around them.
+    ///
+    /// ## Arguments
+    /// * `access_type` - Whether the access is a read, a write, or an instruction fetch.
+    /// * `address` - The address being accessed.
+    fn access_hierarchy(&mut self, access_type: AccessType, address: usize) {
+        let (l1_index, l1_tag) = (self.l1.index_of(address), self.l1.tag_of(address));
+        self.stats.total_accesses += 1;
+        if let Some(bytes) = self.write_buffer.as_mut().and_then(|write_buffer| write_buffer.tick()) {
+            self.count_memory_traffic(bytes);
+        }
+        if let Some(mshrs) = self.mshrs.as_mut() {
+            mshrs.tick();
+        }
+        if let Some(tlb) = self.tlb.as_mut() {
+            if tlb.translate(address) {
+                self.stats.tlb_hits += 1;
+            } else {
+                self.stats.tlb_misses += 1;
+            }
+        }
+        let miss_kind = self.classifier.as_mut().map(|classifier| classifier.observe(address));
+        let l1_shadow_hit = self.l1_shadow.as_mut().is_some_and(|shadow| shadow.observe(address));
+        if let Some(reuse_histogram) = self.reuse_histogram.as_mut() {
+            reuse_histogram.observe(self.l1.block_address_of(address));
+        }
+        if self.bank_conflicts.as_mut().is_some_and(|banks| banks.observe(l1_index)) {
+            self.stats.bank_conflicts += 1;
+        }
+        self.log_event(|| format!("{:?} {:x}: L1 index {} tag {:x}", access_type, address, l1_index, l1_tag));
+
+        // try to read from l1
+        let l1_hit_or_miss =
+            if access_type == AccessType::Write {
+                self.l1.write(l1_index, l1_tag)
+            } else {
+                self.l1.read(l1_index, l1_tag)
+            };
+        // in a sectored l1 the tag can hit while the sector being accessed isn't there yet
+        let sector = self.l1.sector_of(address);
+        let sector_miss = self.l1.is_sectored()
+            && l1_hit_or_miss == HIT
+            && !self.l1.has_sector(l1_index, l1_tag, sector);
+        let l1_hit_or_miss = if sector_miss { MISS } else { l1_hit_or_miss };
+        self.l1.record_set_access(l1_index, &l1_hit_or_miss);
+        let l1_hit = l1_hit_or_miss == HIT;
+        let streaming = self.streaming_detector.as_mut().is_some_and(|detector| detector.observe(l1_hit));
+        if let Some(baseline) = self.streaming_baseline.as_mut() {
+            // a sector miss is still a block hit, which is all the baseline tracks
+            let block_hit = l1_hit || sector_miss;
+            match (baseline.observe(address, access_type == AccessType::Write), block_hit) {
+                (true, false) => self.stats.streaming_misses_added += 1,
+                (false, true) => self.stats.streaming_misses_avoided += 1,
+                _ => {}
+            }
+        }
+        if let Some(hot_blocks) = self.hot_blocks.as_mut() {
+            hot_blocks.observe(self.l1.block_address_of(address), l1_hit);
+        }
+        self.log_event(|| format!("  L1 {}", if l1_hit { "hit" } else { "miss" }));
+        if l1_hit_or_miss == HIT {
+            // the block is resident, but its data may not have arrived yet
+            self.track_miss(address, true);
+
+            // we hit in l1, maybe thanks to a prefetch
+            if self.l1.take_prefetched(l1_index, l1_tag) {
+                self.stats.useful_prefetches += 1;
+            }
+            if access_type == AccessType::Write && self.l1.is_sectored() {
+                self.l1.mark_sector_dirty(l1_index, l1_tag, sector);
+            }
+            self.count_l1_access(access_type);
+            return;
+        }
+
+        // we missed in l1
+        match access_type {
+            AccessType::Read => self.stats.l1_read_misses += 1,
+            AccessType::Write => self.stats.l1_write_misses += 1,
+            AccessType::InstructionFetch => self.stats.l1_instruction_fetch_misses += 1,
+        }
+        self.track_miss(address, false);
+        if sector_miss {
+            // the block is already resident, so only the sector has to be fetched
+            self.stats.sector_misses += 1;
+            self.fetch_unless_validated(access_type, address);
+            self.l1.fill_sector(l1_index, l1_tag, sector, access_type == AccessType::Write);
+            self.count_l1_access(access_type);
+            return;
+        }
+        match miss_kind {
+            Some(MissKind::Compulsory) => self.stats.compulsory_misses += 1,
+            Some(MissKind::Capacity) => self.stats.capacity_misses += 1,
+            Some(MissKind::Conflict) => self.stats.conflict_misses += 1,
+            None => {}
+        }
+        if l1_shadow_hit {
+            self.stats.l1_shadow_conflict_misses += 1;
+        }
+
+        // a detected stream shouldn't push out the blocks l1 is reusing
+        let block_address = self.l1.block_address_of(address);
+        let streaming_action = self.streaming_detector
+            .as_ref()
+            .filter(|_| streaming)
+            .map(|detector| detector.action);
+        match streaming_action {
+            Some(StreamingAction::Bypass) if access_type != AccessType::Write && !self.victim.contains(block_address) => {
+                self.stats.bypasses += 1;
+                self.log_event(|| "  bypass L1".to_string());
+                self.fetch_for_l1(self.l1.sector_size, address);
+                self.count_l1_access(access_type);
+            }
+            Some(_) => {
+                self.stats.lru_insertions += 1;
+                let insertion = std::mem::replace(&mut self.l1.insertion, InsertionPosition::Lru);
+                self.fill_l1(access_type, address);
+                self.l1.insertion = insertion;
+            }
+            None => self.fill_l1(access_type, address),
+        }
+
+        // train the prefetcher on the miss and prefetch along any confirmed stride
+        let prefetch = self.stride_prefetcher
+            .as_mut()
+            .and_then(|prefetcher| prefetcher.observe_miss(block_address));
+        if let Some(prefetch_block_address) = prefetch {
+            self.prefetch_for_l1(prefetch_block_address);
+        }
+        for next in 1..=self.l1_prefetch_degree {
+            self.prefetch_for_l1(block_address + next);
+        }
+
+        // the dip policy selectors only move on misses
+        if self.stats.dip_enabled {
+            self.stats.l1_psel = self.l1.psel;
+            self.stats.l2_psel = self.l2.as_ref().map_or(0, |l2| l2.psel);
+        }
+    }
+
+    /// Check the invariants of every cache after an access.
+    ///
+    /// ## Throws
+    /// This function will panic with the number of the access, counting from 1, and the
+    /// cache and set that broke an invariant.
+    fn check_invariants(&mut self) {
+        self.checked_accesses += 1;
+        let l2 = self.l2.iter().map(|l2| ("L2", l2));
+        for (name, cache) in std::iter::once(("L1", &self.l1)).chain(l2) {
+            if let Err(e) = cache.check_invariants() {
+                panic!("Cache invariant broken after access {}: {} {}", self.checked_accesses, name, e);
+            }
+        }
+    }
+
+    /// Account for an access in the MSHRs, if there are any. An access to a block that is
+    /// still outstanding is a secondary miss, and any other L1 miss takes a register.
+    ///
+    /// ## Arguments
+    /// * `address` - The address being accessed.
+    /// * `l1_hit` - Whether the block was resident in L1.
+    fn track_miss(&mut self, address: usize, l1_hit: bool) {
+        let block_address = self.l1.block_address_of(address);
+        let Some(mshrs) = self.mshrs.as_mut() else {
+            return;
+        };
+        if mshrs.is_outstanding(block_address) {
+            self.stats.mshr_secondary_misses += 1;
+        } else if !l1_hit && mshrs.allocate(block_address) {
+            self.stats.mshr_stalls += 1;
+        }
+    }
+
+    /// Bring the block for an access that missed in L1 into L1, from the victim cache, L2,
+    /// or main memory.
+    ///
+    /// ## Arguments
+    /// * `access_type` - Whether the access is a read, a write, or an instruction fetch.
+    /// * `address` - The address being accessed.
+    fn fill_l1(&mut self, access_type: AccessType, address: usize) {
+        let (l1_index, l1_tag) = (self.l1.index_of(address), self.l1.tag_of(address));
+
+        // a hit in the victim cache swaps the block back into l1 without touching l2
+        if self.victim.is_enabled() {
+            if let Some(block) = self.victim.take(self.l1.block_address_of(address)) {
+                self.stats.victim_hits += 1;
+                self.log_event(|| "  victim cache hit".to_string());
+                self.make_room_in_l1(l1_index, l1_tag);
+                if block.dirty || access_type == AccessType::Write {
+                    self.l1.install_for_write(l1_index, l1_tag, block.address);
+                } else {
+                    self.l1.install(l1_index, l1_tag, block.address);
+                }
+                self.claim_from_l2(address);
+                self.count_l1_access(access_type);
+                return;
+            }
+        }
+
+        // a hit at the head of a stream buffer moves the block into l1 and tops the buffer up
+        let block_address = self.l1.block_address_of(address);
+        if let Some(refill) = self.stream_buffers.as_mut().and_then(|buffers| buffers.take(block_address)) {
+            self.stats.stream_buffer_hits += 1;
+            self.log_event(|| "  stream buffer hit".to_string());
+            self.make_room_in_l1(l1_index, l1_tag);
+            if access_type == AccessType::Write {
+                self.l1.install_for_write(l1_index, l1_tag, address);
+            } else {
+                self.l1.install(l1_index, l1_tag, address);
+            }
+            self.claim_from_l2(address);
+            self.count_l1_access(access_type);
+            self.prefetch_into_stream_buffer(refill);
+            return;
+        }
+
+        // check if we need to evict a block before inserting
+        self.make_room_in_l1(l1_index, l1_tag);
+
+        // a sectored l1 only fetches the sector being accessed
+        self.fetch_unless_validated(access_type, address);
+        self.install_in_l1(access_type, address);
+        self.claim_from_l2(address);
+        self.count_l1_access(access_type);
+
+        // follow the block that missed everywhere with a new stream
+        if let Some(blocks) = self.stream_buffers.as_mut().map(|buffers| buffers.allocate(block_address)) {
+            for block in blocks {
+                self.prefetch_into_stream_buffer(block);
+            }
+        }
+    }
+
+    /// Fetch the sector or block an L1 miss needs, unless the miss is a write that
+    /// write-validate lets L1 allocate without the data.
+    ///
+    /// ## Arguments
+    /// * `access_type` - Whether the access is a read, a write, or an instruction fetch.
+    /// * `address` - The address being accessed.
+    fn fetch_unless_validated(&mut self, access_type: AccessType, address: usize) {
+        if self.write_validate && access_type == AccessType::Write {
+            self.stats.write_validated_misses += 1;
+            self.log_event(|| "  write-validate, no fetch".to_string());
+            return;
+        }
+        self.fetch_for_l1(self.l1.sector_size, address);
+    }
+
+    /// Fetch data that missed in L1 from L2, installing its block in L2 from main memory if
+    /// L2 misses too, or straight from main memory if there is no L2. An exclusive L2 isn't
+    /// filled from memory, since the block is going to L1 instead.
+    ///
+    /// ## Arguments
+    /// * `bytes` - How much L1 is fetching, a block or a single sector.
+    /// * `address` - The address being accessed.
+    fn fetch_for_l1(&mut self, bytes: usize, address: usize) {
+        // check if we have an l2
+        if self.l2.is_none() {
+            self.count_memory_read(address);
+            self.count_memory_transfer(address, bytes);
+            return;
+        }
+
+        // try to read block from l2
+        // every demand access that reaches l2 is one l2 read, hit or miss
+        let (l2_index, l2_tag) = (self.l2().index_of(address), self.l2().tag_of(address));
+        let l2_hit_or_miss = self.l2_mut().read(l2_index, l2_tag);
+        self.l2_mut().record_set_access(l2_index, &l2_hit_or_miss);
+        let l2_shadow_hit = self.l2_shadow.as_mut().is_some_and(|shadow| shadow.observe(address));
+        self.stats.l2_reads += 1;
+        let l2_hit = l2_hit_or_miss == HIT;
+        self.log_event(|| format!("  L2 {}", if l2_hit { "hit" } else { "miss" }));
+        if l2_hit && self.l2_mut().take_prefetched(l2_index, l2_tag) {
+            self.stats.l2_useful_prefetches += 1;
+        }
+        if l2_hit_or_miss == MISS {
+            self.stats.l2_read_misses += 1;
+            if l2_shadow_hit {
+                self.stats.l2_shadow_conflict_misses += 1;
+            }
+            let l2_block_address = self.l2().block_address_of(address);
+            for next in 1..=self.l2_prefetch_degree {
+                self.prefetch_into_l2(l2_block_address + next);
+            }
+            self.count_memory_read(address);
+            if self.inclusion == Inclusion::Exclusive {
+                self.count_memory_transfer(address, bytes);
+                return;
+            }
+
+            // check if we need to evict a block before installing
+            self.make_room_in_l2(l2_index, l2_tag);
+
+            // install in l2
+            self.l2_mut().install(l2_index, l2_tag, address);
+            self.count_memory_transfer(address, self.l2().block_size);
+        }
+    }
+
+    /// Install the block for a demand access that missed in L1, dirty if the access is a write.
+    /// The caller counts the access.
+    ///
+    /// ## Arguments
+    /// * `access_type` - Whether the access is a read, a write, or an instruction fetch.
+    /// * `address` - The address being accessed.
+    fn install_in_l1(&mut self, access_type: AccessType, address: usize) {
+        let (l1_index, l1_tag) = (self.l1.index_of(address), self.l1.tag_of(address));
+        if self.l1.is_sectored() {
+            let sector = self.l1.sector_of(address);
+            self.l1.install_sector(l1_index, l1_tag, address, sector, access_type == AccessType::Write);
+        } else if access_type == AccessType::Write {
+            self.l1.install_for_write(l1_index, l1_tag, address);
+        } else {
+            self.l1.install(l1_index, l1_tag, address);
+        }
+    }
+
+    /// Prefetch a block an L1 prefetcher asked for into the level prefetches are placed in.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The L1 block address of the block to prefetch.
+    fn prefetch_for_l1(&mut self, block_address: usize) {
+        match self.prefetch_placement {
+            PrefetchPlacement::L1 => self.prefetch_into_l1(block_address),
+            PrefetchPlacement::L2 => {
+                let address = block_address << self.l1.block_offset_bits;
+                self.prefetch_into_l2(address >> self.l2().block_offset_bits);
+            }
+        }
+    }
+
+    /// Prefetch a block into L1 from L2, or from main memory if there is no L2.
+    /// Blocks already in L1 or the victim cache aren't prefetched again.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address of the block to prefetch.
+    fn prefetch_into_l1(&mut self, block_address: usize) {
+        let address = block_address << self.l1.block_offset_bits;
+        let (l1_index, l1_tag) = (self.l1.index_of(address), self.l1.tag_of(address));
+        if self.l1.contains(l1_index, l1_tag) || self.victim.contains(block_address) {
+            return;
+        }
+        self.stats.l1_prefetches += 1;
+        self.log_event(|| format!("  prefetch {:x} into L1", address));
+        self.make_room_in_l1(l1_index, l1_tag);
+        self.fetch_prefetch(address);
+        self.l1.install_prefetch(l1_index, l1_tag, address);
+        self.claim_from_l2(address);
+    }
+
+    /// Under an exclusive policy, remove a block that was just installed in L1 from L2, so
+    /// it's only held in one level. A dirty L2 copy leaves the L1 copy dirty.
+    ///
+    /// ## Arguments
+    /// * `address` - The address of the block installed in L1.
+    fn claim_from_l2(&mut self, address: usize) {
+        if self.inclusion != Inclusion::Exclusive || self.l2.is_none() {
+            return;
+        }
+        let (l2_index, l2_tag) = (self.l2().index_of(address), self.l2().tag_of(address));
+        if let Some(l2_evicted_result) = self.l2_mut().invalidate(l2_index, l2_tag) {
+            if l2_evicted_result.evicted_block_was_dirty {
+                self.l1.mark_dirty(self.l1.index_of(address), self.l1.tag_of(address));
+            }
+        }
+    }
+
+    /// Prefetch a block into L2 from main memory. Blocks already in L2 aren't prefetched
+    /// again, and neither are blocks in L1 when L2 is exclusive.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address of the block to prefetch.
+    fn prefetch_into_l2(&mut self, block_address: usize) {
+        let address = block_address << self.l2().block_offset_bits;
+        let (l2_index, l2_tag) = (self.l2().index_of(address), self.l2().tag_of(address));
+        if self.l2().contains(l2_index, l2_tag)
+            || (self.inclusion == Inclusion::Exclusive && self.l1.holds_address(address))
+        {
+            return;
+        }
+        self.stats.l2_prefetches += 1;
+        self.log_event(|| format!("  prefetch {:x} into L2", address));
+        self.make_room_in_l2(l2_index, l2_tag);
+        self.l2_mut().install_prefetch(l2_index, l2_tag, address);
+        self.count_memory_transfer(address, self.l2().block_size);
+    }
+
+    /// Prefetch a block into the tail of a stream buffer from L2, or from main memory if
+    /// there is no L2.
+    ///
+    /// ## Arguments
+    /// * `block_address` - The block address of the block to prefetch.
+    fn prefetch_into_stream_buffer(&mut self, block_address: usize) {
+        let address = block_address << self.l1.block_offset_bits;
+        self.stats.stream_buffer_prefetches += 1;
+        self.log_event(|| format!("  prefetch {:x} into a stream buffer", address));
+        self.fetch_prefetch(address);
+    }
+
+    /// Read a block being prefetched above L2 from L2, installing it in L2 from main memory
+    /// if L2 misses too (unless L2 is exclusive), or straight from main memory if there is
+    /// no L2.
+    ///
+    /// ## Arguments
+    /// * `address` - The address of the block being prefetched.
+    fn fetch_prefetch(&mut self, address: usize) {
+        if self.l2.is_none() {
+            self.count_memory_transfer(address, self.l1.block_size);
+            return;
+        }
+        let (l2_index, l2_tag) = (self.l2().index_of(address), self.l2().tag_of(address));
+        self.stats.l2_reads_from_l1_prefetch += 1;
+        if self.l2_mut().read(l2_index, l2_tag) == MISS {
+            self.stats.l2_read_misses_from_l1_prefetch += 1;
+            if self.inclusion == Inclusion::Exclusive {
+                self.count_memory_transfer(address, self.l1.block_size);
+                return;
+            }
+            self.make_room_in_l2(l2_index, l2_tag);
+            self.l2_mut().install(l2_index, l2_tag, address);
+            self.count_memory_transfer(address, self.l2().block_size);
+        }
+    }
+
+    /// Write back every dirty block in the hierarchy, as happens when a program finishes.
+    /// Dirty L1 and victim cache blocks are written back as if they were evicted, to L2
+    /// before L2 is flushed, so each dirty block reaches memory exactly once. An exclusive L2
+    /// doesn't hold the blocks above it, so those are written straight to memory instead.
+    /// Writebacks to memory go through the write buffer like any other, and the buffer is
+    /// drained at the end.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // a single 2-way set in l1, and a direct-mapped l2 where 0x100 and 0x120 share set 0.
+    /// // the buffer doesn't drain on its own during this short run
+    /// let (l1, l2) = (Cache::new(32, 2, 16)?, Cache::new(32, 1, 16)?);
+    /// let mut simulator = Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Nine);
+    /// simulator.enable_write_buffer(4, 100);
+    /// simulator.access(AccessType::Write, 0x100);
+    /// simulator.access(AccessType::Write, 0x120);
+    /// assert_eq!(simulator.stats.total_memory_traffic, 2);
+    ///
+    /// // both dirty l1 blocks miss in l2 on their way down (2 transfers), and the second
+    /// // pushes the first out of l2 into the write buffer. flushing l2 buffers the second,
+    /// // and draining the buffer counts each writeback once
+    /// simulator.flush();
+    /// let stats = &simulator.stats;
+    /// assert_eq!((stats.l1_write_backs, stats.l2_writes, stats.l2_write_misses), (2, 2, 2));
+    /// assert_eq!((stats.l2_write_backs, stats.write_back_bytes), (2, 32));
+    /// assert_eq!(stats.total_memory_traffic, 2 + 2 + 2);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn flush(&mut self) {
+        let mut flushed = self.l1.flush();
+        flushed.extend(self.flush_victim_cache());
+        for evicted in flushed {
+            if self.inclusion == Inclusion::Exclusive && self.l2.is_some() {
+                self.stats.l1_write_backs += 1;
+                self.write_back_to_memory(evicted.evicted_block_address, evicted.evicted_dirty_sectors * self.l1.sector_size);
+            } else {
+                self.write_back_from_l1(evicted);
+            }
+        }
+        if self.l2.is_some() {
+            for evicted in self.l2_mut().flush() {
+                self.stats.l2_write_backs += 1;
+                self.write_back_to_memory(evicted.evicted_block_address, self.l2().block_size);
+            }
+        }
+        self.drain_write_buffer();
+    }
+
+    /// Clear the dirty bits of every block in the victim cache.
+    ///
+    /// Returns an eviction result for every block that was dirty, like `Cache::flush`.
+    fn flush_victim_cache(&mut self) -> Vec<EvictionResult> {
+        let mut flushed = vec![];
+        for block in self.victim.blocks.iter_mut() {
+            if block.dirty {
+                block.dirty = false;
+                flushed.push(EvictionResult {
+                    evicted_block_address: block.address,
+                    evicted_block_was_dirty: true,
+                    evicted_block_was_prefetched: false,
+                    evicted_dirty_sectors: 1,
+                });
+            }
+        }
+        flushed
+    }
+
+    /// Get L2, on a path that is only taken when there is one.
+    fn l2(&self) -> &Cache {
+        self.l2.as_ref().expect("only reached with an L2")
+    }
+
+    /// Get L2 for modification, on a path that is only taken when there is one.
+    fn l2_mut(&mut self) -> &mut Cache {
+        self.l2.as_mut().expect("only reached with an L2")
+    }
+
+    /// Count one transfer to or from main memory.
+    ///
+    /// ## Arguments
+    /// * `bytes` - The size of the transfer, a block or a single sector.
+    fn count_memory_traffic(&mut self, bytes: usize) {
+        self.stats.total_memory_traffic += 1;
+        self.stats.memory_traffic_bytes += bytes;
+    }
+
+    /// Count one transfer to or from main memory, and to the memory region of its block.
+    ///
+    /// ## Arguments
+    /// * `address` - Any address within the block being transferred.
+    /// * `bytes` - The size of the transfer, a block or a single sector.
+    fn count_memory_transfer(&mut self, address: usize, bytes: usize) {
+        self.count_region_traffic(address, bytes);
+        self.count_memory_traffic(bytes);
+    }
+
+    /// Count bytes transferred to or from the memory region of a block. Does nothing if
+    /// memory isn't split into regions.
+    ///
+    /// ## Arguments
+    /// * `address` - Any address within the block being transferred.
+    /// * `bytes` - The size of the transfer.
+    fn count_region_traffic(&mut self, address: usize, bytes: usize) {
+        if let Some(regions) = &self.memory_regions {
+            self.stats.region_traffic_bytes[regions.region_of(address)] += bytes;
+        }
+    }
+
+    /// Count a demand fetch that reached main memory against the memory region of its block.
+    /// Does nothing if memory isn't split into regions.
+    ///
+    /// ## Arguments
+    /// * `address` - The address being accessed.
+    fn count_memory_read(&mut self, address: usize) {
+        if let Some(regions) = &self.memory_regions {
+            self.stats.region_memory_reads[regions.region_of(address)] += 1;
+        }
+    }
+
+    /// Write a block back to main memory, through the write buffer if there is one. A full
+    /// buffer stalls until its oldest writeback drains. The writeback counts against the
+    /// memory region of the block as soon as it's issued.
+    ///
+    /// ## Arguments
+    /// * `address` - Any address within the block being written back.
+    /// * `bytes` - The size of the writeback, a block or just its dirty sectors.
+    fn write_back_to_memory(&mut self, address: usize, bytes: usize) {
+        self.stats.write_back_bytes += bytes;
+        self.count_region_traffic(address, bytes);
+        let Some(write_buffer) = self.write_buffer.as_mut() else {
+            self.count_memory_traffic(bytes);
+            return;
+        };
+        let drained = if write_buffer.is_full() { write_buffer.pop() } else { None };
+        write_buffer.push(bytes);
+        if let Some(drained_bytes) = drained {
+            self.stats.write_buffer_stalls += 1;
+            self.count_memory_traffic(drained_bytes);
+        }
+    }
+
+    /// Count a completed access to L1.
+    fn count_l1_access(&mut self, access_type: AccessType) {
+        match access_type {
+            AccessType::Read => self.stats.l1_reads += 1,
+            AccessType::Write => self.stats.l1_writes += 1,
+            AccessType::InstructionFetch => self.stats.l1_instruction_fetches += 1,
+        }
+    }
+
+    /// Evict the least recently used block from an L1 set if the set is full.
+    /// The evicted block goes to the victim cache if there is one, otherwise it's
+    /// written back if it was dirty.
+    ///
+    /// ## Arguments
+    /// * `l1_index` - The L1 set that needs a free way.
+    /// * `l1_tag` - The tag of the block that needs the way.
+    fn make_room_in_l1(&mut self, l1_index: usize, l1_tag: usize) {
+        if !self.l1.set_is_full(l1_index, l1_tag) {
+            return;
+        }
+        let l1_evicted_result = self.l1.evict_lru_block(l1_index, l1_tag);
+        let (evicted_address, evicted_dirty) =
+            (l1_evicted_result.evicted_block_address, l1_evicted_result.evicted_block_was_dirty);
+        self.log_event(|| format!("  L1 evict {:x}{}", evicted_address, if evicted_dirty { " (dirty)" } else { "" }));
+        if let Some(replacement_log) = self.replacement_log.as_mut() {
+            replacement_log.record(1, l1_index, self.l1.tag_of(evicted_address), l1_tag);
+        }
+        self.stats.l1_evictions += 1;
+        if !evicted_dirty {
+            self.stats.l1_clean_evictions += 1;
+        }
+        if l1_evicted_result.evicted_block_was_prefetched {
+            self.stats.unused_prefetches += 1;
+        }
+        if !self.victim.is_enabled() {
+            self.write_back_from_l1(l1_evicted_result);
+            return;
+        }
+
+        // push the evicted block into the victim cache, writing back whatever falls out of it
+        let block = Block {
+            address: l1_evicted_result.evicted_block_address,
+            tag: self.l1.block_address_of(l1_evicted_result.evicted_block_address),
+            valid: true,
+            dirty: l1_evicted_result.evicted_block_was_dirty,
+            ..Block::new()
+        };
+        if let Some(victim_evicted_block) = self.victim.insert(block) {
+            let victim_evicted_address = victim_evicted_block.address;
+            self.log_event(|| format!("  victim cache evict {:x}", victim_evicted_address));
+            self.write_back_from_l1(EvictionResult {
+                evicted_block_address: victim_evicted_block.address,
+                evicted_block_was_dirty: victim_evicted_block.dirty,
+                evicted_block_was_prefetched: false,
+                evicted_dirty_sectors: victim_evicted_block.dirty as usize,
+            });
+        }
+    }
+
+    /// Write a block leaving L1 back to L2, or to main memory if there is no L2.
+    /// Clean blocks are simply dropped, except that an exclusive L2 takes every block.
+    ///
+    /// ## Arguments
+    /// * `l1_evicted_result` - The block leaving L1.
+    fn write_back_from_l1(&mut self, l1_evicted_result: EvictionResult) {
+        if self.inclusion == Inclusion::Exclusive && self.l2.is_some() {
+            self.move_to_l2(l1_evicted_result);
+            return;
+        }
+        if !l1_evicted_result.evicted_block_was_dirty {
+            return;
+        }
+
+        let evicted_block_address = l1_evicted_result.evicted_block_address;
+        let to = if self.l2.is_none() { "memory" } else { "L2" };
+        self.log_event(|| format!("  writeback {:x} to {}", evicted_block_address, to));
+
+        // check if we have an l2
+        if self.l2.is_none() {
+            // no l2, write back to main memory, only the dirty sectors if l1 is sectored
+            self.stats.l1_write_backs += 1;
+            self.write_back_to_memory(evicted_block_address, l1_evicted_result.evicted_dirty_sectors * self.l1.sector_size);
+            return;
+        }
+
+        let l2_write_back_index = self.l2().index_of(evicted_block_address);
+        let l2_write_back_tag = self.l2().tag_of(evicted_block_address);
+
+        // try to write back to l2
+        let l2_hit_or_miss = self.l2_mut().write(l2_write_back_index, l2_write_back_tag);
+        self.l2_mut().record_set_access(l2_write_back_index, &l2_hit_or_miss);
+        let l2_shadow_hit = self.l2_shadow.as_mut().is_some_and(|shadow| shadow.observe(evicted_block_address));
+        if l2_hit_or_miss == MISS {
+            self.stats.l2_write_misses += 1;
+            if l2_shadow_hit {
+                self.stats.l2_shadow_conflict_misses += 1;
+            }
+            self.log_event(|| "  L2 write miss".to_string());
+
+            // check if we need to evict a block from l2 before installing
+            self.make_room_in_l2(l2_write_back_index, l2_write_back_tag);
+
+            // install dirty, since l2 now holds the only up-to-date copy
+            self.l2_mut().install_for_write(l2_write_back_index, l2_write_back_tag, evicted_block_address);
+            self.count_memory_transfer(evicted_block_address, self.l2().block_size);
+        }
+
+        self.stats.l1_write_backs += 1;
+        self.stats.l2_writes += 1;
+    }
+
+    /// Move a block leaving L1, clean or dirty, into an exclusive L2. Only dirty blocks count
+    /// as writebacks, and nothing is fetched from memory, since L1 supplies the whole block.
+    ///
+    /// ## Arguments
+    /// * `l1_evicted_result` - The block leaving L1.
+    fn move_to_l2(&mut self, l1_evicted_result: EvictionResult) {
+        let (address, dirty) =
+            (l1_evicted_result.evicted_block_address, l1_evicted_result.evicted_block_was_dirty);
+        self.log_event(|| format!("  move {:x} to L2{}", address, if dirty { " (dirty)" } else { "" }));
+        let (l2_index, l2_tag) = (self.l2().index_of(address), self.l2().tag_of(address));
+        self.make_room_in_l2(l2_index, l2_tag);
+        if dirty {
+            self.l2_mut().install_for_write(l2_index, l2_tag, address);
+            self.stats.l1_write_backs += 1;
+        } else {
+            self.l2_mut().install(l2_index, l2_tag, address);
+        }
+        self.stats.l2_writes += 1;
+        self.stats.l2_victim_fills += 1;
+    }
+
+    /// Evict the least recently used block from an L2 set if the set is full, writing it
+    /// back to main memory if it was dirty. Under an inclusive policy the evicted block is
+    /// also back-invalidated from L1.
+    ///
+    /// ## Arguments
+    /// * `l2_index` - The L2 set that needs a free way.
+    /// * `l2_tag` - The tag of the block that needs the way.
+    fn make_room_in_l2(&mut self, l2_index: usize, l2_tag: usize) {
+        if !self.l2().set_is_full(l2_index, l2_tag) {
+            return;
+        }
+        let l2_evicted_result = self.l2_mut().evict_lru_block(l2_index, l2_tag);
+        let (evicted_address, evicted_dirty) =
+            (l2_evicted_result.evicted_block_address, l2_evicted_result.evicted_block_was_dirty);
+        self.log_event(|| format!("  L2 evict {:x}{}", evicted_address, if evicted_dirty { " (dirty)" } else { "" }));
+        if let Some(replacement_log) = self.replacement_log.as_mut() {
+            let evicted_tag = self.l2.as_ref().expect("only reached with an L2").tag_of(evicted_address);
+            replacement_log.record(2, l2_index, evicted_tag, l2_tag);
+        }
+        self.stats.l2_evictions += 1;
+        if !evicted_dirty {
+            self.stats.l2_clean_evictions += 1;
+        }
+
+        // write evicted block back to main memory if it was dirty
+        if l2_evicted_result.evicted_block_was_dirty {
+            self.stats.l2_write_backs += 1;
+            self.write_back_to_memory(evicted_address, self.l2().block_size);
+        }
+
+        if self.inclusion == Inclusion::Inclusive {
+            self.back_invalidate(l2_evicted_result.evicted_block_address);
+        }
+    }
+
+    /// Invalidate a block evicted from L2 in L1 (or the victim cache or stream buffers) to
+    /// keep L1 a subset of L2. A dirty L1 copy is written straight back to main memory.
+    ///
+    /// ## Arguments
+    /// * `address` - The address of the block evicted from L2.
+    fn back_invalidate(&mut self, address: usize) {
+        let (l1_index, l1_tag) = (self.l1.index_of(address), self.l1.tag_of(address));
+        if let Some(stream_buffers) = self.stream_buffers.as_mut() {
+            stream_buffers.invalidate(self.l1.block_address_of(address));
+        }
+        let mut dirty_bytes = self.l1
+            .invalidate(l1_index, l1_tag)
+            .map(|l1_evicted_result| l1_evicted_result.evicted_dirty_sectors * self.l1.sector_size);
+        if dirty_bytes.is_none() && self.victim.is_enabled() {
+            dirty_bytes = self.victim
+                .take(self.l1.block_address_of(address))
+                .map(|block| block.dirty as usize * self.l1.block_size);
+        }
+
+        if let Some(dirty_bytes) = dirty_bytes {
+            self.log_event(|| format!("  back-invalidate {:x} from L1", address));
+            self.stats.back_invalidations += 1;
+            if dirty_bytes != 0 {
+                self.stats.l1_write_backs += 1;
+                self.write_back_to_memory(address, dirty_bytes);
+            }
+        }
+    }
+}
+
+/// Simulate a trace through the hierarchy described by a configuration, without printing
+/// anything.
+///
+/// ## Arguments
+/// * `config` - The hierarchy and options to simulate.
+/// * `accesses` - The records of the trace.
+///
+/// Returns the statistics of the run.
+///
+/// ## Throws
+/// This function will panic with the reason if the configuration is invalid.
+///
+/// ## Example
+/// ```
+/// use cache_simulator::access::{Access, AccessType};
+/// use cache_simulator::config::Config;
+/// use cache_simulator::simulator::simulate;
+/// use cache_simulator::sweep::Geometry;
+///
+/// let geometry = Geometry { block_size: 16, l1_size: 64, l1_assoc: 2, l2_size: 0, l2_assoc: 0 };
+/// let trace = [0x0, 0x4, 0x40].map(|address| Access { rw: AccessType::Read, address, size: 1, timestamp: None });
+/// let stats = simulate(&Config::new(geometry), trace.into_iter());
+/// assert_eq!((stats.l1_reads, stats.l1_read_misses), (3, 2));
+///
+/// // three 16-byte fills, and the dirty block at 0x100 written back when 0x140 evicts it
+/// let trace = [(AccessType::Write, 0x100), (AccessType::Read, 0x120), (AccessType::Read, 0x140)]
+///     .map(|(rw, address)| Access { rw, address, size: 1, timestamp: None });
+/// let stats = simulate(&Config::new(geometry), trace.into_iter());
+/// assert_eq!((stats.total_memory_traffic, stats.memory_traffic_bytes, stats.write_back_bytes), (4, 64, 16));
+///
+/// // the 8-byte read at 0x10c hits in the block at 0x100 and misses in the one at 0x110
+/// let trace = [(0x100, 4), (0x10c, 8)].map(|(address, size)| Access { rw: AccessType::Read, address, size, timestamp: None });
+/// let stats = simulate(&Config::new(geometry), trace.into_iter());
+/// assert_eq!((stats.l1_reads, stats.l1_read_misses, stats.split_accesses), (3, 2, 1));
+/// ```
+pub fn simulate(config: &Config, accesses: impl Iterator<Item = Access>) -> Statistics {
+    let mut simulator = config.build_simulator().unwrap_or_else(|e| panic!("{}", e));
+    simulator
+        .run(accesses, config.warmup, config.flush_at_end, |_| Ok(()))
+        .expect("nothing is written during the run");
+    simulator.stats
+}
@@ -1,69 +1,991 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use clap::ValueEnum;
+use crate::cache::cache::DIP_PSEL_MAX;
+
+/// Dynamic energy of each kind of event, in arbitrary units. Only the ratios between them
+/// matter for comparing configurations.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EnergyModel {
+    pub l1_read: f32,
+    pub l1_write: f32,
+    pub l2_read: f32,
+    pub l2_write: f32,
+    pub memory_access: f32,
+}
+
+impl Default for EnergyModel {
+    fn default() -> Self {
+        // roughly an order of magnitude between L1 and L2, and a little more out to memory
+        EnergyModel {
+            l1_read: 1.0,
+            l1_write: 1.0,
+            l2_read: 8.0,
+            l2_write: 8.0,
+            memory_access: 100.0,
+        }
+    }
+}
+
+/// How the measurements are laid out by `Statistics::print_stats`.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum StatsStyle {
+    /// The lettered `a.` to `r.` lines of the course assignment, then the rest unlettered.
+    Assignment,
+    /// `key: value` lines without letters, grouped into sections.
+    Plain,
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct Statistics {
+    /// Every lookup simulated after the warmup, one per trace record unless the record spans
+    /// several blocks. It's the sum of L1 reads, writes, and instruction fetches, which count
+    /// demand accesses only (including ones that bypass L1), so reads plus writes fall short
+    /// of it on traces with instruction fetches. Prefetches are counted separately and never
+    /// as accesses.
+    pub total_accesses: usize,
+    /// Malformed trace lines that were skipped instead of stopping the run. They never reach
+    /// the caches, so they aren't part of `total_accesses`.
+    pub skipped_lines: usize,
+    /// Trace records whose bytes span more than one L1 block, each looked up separately.
+    pub split_accesses: usize,
     pub l1_reads: usize,
     pub l1_read_misses: usize,
     pub l1_writes: usize,
     pub l1_write_misses: usize,
     pub l1_write_backs: usize,
+    pub l1_instruction_fetches: usize,
+    pub l1_instruction_fetch_misses: usize,
+    pub l1_evictions: usize,
+    pub l1_clean_evictions: usize,
 
     pub l2_reads: usize,
     pub l2_read_misses: usize,
     pub l2_writes: usize,
     pub l2_write_misses: usize,
     pub l2_write_backs: usize,
+    pub l2_evictions: usize,
+    pub l2_clean_evictions: usize,
 
+    /// Blocks (or sectors) transferred to or from main memory.
     pub total_memory_traffic: usize,
+    /// Bytes transferred to or from main memory, which unlike the transfer count can be
+    /// compared across block sizes.
+    pub memory_traffic_bytes: usize,
+    /// Bytes of dirty data written back to main memory, part of `memory_traffic_bytes`.
+    pub write_back_bytes: usize,
 
     pub l1_prefetches: usize,
     pub l2_prefetches: usize,
     pub l2_reads_from_l1_prefetch: usize,
     pub l2_read_misses_from_l1_prefetch: usize,
+    pub prefetching: bool,
+    /// Prefetched L1 blocks that were demanded before leaving L1.
+    pub useful_prefetches: usize,
+    /// Prefetched L1 blocks that were evicted before ever being demanded.
+    pub unused_prefetches: usize,
+    pub l2_prefetching: bool,
+    /// Blocks prefetched into L2 that a demand read from L1 hit before they left L2.
+    pub l2_useful_prefetches: usize,
+
+    pub stream_buffers_enabled: bool,
+    /// L1 misses found at the head of a stream buffer.
+    pub stream_buffer_hits: usize,
+    /// Blocks fetched into the stream buffers.
+    pub stream_buffer_prefetches: usize,
+
+    pub streaming_detection: bool,
+    /// L1 misses installed at the LRU position because L1 was streaming.
+    pub lru_insertions: usize,
+    /// L1 read misses that skipped L1 because it was streaming.
+    pub bypasses: usize,
+    /// L1 misses that would have hit had every block been inserted at MRU, and hits that
+    /// would have missed, as estimated by an L1 without streaming detection.
+    pub streaming_misses_added: usize,
+    pub streaming_misses_avoided: usize,
+
+    pub victim_cache_enabled: bool,
+    pub victim_hits: usize,
+
+    pub inclusive: bool,
+    pub back_invalidations: usize,
+    pub exclusive: bool,
+    pub coherence: bool,
+    /// Misses on blocks the core lost to another core's write.
+    pub coherence_misses: usize,
+    /// Copies removed from other cores' L1s by a write.
+    pub coherence_invalidations: usize,
+    /// Modified copies written back and kept Shared because another core read them.
+    pub coherence_downgrades: usize,
+    pub l2_victim_fills: usize,
+
+    pub classify_misses: bool,
+    pub compulsory_misses: usize,
+    pub capacity_misses: usize,
+    pub conflict_misses: usize,
+
+    pub conflict_shadow: bool,
+    /// L1 demand misses that a fully-associative L1 of the same size would have hit.
+    pub l1_shadow_conflict_misses: usize,
+    /// L2 read and write misses that a fully-associative L2 of the same size would have hit.
+    pub l2_shadow_conflict_misses: usize,
+
+    pub periodic_flush: bool,
+    /// Flushes of every dirty block, one after each flush interval. Their writebacks are
+    /// counted with the others.
+    pub periodic_flushes: usize,
+
+    pub write_buffer_enabled: bool,
+    /// Writebacks that found the write buffer full and had to wait for an entry to drain.
+    pub write_buffer_stalls: usize,
+
+    pub banks_enabled: bool,
+    /// L1 accesses to the same bank as one of the accesses shortly before them.
+    pub bank_conflicts: usize,
+
+    pub mshrs_enabled: bool,
+    /// L1 accesses to a block whose miss was still outstanding, merged into its MSHR.
+    pub mshr_secondary_misses: usize,
+    /// L1 misses that found every MSHR busy and had to wait for the oldest miss.
+    pub mshr_stalls: usize,
+
+    pub overlap_enabled: bool,
+    /// L1 misses that were the first of their overlap window, the only ones that pay the
+    /// latency of the next level.
+    pub overlapped_l1_misses: usize,
+    /// L2 read misses that were the first of their overlap window, the only ones that pay
+    /// the memory latency.
+    pub overlapped_l2_misses: usize,
+
+    /// The latency multiplier of every memory region, empty if memory isn't split into
+    /// regions.
+    pub region_latency_multipliers: Vec<usize>,
+    /// Bytes transferred to or from each memory region, by the address of the block.
+    pub region_traffic_bytes: Vec<usize>,
+    /// Demand fetches that reached main memory in each region, which pay its latency.
+    pub region_memory_reads: Vec<usize>,
+    /// The demand L2 misses of each core, empty unless L2 is partitioned between the cores.
+    pub core_l2_read_misses: Vec<usize>,
+
+    pub tlb_enabled: bool,
+    pub tlb_hits: usize,
+    pub tlb_misses: usize,
+    pub tlb_miss_penalty: usize,
+
+    pub sectored: bool,
+    /// L1 misses whose block was resident but whose sector wasn't.
+    pub sector_misses: usize,
+
+    pub write_validate: bool,
+    /// L1 write misses that allocated their block or sector without fetching it.
+    pub write_validated_misses: usize,
+
+    pub dip_enabled: bool,
+    pub l1_psel: usize,
+    pub l2_psel: usize,
+
+    pub energy_enabled: bool,
+    pub energy: EnergyModel,
+
+    pub l2_enabled: bool,
+    pub l1_latency: usize,
+    pub l2_latency: usize,
+    pub memory_latency: usize,
 }
 
 impl Statistics {
     pub fn new() -> Self {
         Self {
+            total_accesses: 0,
+            skipped_lines: 0,
+            split_accesses: 0,
             l1_reads: 0,
             l1_read_misses: 0,
             l1_writes: 0,
             l1_write_misses: 0,
             l1_write_backs: 0,
+            l1_instruction_fetches: 0,
+            l1_instruction_fetch_misses: 0,
+            l1_evictions: 0,
+            l1_clean_evictions: 0,
             l2_reads: 0,
             l2_read_misses: 0,
             l2_writes: 0,
             l2_write_misses: 0,
             l2_write_backs: 0,
+            l2_evictions: 0,
+            l2_clean_evictions: 0,
             total_memory_traffic: 0,
+            memory_traffic_bytes: 0,
+            write_back_bytes: 0,
             l1_prefetches: 0,
             l2_prefetches: 0,
             l2_reads_from_l1_prefetch: 0,
-            l2_read_misses_from_l1_prefetch: 0
-        }
-    }
-
-    pub fn print_stats(self) {
-        let l1_miss_rate: f32 = ((self.l1_read_misses + self.l1_write_misses) as f32) /
-            ((self.l1_reads + self.l1_writes) as f32);
-        let mut l2_miss_rate: f32 = (self.l2_read_misses as f32) / (self.l2_reads as f32);
-        if l2_miss_rate.is_nan() {
-            l2_miss_rate = 0.0;
-        }
-        println!("===== Measurements =====");
-        println!("a. L1 reads:                   {}", self.l1_reads);
-        println!("b. L1 read misses:             {}", self.l1_read_misses);
-        println!("c. L1 writes:                  {}", self.l1_writes);
-        println!("d. L1 write misses:            {}", self.l1_write_misses);
-        println!("e. L1 miss rate:               {:.4}", l1_miss_rate);
-        println!("f. L1 writebacks:              {}", self.l1_write_backs);
-        println!("g. L1 prefetches:              {}", self.l1_prefetches);
-        println!("h. L2 reads (demand):          {}", self.l2_reads);
-        println!("i. L2 read misses (demand):    {}", self.l2_read_misses);
-        println!("j. L2 reads (prefetch):        {}", self.l2_reads_from_l1_prefetch);
-        println!("k. L2 read misses (prefetch):  {}", self.l2_read_misses_from_l1_prefetch);
-        println!("l. L2 writes:                  {}", self.l2_writes);
-        println!("m. L2 write misses:            {}", self.l2_write_misses);
-        println!("n. L2 miss rate:               {:.4}", l2_miss_rate);
-        println!("o. L2 writebacks:              {}", self.l2_write_backs);
-        println!("p. L2 prefetches:              {}", self.l2_prefetches);
-        println!("q. memory traffic:             {}", self.total_memory_traffic);
+            l2_read_misses_from_l1_prefetch: 0,
+            prefetching: false,
+            useful_prefetches: 0,
+            l2_prefetching: false,
+            l2_useful_prefetches: 0,
+            unused_prefetches: 0,
+            stream_buffers_enabled: false,
+            stream_buffer_hits: 0,
+            stream_buffer_prefetches: 0,
+            streaming_detection: false,
+            lru_insertions: 0,
+            bypasses: 0,
+            streaming_misses_added: 0,
+            streaming_misses_avoided: 0,
+            victim_cache_enabled: false,
+            victim_hits: 0,
+            inclusive: false,
+            back_invalidations: 0,
+            exclusive: false,
+            coherence: false,
+            coherence_misses: 0,
+            coherence_invalidations: 0,
+            coherence_downgrades: 0,
+            l2_victim_fills: 0,
+            classify_misses: false,
+            compulsory_misses: 0,
+            capacity_misses: 0,
+            conflict_misses: 0,
+            conflict_shadow: false,
+            l1_shadow_conflict_misses: 0,
+            l2_shadow_conflict_misses: 0,
+            periodic_flush: false,
+            periodic_flushes: 0,
+            write_buffer_enabled: false,
+            write_buffer_stalls: 0,
+            banks_enabled: false,
+            bank_conflicts: 0,
+            mshrs_enabled: false,
+            mshr_secondary_misses: 0,
+            mshr_stalls: 0,
+            overlap_enabled: false,
+            overlapped_l1_misses: 0,
+            overlapped_l2_misses: 0,
+            region_latency_multipliers: vec![],
+            region_traffic_bytes: vec![],
+            region_memory_reads: vec![],
+            core_l2_read_misses: vec![],
+            tlb_enabled: false,
+            tlb_hits: 0,
+            tlb_misses: 0,
+            tlb_miss_penalty: 0,
+            sectored: false,
+            sector_misses: 0,
+            write_validate: false,
+            write_validated_misses: 0,
+            dip_enabled: false,
+            l1_psel: DIP_PSEL_MAX / 2,
+            l2_psel: DIP_PSEL_MAX / 2,
+            energy_enabled: false,
+            energy: EnergyModel::default(),
+            l2_enabled: false,
+            l1_latency: 1,
+            l2_latency: 10,
+            memory_latency: 100,
+        }
+    }
+
+    /// Zero every counter while keeping the configuration (enabled features and latencies).
+    /// The DIP policy selectors mirror cache state rather than counting events, so they are
+    /// kept too.
+    pub fn reset(&mut self) {
+        *self = Self {
+            victim_cache_enabled: self.victim_cache_enabled,
+            inclusive: self.inclusive,
+            exclusive: self.exclusive,
+            coherence: self.coherence,
+            classify_misses: self.classify_misses,
+            conflict_shadow: self.conflict_shadow,
+            prefetching: self.prefetching,
+            l2_prefetching: self.l2_prefetching,
+            stream_buffers_enabled: self.stream_buffers_enabled,
+            streaming_detection: self.streaming_detection,
+            periodic_flush: self.periodic_flush,
+            write_buffer_enabled: self.write_buffer_enabled,
+            banks_enabled: self.banks_enabled,
+            mshrs_enabled: self.mshrs_enabled,
+            overlap_enabled: self.overlap_enabled,
+            region_traffic_bytes: vec![0; self.region_latency_multipliers.len()],
+            region_memory_reads: vec![0; self.region_latency_multipliers.len()],
+            region_latency_multipliers: self.region_latency_multipliers.clone(),
+            core_l2_read_misses: vec![0; self.core_l2_read_misses.len()],
+            tlb_enabled: self.tlb_enabled,
+            tlb_miss_penalty: self.tlb_miss_penalty,
+            sectored: self.sectored,
+            write_validate: self.write_validate,
+            dip_enabled: self.dip_enabled,
+            l1_psel: self.l1_psel,
+            l2_psel: self.l2_psel,
+            energy_enabled: self.energy_enabled,
+            energy: self.energy,
+            l2_enabled: self.l2_enabled,
+            l1_latency: self.l1_latency,
+            l2_latency: self.l2_latency,
+            memory_latency: self.memory_latency,
+            ..Self::new()
+        };
+    }
+
+    /// Add the counters of another run to these, to combine the measurements of parts of a
+    /// trace run separately, e.g. one file at a time. Every counter is summed, and rates are
+    /// derived from the summed counters when printed. The configuration (enabled features,
+    /// latencies, and energies) is kept from these statistics, and the DIP policy selectors
+    /// are taken from `other`, as the state at the end of the later run.
+    ///
+    /// The sum only matches a single run over the whole trace if each part started with the
+    /// caches the previous part left behind. Parts run on cold caches miss more.
+    ///
+    /// ## Arguments
+    /// * `other` - The statistics of the run that followed these.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::{Access, AccessType};
+    /// use cache_simulator::config::Config;
+    /// use cache_simulator::sweep::Geometry;
+    ///
+    /// let geometry = Geometry { block_size: 16, l1_size: 256, l1_assoc: 2, l2_size: 1024, l2_assoc: 4 };
+    /// let config = Config { l1_prefetch_degree: 1, victim_size: 2, ..Config::new(geometry) };
+    /// let trace: Vec<Access> = (0..400)
+    ///     .map(|i| Access { rw: if i % 3 == 0 { AccessType::Write } else { AccessType::Read }, address: (i * 0x58) % 0xa00, size: 1, timestamp: None })
+    ///     .collect();
+    ///
+    /// let mut whole = config.build_simulator()?;
+    /// whole.run(trace.iter().copied(), 0, true, |_| Ok(())).unwrap();
+    ///
+    /// // run the halves on the same caches, measuring each on its own
+    /// let mut halves = config.build_simulator()?;
+    /// halves.run(trace[..150].iter().copied(), 0, false, |_| Ok(())).unwrap();
+    /// let mut merged = halves.stats.clone();
+    /// halves.stats.reset();
+    /// halves.run(trace[150..].iter().copied(), 0, true, |_| Ok(())).unwrap();
+    /// merged.merge(&halves.stats);
+    /// assert_eq!(merged, whole.stats);
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn merge(&mut self, other: &Statistics) {
+        // destructured so that a new field can't be left out of the sum
+        let Statistics {
+            total_accesses,
+            skipped_lines,
+            split_accesses,
+            l1_reads,
+            l1_read_misses,
+            l1_writes,
+            l1_write_misses,
+            l1_write_backs,
+            l1_instruction_fetches,
+            l1_instruction_fetch_misses,
+            l1_evictions,
+            l1_clean_evictions,
+            l2_reads,
+            l2_read_misses,
+            l2_writes,
+            l2_write_misses,
+            l2_write_backs,
+            l2_evictions,
+            l2_clean_evictions,
+            total_memory_traffic,
+            memory_traffic_bytes,
+            write_back_bytes,
+            l1_prefetches,
+            l2_prefetches,
+            l2_reads_from_l1_prefetch,
+            l2_read_misses_from_l1_prefetch,
+            useful_prefetches,
+            unused_prefetches,
+            l2_useful_prefetches,
+            stream_buffer_hits,
+            stream_buffer_prefetches,
+            lru_insertions,
+            bypasses,
+            streaming_misses_added,
+            streaming_misses_avoided,
+            victim_hits,
+            back_invalidations,
+            coherence_misses,
+            coherence_invalidations,
+            coherence_downgrades,
+            l2_victim_fills,
+            compulsory_misses,
+            capacity_misses,
+            conflict_misses,
+            l1_shadow_conflict_misses,
+            l2_shadow_conflict_misses,
+            periodic_flushes,
+            write_buffer_stalls,
+            bank_conflicts,
+            mshr_secondary_misses,
+            mshr_stalls,
+            overlapped_l1_misses,
+            overlapped_l2_misses,
+            region_traffic_bytes,
+            region_memory_reads,
+            core_l2_read_misses,
+            tlb_hits,
+            tlb_misses,
+            sector_misses,
+            write_validated_misses,
+            prefetching: _,
+            l2_prefetching: _,
+            stream_buffers_enabled: _,
+            streaming_detection: _,
+            victim_cache_enabled: _,
+            inclusive: _,
+            exclusive: _,
+            coherence: _,
+            classify_misses: _,
+            conflict_shadow: _,
+            periodic_flush: _,
+            write_buffer_enabled: _,
+            banks_enabled: _,
+            mshrs_enabled: _,
+            overlap_enabled: _,
+            region_latency_multipliers: _,
+            tlb_enabled: _,
+            tlb_miss_penalty: _,
+            sectored: _,
+            write_validate: _,
+            dip_enabled: _,
+            l1_psel,
+            l2_psel,
+            energy_enabled: _,
+            energy: _,
+            l2_enabled: _,
+            l1_latency: _,
+            l2_latency: _,
+            memory_latency: _,
+        } = other;
+        self.total_accesses += total_accesses;
+        self.skipped_lines += skipped_lines;
+        self.split_accesses += split_accesses;
+        self.l1_reads += l1_reads;
+        self.l1_read_misses += l1_read_misses;
+        self.l1_writes += l1_writes;
+        self.l1_write_misses += l1_write_misses;
+        self.l1_write_backs += l1_write_backs;
+        self.l1_instruction_fetches += l1_instruction_fetches;
+        self.l1_instruction_fetch_misses += l1_instruction_fetch_misses;
+        self.l1_evictions += l1_evictions;
+        self.l1_clean_evictions += l1_clean_evictions;
+        self.l2_reads += l2_reads;
+        self.l2_read_misses += l2_read_misses;
+        self.l2_writes += l2_writes;
+        self.l2_write_misses += l2_write_misses;
+        self.l2_write_backs += l2_write_backs;
+        self.l2_evictions += l2_evictions;
+        self.l2_clean_evictions += l2_clean_evictions;
+        self.total_memory_traffic += total_memory_traffic;
+        self.memory_traffic_bytes += memory_traffic_bytes;
+        self.write_back_bytes += write_back_bytes;
+        self.l1_prefetches += l1_prefetches;
+        self.l2_prefetches += l2_prefetches;
+        self.l2_reads_from_l1_prefetch += l2_reads_from_l1_prefetch;
+        self.l2_read_misses_from_l1_prefetch += l2_read_misses_from_l1_prefetch;
+        self.useful_prefetches += useful_prefetches;
+        self.unused_prefetches += unused_prefetches;
+        self.l2_useful_prefetches += l2_useful_prefetches;
+        self.stream_buffer_hits += stream_buffer_hits;
+        self.stream_buffer_prefetches += stream_buffer_prefetches;
+        self.lru_insertions += lru_insertions;
+        self.bypasses += bypasses;
+        self.streaming_misses_added += streaming_misses_added;
+        self.streaming_misses_avoided += streaming_misses_avoided;
+        self.victim_hits += victim_hits;
+        self.back_invalidations += back_invalidations;
+        self.coherence_misses += coherence_misses;
+        self.coherence_invalidations += coherence_invalidations;
+        self.coherence_downgrades += coherence_downgrades;
+        self.l2_victim_fills += l2_victim_fills;
+        self.compulsory_misses += compulsory_misses;
+        self.capacity_misses += capacity_misses;
+        self.conflict_misses += conflict_misses;
+        self.l1_shadow_conflict_misses += l1_shadow_conflict_misses;
+        self.l2_shadow_conflict_misses += l2_shadow_conflict_misses;
+        self.periodic_flushes += periodic_flushes;
+        self.write_buffer_stalls += write_buffer_stalls;
+        self.bank_conflicts += bank_conflicts;
+        self.mshr_secondary_misses += mshr_secondary_misses;
+        self.mshr_stalls += mshr_stalls;
+        self.overlapped_l1_misses += overlapped_l1_misses;
+        self.overlapped_l2_misses += overlapped_l2_misses;
+        for (total, bytes) in self.region_traffic_bytes.iter_mut().zip(region_traffic_bytes) {
+            *total += bytes;
+        }
+        for (total, reads) in self.region_memory_reads.iter_mut().zip(region_memory_reads) {
+            *total += reads;
+        }
+        for (total, misses) in self.core_l2_read_misses.iter_mut().zip(core_l2_read_misses) {
+            *total += misses;
+        }
+        self.tlb_hits += tlb_hits;
+        self.tlb_misses += tlb_misses;
+        self.sector_misses += sector_misses;
+        self.write_validated_misses += write_validated_misses;
+        self.l1_psel = *l1_psel;
+        self.l2_psel = *l2_psel;
+    }
+
+    /// Get the number of demand accesses to L1: reads, writes, and instruction fetches.
+    pub fn l1_accesses(&self) -> usize {
+        self.l1_reads + self.l1_writes + self.l1_instruction_fetches
+    }
+
+    /// Get the number of demand misses in L1: reads, writes, and instruction fetches.
+    pub fn l1_misses(&self) -> usize {
+        self.l1_read_misses + self.l1_write_misses + self.l1_instruction_fetch_misses
+    }
+
+    /// Compute the fraction of L1 prefetches that were demanded before being evicted.
+    ///
+    /// Returns 0 if nothing was prefetched.
+    pub fn prefetch_accuracy(&self) -> f32 {
+        ratio(self.useful_prefetches, self.l1_prefetches)
+    }
+
+    /// Compute the fraction of would-be L1 misses that prefetching turned into hits.
+    /// Without prefetching every useful prefetch would have been a demand miss.
+    ///
+    /// Returns 0 if there would have been no misses.
+    pub fn prefetch_coverage(&self) -> f32 {
+        ratio(self.useful_prefetches, self.l1_misses() + self.useful_prefetches)
+    }
+
+    /// Compute the fraction of L1 misses that were conflict misses, according to the
+    /// fully-associative shadow of L1.
+    ///
+    /// Returns 0 if L1 never missed.
+    pub fn l1_conflict_fraction(&self) -> f32 {
+        ratio(self.l1_shadow_conflict_misses, self.l1_misses())
+    }
+
+    /// Compute the fraction of L2 read and write misses that were conflict misses, according
+    /// to the fully-associative shadow of L2.
+    ///
+    /// Returns 0 if L2 never missed.
+    pub fn l2_conflict_fraction(&self) -> f32 {
+        ratio(self.l2_shadow_conflict_misses, self.l2_read_misses + self.l2_write_misses)
+    }
+
+    /// Compute the average memory access time in cycles.
+    /// Every access pays the L1 latency, L1 misses pay the L2 latency, and L2 misses pay
+    /// the memory latency. Without an L2, L1 misses pay the memory latency instead.
+    /// TLB misses add the TLB miss penalty.
+    ///
+    /// Returns 0 if there were no accesses.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::statistics::Statistics;
+    ///
+    /// // 10 accesses with 4 L1 misses, 2 of which miss in L2 as well
+    /// let mut stats = Statistics {
+    ///     l1_reads: 8,
+    ///     l1_read_misses: 3,
+    ///     l1_writes: 2,
+    ///     l1_write_misses: 1,
+    ///     l2_read_misses: 2,
+    ///     l2_enabled: true,
+    ///     l1_latency: 1,
+    ///     l2_latency: 10,
+    ///     memory_latency: 100,
+    ///     ..Statistics::new()
+    /// };
+    /// // (10 * 1 + 4 * 10 + 2 * 100) / 10
+    /// assert_eq!(stats.average_access_time(), 25.0);
+    ///
+    /// // without an L2 every L1 miss goes to memory: (10 * 1 + 4 * 100) / 10
+    /// stats.l2_enabled = false;
+    /// assert_eq!(stats.average_access_time(), 41.0);
+    ///
+    /// // and 2 TLB misses add 2 * 30 more cycles
+    /// (stats.tlb_misses, stats.tlb_miss_penalty) = (2, 30);
+    /// assert_eq!(stats.average_access_time(), 47.0);
+    /// assert_eq!(Statistics::new().average_access_time(), 0.0);
+    /// ```
+    pub fn average_access_time(&self) -> f32 {
+        let accesses = self.l1_accesses();
+        let cycles = accesses * self.l1_latency + self.stall_cycles() + self.tlb_misses * self.tlb_miss_penalty;
+        ratio(cycles, accesses)
+    }
+
+    /// Compute the cycles spent waiting on misses, with every miss serviced one after
+    /// another: L1 misses pay the L2 latency and L2 misses pay the memory latency, or L1
+    /// misses pay the memory latency without an L2. Fetches from a slower memory region pay
+    /// its multiple of the memory latency.
+    pub fn stall_cycles(&self) -> usize {
+        self.miss_cycles(self.l1_misses(), self.l2_read_misses) + self.far_memory_cycles()
+    }
+
+    /// Compute the cycles demand fetches from slower memory regions wait on top of the plain
+    /// memory latency.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::regions::{MemoryRegion, MemoryRegions};
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // memory from 0x1000 up is 3 times as slow
+    /// let l1 = Cache::new(64, 2, 16)?;
+    /// let mut simulator = Simulator::new(l1, None, VictimCache::new(0), Inclusion::Nine);
+    /// let far = MemoryRegion { start: 0x1000, latency_multiplier: 3 };
+    /// simulator.enable_memory_regions(MemoryRegions::new(vec![far]).unwrap());
+    /// for address in [0xf00, 0xf10, 0x1000, 0x1010, 0x1020, 0xf00] {
+    ///     simulator.access(AccessType::Write, address);
+    /// }
+    /// simulator.flush();
+    ///
+    /// // 0xf00 is evicted dirty by 0x1020, then 0x1000 by 0xf00 coming back, and the flush
+    /// // writes back two blocks from each region
+    /// let stats = &simulator.stats;
+    /// assert_eq!(stats.region_memory_reads, [3, 3]);
+    /// assert_eq!(stats.region_traffic_bytes, [16 * 3 + 16 * 3, 16 * 3 + 16 * 3]);
+    /// assert_eq!(stats.region_traffic_bytes.iter().sum::<usize>(), stats.memory_traffic_bytes);
+    /// assert_eq!(stats.far_memory_cycles(), 3 * 2 * 100);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn far_memory_cycles(&self) -> usize {
+        self.region_memory_reads
+            .iter()
+            .zip(&self.region_latency_multipliers)
+            .map(|(reads, multiplier)| reads * (multiplier - 1) * self.memory_latency)
+            .sum()
+    }
+
+    /// Compute the cycles spent waiting on misses like `stall_cycles`, but with the misses
+    /// of each overlap window serviced together, so that only the first miss of a window at
+    /// each level pays its latency.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::access::AccessType;
+    /// use cache_simulator::cache::cache::Cache;
+    /// use cache_simulator::cache::victim::VictimCache;
+    /// use cache_simulator::simulator::{Inclusion, Simulator};
+    ///
+    /// // windows of 2: (miss, miss), (hit, miss), (miss, miss)
+    /// let l1 = Cache::new(64, 2, 16)?;
+    /// let mut simulator = Simulator::new(l1, None, VictimCache::new(0), Inclusion::Nine);
+    /// simulator.enable_overlap_window(2);
+    /// for address in [0x100, 0x110, 0x100, 0x120, 0x130, 0x140] {
+    ///     simulator.access(AccessType::Read, address);
+    /// }
+    /// // five misses at 100 cycles each, but only one per window is charged
+    /// assert_eq!(simulator.stats.stall_cycles(), 500);
+    /// assert_eq!(simulator.stats.overlapped_stall_cycles(), 300);
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn overlapped_stall_cycles(&self) -> usize {
+        self.miss_cycles(self.overlapped_l1_misses, self.overlapped_l2_misses)
+    }
+
+    /// Compute the cycles a number of misses at each level wait for the level below.
+    ///
+    /// ## Arguments
+    /// * `l1_misses` - The number of L1 misses.
+    /// * `l2_misses` - The number of L2 read misses.
+    fn miss_cycles(&self, l1_misses: usize, l2_misses: usize) -> usize {
+        if self.l2_enabled {
+            l1_misses * self.l2_latency + l2_misses * self.memory_latency
+        } else {
+            l1_misses * self.memory_latency
+        }
+    }
+
+    /// Estimate the dynamic energy of the run by weighting each event count by its energy.
+    /// L1 reads include instruction fetches, L2 reads include prefetch reads, and every
+    /// block of memory traffic counts as one memory access.
+    pub fn dynamic_energy(&self) -> f32 {
+        let energy = &self.energy;
+        (self.l1_reads + self.l1_instruction_fetches) as f32 * energy.l1_read
+            + self.l1_writes as f32 * energy.l1_write
+            + (self.l2_reads + self.l2_reads_from_l1_prefetch) as f32 * energy.l2_read
+            + self.l2_writes as f32 * energy.l2_write
+            + self.total_memory_traffic as f32 * energy.memory_access
+    }
+
+    /// Print out the measurements.
+    ///
+    /// ## Arguments
+    /// * `style` - How to lay the measurements out.
+    /// * `out` - Where to write the measurements, e.g. stdout.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::statistics::{Statistics, StatsStyle};
+    ///
+    /// let mut stats = Statistics::new();
+    /// stats.l1_reads = 3;
+    /// stats.l1_read_misses = 1;
+    /// let mut assignment = vec![];
+    /// stats.print_stats(StatsStyle::Assignment, &mut assignment).unwrap();
+    /// let assignment = String::from_utf8(assignment).unwrap();
+    /// assert!(assignment.contains("\na. L1 reads:                   3\n"));
+    /// assert!(assignment.contains("\ne. L1 miss rate:               0.3333\n"));
+    /// assert!(assignment.contains("\nr. total accesses:             0\n"));
+    ///
+    /// let mut plain = vec![];
+    /// stats.print_stats(StatsStyle::Plain, &mut plain).unwrap();
+    /// let plain = String::from_utf8(plain).unwrap();
+    /// assert!(plain.contains("\n[L1]\nL1 reads: 3\nL1 read misses: 1\n"));
+    /// assert!(plain.contains("\nL1 miss rate: 0.3333\n"));
+    /// assert!(!plain.contains("a. "));
+    /// ```
+    pub fn print_stats<W: Write>(&self, style: StatsStyle, out: &mut W) -> io::Result<()> {
+        writeln!(out, "===== Measurements =====")?;
+        let mut lines = self.stat_lines();
+        match style {
+            StatsStyle::Assignment => {
+                for line in lines {
+                    let label = match line.letter {
+                        Some(letter) => format!("{}. {}:", letter, line.label),
+                        None => format!("{}:", line.label),
+                    };
+                    writeln!(out, "{:<30} {}", label, line.value)?;
+                }
+            }
+            StatsStyle::Plain => {
+                // the sort is stable, so each section keeps the assignment order
+                lines.sort_by_key(|line| line.section);
+                let mut section = None;
+                for line in lines {
+                    if section != Some(line.section) {
+                        if section.is_some() {
+                            writeln!(out)?;
+                        }
+                        writeln!(out, "[{}]", line.section.name())?;
+                        section = Some(line.section);
+                    }
+                    writeln!(out, "{}: {}", line.label, line.value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get every measurement to print, in the order of the assignment style.
+    fn stat_lines(&self) -> Vec<StatLine> {
+        use Section::{Accesses, Cost, Memory, L1, L2};
+        // an empty or l1-only trace has nothing to divide by, which reads as a 0 rate
+        let l1_miss_rate = ratio(self.l1_misses(), self.l1_accesses());
+        let l2_miss_rate = ratio(self.l2_read_misses, self.l2_reads);
+        let mut lines = vec![
+            StatLine::lettered(L1, 'a', "L1 reads", self.l1_reads),
+            StatLine::lettered(L1, 'b', "L1 read misses", self.l1_read_misses),
+            StatLine::lettered(L1, 'c', "L1 writes", self.l1_writes),
+            StatLine::lettered(L1, 'd', "L1 write misses", self.l1_write_misses),
+            StatLine::lettered(L1, 'e', "L1 miss rate", format!("{:.4}", l1_miss_rate)),
+            StatLine::lettered(L1, 'f', "L1 writebacks", self.l1_write_backs),
+            StatLine::lettered(L1, 'g', "L1 prefetches", self.l1_prefetches),
+            StatLine::lettered(L2, 'h', "L2 reads (demand)", self.l2_reads),
+            StatLine::lettered(L2, 'i', "L2 read misses (demand)", self.l2_read_misses),
+            StatLine::lettered(L2, 'j', "L2 reads (prefetch)", self.l2_reads_from_l1_prefetch),
+            StatLine::lettered(L2, 'k', "L2 read misses (prefetch)", self.l2_read_misses_from_l1_prefetch),
+            StatLine::lettered(L2, 'l', "L2 writes", self.l2_writes),
+            StatLine::lettered(L2, 'm', "L2 write misses", self.l2_write_misses),
+            StatLine::lettered(L2, 'n', "L2 miss rate", format!("{:.4}", l2_miss_rate)),
+            StatLine::lettered(L2, 'o', "L2 writebacks", self.l2_write_backs),
+            StatLine::lettered(L2, 'p', "L2 prefetches", self.l2_prefetches),
+            StatLine::lettered(Memory, 'q', "memory traffic", self.total_memory_traffic),
+            StatLine::lettered(Accesses, 'r', "total accesses", self.total_accesses),
+            StatLine::new(Memory, "memory traffic (bytes)", self.memory_traffic_bytes),
+            StatLine::new(Memory, "writeback traffic (bytes)", self.write_back_bytes),
+        ];
+        if self.skipped_lines != 0 {
+            lines.push(StatLine::new(Accesses, "skipped malformed lines", self.skipped_lines));
+        }
+        if self.split_accesses != 0 {
+            lines.push(StatLine::new(Accesses, "block-spanning accesses", self.split_accesses));
+        }
+        if self.l1_instruction_fetches != 0 {
+            lines.push(StatLine::new(L1, "L1 instruction fetches", self.l1_instruction_fetches));
+            lines.push(StatLine::new(L1, "L1 instruction fetch misses", self.l1_instruction_fetch_misses));
+        }
+        lines.push(StatLine::new(Cost, "average access time", format!("{:.4}", self.average_access_time())));
+        lines.push(StatLine::new(L1, "L1 evictions", self.l1_evictions));
+        lines.push(StatLine::new(L1, "L1 clean evictions", self.l1_clean_evictions));
+        if self.l2_enabled {
+            lines.push(StatLine::new(L2, "L2 evictions", self.l2_evictions));
+            lines.push(StatLine::new(L2, "L2 clean evictions", self.l2_clean_evictions));
+        }
+        if self.prefetching {
+            lines.push(StatLine::new(L1, "useful prefetches", self.useful_prefetches));
+            lines.push(StatLine::new(L1, "unused prefetch evictions", self.unused_prefetches));
+            lines.push(StatLine::new(L1, "prefetch accuracy", format!("{:.4}", self.prefetch_accuracy())));
+            lines.push(StatLine::new(L1, "prefetch coverage", format!("{:.4}", self.prefetch_coverage())));
+        }
+        if self.l2_prefetching {
+            lines.push(StatLine::new(L2, "useful L2 prefetches", self.l2_useful_prefetches));
+        }
+        if self.stream_buffers_enabled {
+            lines.push(StatLine::new(L1, "stream buffer hits", self.stream_buffer_hits));
+            lines.push(StatLine::new(L1, "stream buffer prefetches", self.stream_buffer_prefetches));
+        }
+        if self.streaming_detection {
+            lines.push(StatLine::new(L1, "L1 streaming LRU insertions", self.lru_insertions));
+            lines.push(StatLine::new(L1, "L1 streaming bypasses", self.bypasses));
+            lines.push(StatLine::new(L1, "L1 misses avoided vs MRU", self.streaming_misses_avoided));
+            lines.push(StatLine::new(L1, "L1 misses added vs MRU", self.streaming_misses_added));
+        }
+        if self.victim_cache_enabled {
+            lines.push(StatLine::new(L1, "victim cache hits", self.victim_hits));
+        }
+        if self.classify_misses {
+            lines.push(StatLine::new(L1, "L1 compulsory misses", self.compulsory_misses));
+            lines.push(StatLine::new(L1, "L1 capacity misses", self.capacity_misses));
+            lines.push(StatLine::new(L1, "L1 conflict misses", self.conflict_misses));
+        }
+        if self.conflict_shadow {
+            lines.push(StatLine::new(L1, "L1 shadow conflict misses", self.l1_shadow_conflict_misses));
+            lines.push(StatLine::new(L1, "L1 conflict miss fraction", format!("{:.4}", self.l1_conflict_fraction())));
+            if self.l2_enabled {
+                lines.push(StatLine::new(L2, "L2 shadow conflict misses", self.l2_shadow_conflict_misses));
+                lines.push(StatLine::new(L2, "L2 conflict miss fraction", format!("{:.4}", self.l2_conflict_fraction())));
+            }
+        }
+        if self.inclusive {
+            lines.push(StatLine::new(L2, "back invalidations", self.back_invalidations));
+        }
+        if self.exclusive {
+            lines.push(StatLine::new(L2, "L1 victims moved to L2", self.l2_victim_fills));
+        }
+        if self.coherence {
+            lines.push(StatLine::new(L1, "coherence misses", self.coherence_misses));
+            lines.push(StatLine::new(L1, "coherence invalidations", self.coherence_invalidations));
+            lines.push(StatLine::new(L1, "coherence downgrades", self.coherence_downgrades));
+        }
+        for (core, misses) in self.core_l2_read_misses.iter().enumerate() {
+            lines.push(StatLine::new(L2, format!("core {} L2 read misses", core), misses));
+        }
+        if self.periodic_flush {
+            lines.push(StatLine::new(Memory, "periodic flushes", self.periodic_flushes));
+        }
+        if self.write_buffer_enabled {
+            lines.push(StatLine::new(Memory, "write buffer stalls", self.write_buffer_stalls));
+        }
+        if self.banks_enabled {
+            lines.push(StatLine::new(L1, "L1 bank conflicts", self.bank_conflicts));
+        }
+        if self.mshrs_enabled {
+            lines.push(StatLine::new(L1, "MSHR secondary misses", self.mshr_secondary_misses));
+            lines.push(StatLine::new(L1, "MSHR stalls", self.mshr_stalls));
+        }
+        if self.overlap_enabled {
+            lines.push(StatLine::new(Cost, "stall cycles", self.stall_cycles()));
+            lines.push(StatLine::new(Cost, "overlapped stall cycles", self.overlapped_stall_cycles()));
+        }
+        for region in 0..self.region_latency_multipliers.len() {
+            lines.push(StatLine::new(Memory, format!("region {} traffic (bytes)", region), self.region_traffic_bytes[region]));
+            lines.push(StatLine::new(Memory, format!("region {} memory reads", region), self.region_memory_reads[region]));
+        }
+        if self.tlb_enabled {
+            lines.push(StatLine::new(Accesses, "TLB hits", self.tlb_hits));
+            lines.push(StatLine::new(Accesses, "TLB misses", self.tlb_misses));
+        }
+        if self.sectored {
+            lines.push(StatLine::new(L1, "L1 sector misses", self.sector_misses));
+        }
+        if self.write_validate {
+            lines.push(StatLine::new(L1, "write-validated misses", self.write_validated_misses));
+        }
+        if self.dip_enabled {
+            lines.push(StatLine::new(L1, "L1 DIP PSEL", self.l1_psel));
+            lines.push(StatLine::new(L1, "L1 DIP winner", dip_winner(self.l1_psel)));
+            if self.l2_enabled {
+                lines.push(StatLine::new(L2, "L2 DIP PSEL", self.l2_psel));
+                lines.push(StatLine::new(L2, "L2 DIP winner", dip_winner(self.l2_psel)));
+            }
+        }
+        if self.energy_enabled {
+            lines.push(StatLine::new(Cost, "dynamic energy", format!("{:.4}", self.dynamic_energy())));
+        }
+        lines
+    }
+}
+
+/// Divide two counts for a rate, treating a zero denominator as a rate of 0 rather than NaN.
+///
+/// ## Arguments
+/// * `numerator` - The count being measured, e.g. misses.
+/// * `denominator` - The count it's measured against, e.g. accesses.
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        return 0.0;
+    }
+    numerator as f32 / denominator as f32
+}
+
+/// The group a measurement is printed in by the plain style, in printing order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Section {
+    Accesses,
+    L1,
+    L2,
+    Memory,
+    Cost,
+}
+
+impl Section {
+    /// Get the heading of the section.
+    fn name(self) -> &'static str {
+        match self {
+            Section::Accesses => "accesses",
+            Section::L1 => "L1",
+            Section::L2 => "L2",
+            Section::Memory => "memory",
+            Section::Cost => "cost",
+        }
+    }
+}
+
+/// A single measurement, formatted and ready to print in either style.
+struct StatLine {
+    section: Section,
+    /// The letter the assignment gives the measurement, if it's one of its measurements.
+    letter: Option<char>,
+    label: String,
+    value: String,
+}
+
+impl StatLine {
+    /// Creates a measurement without a letter.
+    ///
+    /// ## Arguments
+    /// * `section` - The group the measurement belongs to.
+    /// * `label` - What is measured.
+    /// * `value` - The measurement, already formatted to its precision.
+    fn new(section: Section, label: impl Into<String>, value: impl Display) -> Self {
+        Self { section, letter: None, label: label.into(), value: value.to_string() }
+    }
+
+    /// Creates one of the measurements the assignment asks for.
+    ///
+    /// ## Arguments
+    /// * `section` - The group the measurement belongs to.
+    /// * `letter` - The letter the assignment gives it.
+    /// * `label` - What is measured.
+    /// * `value` - The measurement, already formatted to its precision.
+    fn lettered(section: Section, letter: char, label: &str, value: impl Display) -> Self {
+        Self { letter: Some(letter), ..Self::new(section, label, value) }
+    }
+}
+
+/// Get the name of the insertion policy a DIP policy selector value favours.
+///
+/// ## Arguments
+/// * `psel` - The final value of the policy selector.
+fn dip_winner(psel: usize) -> &'static str {
+    if psel > DIP_PSEL_MAX / 2 { "BIP" } else { "LRU" }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new()
     }
 }
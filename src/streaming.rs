@@ -0,0 +1,93 @@
+use clap::ValueEnum;
+use crate::cache::cache::Cache;
+use crate::cache::cache::HitOrMiss::HIT;
+use crate::cache::error::CacheConfigError;
+
+/// What L1 does with the blocks of a detected stream.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum StreamingAction {
+    /// Install them at the LRU position, so they're the next blocks evicted.
+    LruInsert,
+    /// Don't install them in L1 at all. Writes still install at the LRU position, since L1
+    /// is write-allocate.
+    Bypass,
+}
+
+/// Detects streaming through L1 as a run of consecutive misses. A long enough run means the
+/// blocks coming in aren't being reused, so they shouldn't push out the ones that are.
+pub struct StreamingDetector {
+    /// Consecutive misses it takes to detect a stream.
+    pub threshold: usize,
+    pub consecutive_misses: usize,
+    pub action: StreamingAction,
+}
+
+impl StreamingDetector {
+    /// Creates a detector that hasn't seen any misses.
+    ///
+    /// ## Arguments
+    /// * `threshold` - The number of consecutive misses that signal a stream.
+    /// * `action` - What to do with the blocks of a stream.
+    pub fn new(threshold: usize, action: StreamingAction) -> Self {
+        Self {
+            threshold,
+            consecutive_misses: 0,
+            action,
+        }
+    }
+
+    /// Record whether an L1 access hit. Any hit ends the stream.
+    ///
+    /// ## Arguments
+    /// * `hit` - Whether the access hit in L1.
+    ///
+    /// Returns whether L1 is currently streaming.
+    pub fn observe(&mut self, hit: bool) -> bool {
+        self.consecutive_misses = if hit { 0 } else { self.consecutive_misses + 1 };
+        self.consecutive_misses >= self.threshold
+    }
+}
+
+/// A copy of L1 that inserts every block at L1's own insertion position, MRU unless
+/// configured otherwise, and is never bypassed, fed the same lookups as the real L1. Comparing the two estimates the misses streaming detection avoids and
+/// adds. Only L1 itself is copied, so blocks the real L1 gets from prefetches or loses to
+/// invalidations from L2 are missing from it.
+pub struct MruBaseline {
+    pub cache: Cache,
+}
+
+impl MruBaseline {
+    /// Creates an empty baseline for L1.
+    ///
+    /// ## Arguments
+    /// * `l1` - The cache to copy, with its replacement policy and indexing already set up.
+    ///
+    /// Returns an error if the cache can't be copied, like `Cache::new`.
+    pub fn new(l1: &Cache) -> Result<Self, CacheConfigError> {
+        let mut cache = Cache::new(l1.cache_size, l1.assoc, l1.block_size)?;
+        cache.set_replacement_policy(l1.replacement)?;
+        cache.rrip_bits = l1.rrip_bits;
+        cache.set_seed(l1.seed);
+        cache.lfu_aging_interval = l1.lfu_aging_interval;
+        cache.set_insertion(l1.insertion, l1.bimodal_probability)?;
+        if l1.skewed {
+            cache.set_skewed()?;
+        }
+        if l1.page_color_bits != 0 {
+            cache.set_page_coloring(1 << l1.page_offset_bits, l1.page_color_bits)?;
+        }
+        Ok(Self { cache })
+    }
+
+    /// Look up a block in the baseline, installing it if it missed.
+    ///
+    /// ## Arguments
+    /// * `address` - The address being looked up.
+    /// * `is_write` - Whether the lookup is a write.
+    ///
+    /// Returns whether the baseline hit.
+    pub fn observe(&mut self, address: usize, is_write: bool) -> bool {
+        let (index, tag) = (self.cache.index_of(address), self.cache.tag_of(address));
+        self.cache.access(index, tag, address, is_write).hit_or_miss == HIT
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use rayon::prelude::*;
+use crate::access::Access;
+use crate::config::Config;
+use crate::simulator::simulate;
+use crate::statistics::Statistics;
+
+/// The sizes that describe a cache hierarchy: the block size shared by every level, and the
+/// size and associativity of L1 and L2. An L2 size of 0 means there is no L2.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Geometry {
+    pub block_size: usize,
+    pub l1_size: usize,
+    pub l1_assoc: usize,
+    pub l2_size: usize,
+    pub l2_assoc: usize,
+}
+
+/// A line of a sweep file that isn't a valid geometry.
+#[derive(Debug, PartialEq)]
+pub struct SweepParseError {
+    /// The 1-based line number in the sweep file.
+    pub line: usize,
+    pub text: String,
+}
+
+impl fmt::Display for SweepParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sweep line {} must be \"<block_size> <l1_size> <l1_assoc> <l2_size> <l2_assoc>\", got {:?}",
+            self.line, self.text
+        )
+    }
+}
+
+impl std::error::Error for SweepParseError {}
+
+/// Parse a sweep file: one geometry per line as
+/// `<block_size> <l1_size> <l1_assoc> <l2_size> <l2_assoc>`. Blank lines and lines starting
+/// with `#` are skipped.
+///
+/// ## Arguments
+/// * `contents` - The contents of the sweep file.
+///
+/// Returns the geometries in file order, or the first line that couldn't be parsed.
+pub fn parse_sweep(contents: &str) -> Result<Vec<Geometry>, SweepParseError> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty() && !text.trim_start().starts_with('#'))
+        .map(|(i, text)| {
+            let error = || SweepParseError { line: i + 1, text: text.to_string() };
+            let values = text
+                .split_whitespace()
+                .map(|value| value.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| error())?;
+            match values[..] {
+                [block_size, l1_size, l1_assoc, l2_size, l2_assoc] => {
+                    Ok(Geometry { block_size, l1_size, l1_assoc, l2_size, l2_assoc })
+                }
+                _ => Err(error()),
+            }
+        })
+        .collect()
+}
+
+/// Simulate the same trace through several hierarchies in parallel. Each simulation owns its
+/// caches and statistics and reads the one shared copy of the trace.
+///
+/// ## Arguments
+/// * `config` - Every option other than the geometry, applied to each simulation.
+/// * `geometries` - The hierarchies to simulate.
+/// * `trace` - The parsed trace, shared by every simulation.
+///
+/// Returns the statistics of every simulation, in the same order as `geometries`.
+///
+/// ## Throws
+/// This function will panic with the reason if any geometry is invalid.
+///
+/// ## Example
+/// ```
+/// use cache_simulator::access::{Access, AccessType};
+/// use cache_simulator::config::Config;
+/// use cache_simulator::simulator::simulate;
+/// use cache_simulator::statistics::Statistics;
+/// use cache_simulator::sweep::{run_sweep, Geometry};
+///
+/// // a strided mix of reads and writes over 16K, which every geometry below misses on
+/// let trace: Vec<Access> = (0..4096)
+///     .map(|i| {
+///         let rw = if i % 3 == 0 { AccessType::Write } else { AccessType::Read };
+///         Access { rw, address: i * 40 % 16384, size: 4, timestamp: None }
+///     })
+///     .collect();
+/// let geometries = [
+///     Geometry { block_size: 32, l1_size: 1024, l1_assoc: 2, l2_size: 0, l2_assoc: 0 },
+///     Geometry { block_size: 32, l1_size: 1024, l1_assoc: 2, l2_size: 8192, l2_assoc: 4 },
+///     Geometry { block_size: 64, l1_size: 4096, l1_assoc: 1, l2_size: 16384, l2_assoc: 8 },
+///     Geometry { block_size: 16, l1_size: 512, l1_assoc: 4, l2_size: 2048, l2_assoc: 2 },
+/// ];
+/// let config = Config { flush_at_end: true, ..Config::new(geometries[0]) };
+/// let counts = |stats: &Statistics| {
+///     (stats.l1_read_misses, stats.l1_write_misses, stats.l2_read_misses, stats.total_memory_traffic)
+/// };
+///
+/// // running in parallel gives the same results, in the same order, as one at a time
+/// let sequential: Vec<_> = geometries
+///     .iter()
+///     .map(|&geometry| counts(&simulate(&Config { geometry, ..config.clone() }, trace.iter().copied())))
+///     .collect();
+/// let parallel: Vec<_> = run_sweep(&config, &geometries, &trace).iter().map(counts).collect();
+/// assert_eq!(parallel, sequential);
+/// ```
+pub fn run_sweep(config: &Config, geometries: &[Geometry], trace: &[Access]) -> Vec<Statistics> {
+    geometries
+        .par_iter()
+        .map(|&geometry| simulate(&Config { geometry, ..config.clone() }, trace.iter().copied()))
+        .collect()
+}
@@ -0,0 +1,63 @@
+use crate::cache::cache::Cache;
+use crate::cache::cache::HitOrMiss::HIT;
+use crate::cache::error::CacheConfigError;
+
+/// A translation lookaside buffer, modelled as a set-associative LRU cache whose blocks are
+/// pages, so each entry holds one virtual page number. Pages map to frames one to one, so a
+/// TLB miss only costs time and never changes which data the caches see.
+pub struct Tlb {
+    pub entries: Cache,
+}
+
+impl Tlb {
+    /// Creates a new, empty TLB.
+    ///
+    /// ## Arguments
+    /// * `entries` - The number of translations the TLB holds.
+    /// * `assoc` - The associativity of the TLB.
+    /// * `page_size` - The size of a page in bytes.
+    ///
+    /// Returns an error if the entries, associativity, and page size don't describe a valid
+    /// power-of-two geometry, the same as for a cache.
+    pub fn new(entries: usize, assoc: usize, page_size: usize) -> Result<Self, CacheConfigError> {
+        Ok(Self {
+            entries: Cache::new(entries * page_size, assoc, page_size)?,
+        })
+    }
+
+    /// Translate an address, filling the TLB with its page's translation on a miss.
+    ///
+    /// ## Arguments
+    /// * `address` - The virtual address being accessed.
+    ///
+    /// Returns whether the translation was already in the TLB.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::tlb::Tlb;
+    ///
+    /// // two entries for 4K pages, in a single set
+    /// let mut tlb = Tlb::new(2, 2, 4096)?;
+    /// // the first access to a page misses, and any address in the page hits after it
+    /// assert!(!tlb.translate(0x1000));
+    /// assert!(tlb.translate(0x1ff8));
+    /// assert!(!tlb.translate(0x2010));
+    /// assert!(tlb.translate(0x1004));
+    /// // a third page replaces the least recently used one, page 2
+    /// assert!(!tlb.translate(0x3000));
+    /// assert!(tlb.translate(0x1000));
+    /// assert!(!tlb.translate(0x2000));
+    /// # Ok::<(), cache_simulator::cache::error::CacheConfigError>(())
+    /// ```
+    pub fn translate(&mut self, address: usize) -> bool {
+        let (index, tag) = (self.entries.index_of(address), self.entries.tag_of(address));
+        if self.entries.read(index, tag) == HIT {
+            return true;
+        }
+        if self.entries.set_is_full(index, tag) {
+            self.entries.evict_lru_block(index, tag);
+        }
+        self.entries.install(index, tag, address);
+        false
+    }
+}
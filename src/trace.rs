@@ -0,0 +1,292 @@
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use clap::ValueEnum;
+use crate::access::{Access, AccessType};
+use crate::cache::cache::ADDRESS_BITS;
+
+/// The layout of the records in a trace file.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum TraceFormat {
+    /// `<action> <hex address> [size]`, where the action is `r`, `w`, or `i`.
+    Legacy,
+    /// DineroIV's `<label> <hex address> [size]`, where the label is 0 (read), 1 (write),
+    /// or 2 (instruction fetch).
+    Dinero,
+}
+
+/// The base the addresses in a trace are written in. Addresses with a `0x` prefix are read
+/// as hex either way.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum AddressRadix {
+    Hex,
+    Dec,
+}
+
+/// Reasons a trace record can't be parsed.
+#[derive(Debug, PartialEq)]
+pub enum TraceError {
+    /// The record has no action.
+    MissingAction,
+    /// The action isn't one the format knows about.
+    UnknownAction(String),
+    /// The record has an action but no address.
+    MissingAddress,
+    /// The address isn't a valid number in the trace's radix.
+    InvalidAddress(String),
+    /// The address doesn't fit in the `ADDRESS_BITS` bits the caches split.
+    AddressTooWide(String),
+    /// The size isn't a positive decimal number.
+    InvalidSize(String),
+    /// The timestamp isn't a decimal number after an `@`.
+    InvalidTimestamp(String),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::MissingAction => write!(f, "Missing action"),
+            TraceError::UnknownAction(action) => write!(f, "Unknown action {}", action),
+            TraceError::MissingAddress => write!(f, "Missing address"),
+            TraceError::InvalidAddress(address) => write!(f, "Invalid address {}", address),
+            TraceError::AddressTooWide(address) => write!(f, "Address {} is wider than {} bits", address, ADDRESS_BITS),
+            TraceError::InvalidSize(size) => write!(f, "Invalid size {}", size),
+            TraceError::InvalidTimestamp(timestamp) => write!(f, "Invalid timestamp {}", timestamp),
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+/// A trace record that couldn't be parsed, with the line of the trace it's on.
+#[derive(Debug, PartialEq)]
+pub struct TraceLineError {
+    /// The 1-based line number in the trace file.
+    pub line: usize,
+    pub error: TraceError,
+}
+
+impl fmt::Display for TraceLineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl Error for TraceLineError {}
+
+/// Parse a single record of a trace.
+///
+/// ## Arguments
+/// * `line` - The line of the trace holding the record.
+/// * `format` - The layout of the record.
+/// * `radix` - The base the address is written in.
+///
+/// Returns the access type, address, size, and timestamp of the record. The size is an
+/// optional decimal number of bytes after the address, 1 if it's left out. The timestamp is
+/// an optional decimal cycle count after that, marked with an `@`, e.g. `r 1f00 8 @5120` or
+/// `r 1f00 @5120`. An address wider than `ADDRESS_BITS` is an error rather than being cut
+/// down to fit.
+///
+/// ## Example
+/// ```
+/// use cache_simulator::trace::{parse_record, AddressRadix, TraceError, TraceFormat};
+///
+/// assert!(parse_record("r ffffffff", TraceFormat::Legacy, AddressRadix::Hex).is_ok());
+/// assert_eq!(
+///     parse_record("r 0xab12345678", TraceFormat::Legacy, AddressRadix::Hex),
+///     Err(TraceError::AddressTooWide("0xab12345678".to_string()))
+/// );
+///
+/// let access = parse_record("w 1f00 @5120", TraceFormat::Legacy, AddressRadix::Hex)?;
+/// assert_eq!((access.size, access.timestamp), (1, Some(5120)));
+/// let access = parse_record("1 1f00 8 @5121", TraceFormat::Dinero, AddressRadix::Hex)?;
+/// assert_eq!((access.size, access.timestamp), (8, Some(5121)));
+/// assert_eq!(
+///     parse_record("r 1f00 8 @soon", TraceFormat::Legacy, AddressRadix::Hex),
+///     Err(TraceError::InvalidTimestamp("@soon".to_string()))
+/// );
+/// # Ok::<(), TraceError>(())
+/// ```
+pub fn parse_record(line: &str, format: TraceFormat, radix: AddressRadix) -> Result<Access, TraceError> {
+    let parts: Vec<&str> = match format {
+        TraceFormat::Legacy => line.split(' ').collect(),
+        TraceFormat::Dinero => line.split_whitespace().collect(),
+    };
+    let mut parts = parts.into_iter();
+
+    // get the action
+    let action = parts.next().filter(|action| !action.is_empty()).ok_or(TraceError::MissingAction)?;
+    let access_type = match format {
+        TraceFormat::Legacy => action.chars().next().and_then(AccessType::from_char),
+        TraceFormat::Dinero => match action {
+            "0" => Some(AccessType::Read),
+            "1" => Some(AccessType::Write),
+            "2" => Some(AccessType::InstructionFetch),
+            _ => None,
+        },
+    }.ok_or_else(|| TraceError::UnknownAction(action.to_string()))?;
+
+    // get the address
+    let address = parts.next().ok_or(TraceError::MissingAddress)?;
+    let parsed = match address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => match radix {
+            AddressRadix::Hex => usize::from_str_radix(address, 16),
+            AddressRadix::Dec => address.parse(),
+        },
+    };
+    let address = match parsed {
+        Ok(parsed) if parsed >> ADDRESS_BITS != 0 => return Err(TraceError::AddressTooWide(address.to_string())),
+        Ok(parsed) => parsed,
+        Err(_) => return Err(TraceError::InvalidAddress(address.to_string())),
+    };
+
+    // get the size, if there is one
+    let mut parts = parts.peekable();
+    let size = match parts.next_if(|part| !part.starts_with('@')).filter(|size| !size.is_empty()) {
+        Some(size) => size.parse().ok().filter(|&size| size > 0).ok_or_else(|| TraceError::InvalidSize(size.to_string()))?,
+        None => 1,
+    };
+
+    // get the timestamp, if there is one
+    let timestamp = match parts.next().filter(|part| part.starts_with('@')) {
+        Some(timestamp) => Some(timestamp[1..].parse().map_err(|_| TraceError::InvalidTimestamp(timestamp.to_string()))?),
+        None => None,
+    };
+
+    Ok(Access { rw: access_type, address, size, timestamp })
+}
+
+/// Parse a single record of a multicore trace, which may start with the decimal id of the
+/// core making the access, e.g. `1 w 1f00`. Records without one belong to core 0. Only the
+/// legacy format can carry a core id, since DineroIV labels are numbers too, and a leading
+/// number is only taken as a core id when an action follows it.
+///
+/// ## Arguments
+/// * `line` - The line of the trace holding the record.
+/// * `format` - The layout of the record.
+/// * `radix` - The base the address is written in.
+///
+/// Returns the core id with the access of the record.
+///
+/// ## Example
+/// ```
+/// use cache_simulator::access::AccessType;
+/// use cache_simulator::trace::{parse_core_record, AddressRadix, TraceError, TraceFormat};
+///
+/// let (core, access) = parse_core_record("1 w 1f00", TraceFormat::Legacy, AddressRadix::Hex)?;
+/// assert_eq!((core, access.rw, access.address), (1, AccessType::Write, 0x1f00));
+/// assert_eq!(parse_core_record("w 1f00", TraceFormat::Legacy, AddressRadix::Hex)?.0, 0);
+///
+/// // without an action after it the number is the action, which a legacy record can't have
+/// assert_eq!(
+///     parse_core_record("1 200", TraceFormat::Legacy, AddressRadix::Hex),
+///     Err(TraceError::UnknownAction("1".to_string()))
+/// );
+/// # Ok::<(), TraceError>(())
+/// ```
+pub fn parse_core_record(line: &str, format: TraceFormat, radix: AddressRadix) -> Result<(usize, Access), TraceError> {
+    if format == TraceFormat::Legacy {
+        if let Some((core, record)) = line.split_once(' ') {
+            let has_action = record.chars().next().and_then(AccessType::from_char).is_some();
+            if let (Ok(core), true) = (core.parse(), has_action) {
+                return Ok((core, parse_record(record, format, radix)?));
+            }
+        }
+    }
+    Ok((0, parse_record(line, format, radix)?))
+}
+
+/// Read and parse every record of a trace up front, so the simulation can run over the
+/// decoded accesses as many times as it needs.
+///
+/// ## Arguments
+/// * `reader` - Where to read the trace from.
+/// * `format` - The layout of the records.
+/// * `radix` - The base the addresses are written in.
+///
+/// Returns the accesses in trace order, or the first record that couldn't be parsed.
+///
+/// ## Throws
+/// This function will panic if the trace can't be read.
+pub fn read_trace<R: BufRead>(reader: R, format: TraceFormat, radix: AddressRadix) -> Result<Vec<Access>, TraceLineError> {
+    parse_trace(reader, format, radix).collect()
+}
+
+/// Parse the records of a trace one at a time as they're read, so only as much of the trace
+/// as is consumed is ever read.
+///
+/// ## Arguments
+/// * `reader` - Where to read the trace from.
+/// * `format` - The layout of the records.
+/// * `radix` - The base the addresses are written in.
+///
+/// Returns the access of every record in trace order, or the error and line number of a
+/// record that couldn't be parsed.
+///
+/// ## Example
+/// ```
+/// use cache_simulator::trace::{parse_trace, AddressRadix, TraceError, TraceFormat, TraceLineError};
+///
+/// let trace = "r 1f00\nr ab12345678\nw 1f00\n";
+/// let records: Vec<_> = parse_trace(trace.as_bytes(), TraceFormat::Legacy, AddressRadix::Hex).collect();
+/// assert!(records[0].is_ok() && records[2].is_ok());
+/// let error = TraceLineError { line: 2, error: TraceError::AddressTooWide("ab12345678".to_string()) };
+/// assert_eq!(records[1], Err(error));
+/// ```
+///
+/// ## Throws
+/// The iterator will panic if the trace can't be read.
+pub fn parse_trace<R: BufRead>(
+    reader: R,
+    format: TraceFormat,
+    radix: AddressRadix,
+) -> impl Iterator<Item = Result<Access, TraceLineError>> {
+    reader.lines().enumerate().map(move |(i, line)| {
+        let line = line.expect("Failed to read trace");
+        parse_record(&line, format, radix).map_err(|error| TraceLineError { line: i + 1, error })
+    })
+}
+
+/// Read and parse every record of a multicore trace up front, like `read_trace`.
+///
+/// ## Arguments
+/// * `reader` - Where to read the trace from.
+/// * `format` - The layout of the records.
+/// * `radix` - The base the addresses are written in.
+///
+/// Returns the core id and access of every record in trace order, or the first record that
+/// couldn't be parsed.
+///
+/// ## Throws
+/// This function will panic if the trace can't be read.
+pub fn read_core_trace<R: BufRead>(
+    reader: R,
+    format: TraceFormat,
+    radix: AddressRadix,
+) -> Result<Vec<(usize, Access)>, TraceLineError> {
+    parse_core_trace(reader, format, radix).collect()
+}
+
+/// Parse the records of a multicore trace one at a time as they're read, like `parse_trace`.
+///
+/// ## Arguments
+/// * `reader` - Where to read the trace from.
+/// * `format` - The layout of the records.
+/// * `radix` - The base the addresses are written in.
+///
+/// Returns the core id and access of every record in trace order, or the error and line
+/// number of a record that couldn't be parsed.
+///
+/// ## Throws
+/// The iterator will panic if the trace can't be read.
+pub fn parse_core_trace<R: BufRead>(
+    reader: R,
+    format: TraceFormat,
+    radix: AddressRadix,
+) -> impl Iterator<Item = Result<(usize, Access), TraceLineError>> {
+    reader.lines().enumerate().map(move |(i, line)| {
+        let line = line.expect("Failed to read trace");
+        parse_core_record(&line, format, radix).map_err(|error| TraceLineError { line: i + 1, error })
+    })
+}
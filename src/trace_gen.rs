@@ -0,0 +1,89 @@
+use crate::access::{Access, AccessType};
+use crate::rng::SplitMix64;
+
+/// The shape of a synthetic trace.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TraceSpec {
+    /// Number of accesses to generate.
+    pub accesses: usize,
+    /// Size in bytes of the region every address falls in.
+    pub working_set: usize,
+    /// First address of the working set.
+    pub base_address: usize,
+    /// Fraction of accesses that are writes, from 0 to 1.
+    pub write_fraction: f64,
+    /// Fraction of accesses that continue the sequential stream rather than landing at a
+    /// random address, from 0 to 1.
+    pub sequential_fraction: f64,
+    /// Bytes between consecutive accesses of the sequential stream, which wraps around the
+    /// working set.
+    pub stride: usize,
+    /// Seed for the random number generator. The same spec always generates the same trace.
+    pub seed: u64,
+}
+
+impl Default for TraceSpec {
+    fn default() -> Self {
+        TraceSpec {
+            accesses: 100_000,
+            working_set: 1 << 20,
+            base_address: 0x1000_0000,
+            write_fraction: 0.25,
+            sequential_fraction: 0.5,
+            stride: 4,
+            seed: 0,
+        }
+    }
+}
+
+/// Generates the accesses of a synthetic trace, one at a time.
+pub struct TraceGenerator {
+    spec: TraceSpec,
+    rng: SplitMix64,
+    offset: usize,
+    generated: usize,
+}
+
+impl TraceGenerator {
+    /// Create a generator for a trace.
+    ///
+    /// ## Arguments
+    /// * `spec` - The shape of the trace.
+    ///
+    /// ## Throws
+    /// This function will panic if the working set is empty.
+    pub fn new(spec: TraceSpec) -> Self {
+        assert!(spec.working_set != 0, "the working set must not be empty");
+        TraceGenerator {
+            spec,
+            rng: SplitMix64::new(spec.seed),
+            offset: 0,
+            generated: 0,
+        }
+    }
+}
+
+impl Iterator for TraceGenerator {
+    type Item = Access;
+
+    fn next(&mut self) -> Option<Access> {
+        if self.generated == self.spec.accesses {
+            return None;
+        }
+        self.generated += 1;
+
+        let offset = if self.rng.next_fraction() < self.spec.sequential_fraction {
+            let offset = self.offset;
+            self.offset = (self.offset + self.spec.stride) % self.spec.working_set;
+            offset
+        } else {
+            self.rng.next_u64() as usize % self.spec.working_set
+        };
+        let rw = if self.rng.next_fraction() < self.spec.write_fraction {
+            AccessType::Write
+        } else {
+            AccessType::Read
+        };
+        Some(Access { rw, address: self.spec.base_address + offset, size: 1, timestamp: None })
+    }
+}
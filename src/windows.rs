@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+use crate::statistics::Statistics;
+
+/// The misses of one window of time, from the counters at its first and last access.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WindowCounts {
+    /// The first cycle of the window.
+    pub start: u64,
+    pub accesses: usize,
+    pub l1_misses: usize,
+    /// Demand misses in L2.
+    pub l2_misses: usize,
+}
+
+/// Splits a run into fixed windows of time and counts the misses in each, to see how the
+/// miss rate changes over the run. The time of an access is its timestamp, or its index in
+/// the trace if it has none. Timestamps are expected not to decrease, and an access earlier
+/// than the current window is counted in it.
+pub struct TimeWindows {
+    /// The number of cycles in each window.
+    pub window: u64,
+    /// The first cycle of the current window.
+    pub start: u64,
+    /// The accesses, L1 misses, and L2 misses counted before the current window.
+    snapshot: (usize, usize, usize),
+    /// Every window that has ended, without the ones no access fell into.
+    pub windows: Vec<WindowCounts>,
+}
+
+impl TimeWindows {
+    /// Creates a new set of windows that hasn't seen any accesses.
+    ///
+    /// ## Arguments
+    /// * `window` - The number of cycles in each window.
+    ///
+    /// ## Throws
+    /// This function will panic if the window is 0 cycles.
+    pub fn new(window: u64) -> Self {
+        assert!(window != 0, "A time window must be at least 1 cycle.");
+        Self {
+            window,
+            start: 0,
+            snapshot: (0, 0, 0),
+            windows: vec![],
+        }
+    }
+
+    /// Start the window an access falls into, ending the current one if the access is past
+    /// it. Call this before the access is simulated.
+    ///
+    /// ## Arguments
+    /// * `time` - The timestamp of the access.
+    /// * `stats` - The counters before the access.
+    ///
+    /// ## Example
+    /// ```
+    /// use cache_simulator::statistics::Statistics;
+    /// use cache_simulator::windows::{TimeWindows, WindowCounts};
+    ///
+    /// // one miss at cycle 3, then two more at cycles 250 and 260
+    /// let (mut windows, mut stats) = (TimeWindows::new(100), Statistics::new());
+    /// for (time, miss) in [(3, true), (40, false), (250, true), (260, true)] {
+    ///     windows.observe(time, &stats);
+    ///     stats.l1_reads += 1;
+    ///     stats.l1_read_misses += usize::from(miss);
+    /// }
+    /// windows.finish(&stats);
+    /// assert_eq!(windows.windows, [
+    ///     WindowCounts { start: 0, accesses: 2, l1_misses: 1, l2_misses: 0 },
+    ///     WindowCounts { start: 200, accesses: 2, l1_misses: 2, l2_misses: 0 },
+    /// ]);
+    /// ```
+    pub fn observe(&mut self, time: u64, stats: &Statistics) {
+        if time < self.start.saturating_add(self.window) {
+            return;
+        }
+        self.finish(stats);
+        self.start = time / self.window * self.window;
+    }
+
+    /// End the current window, keeping its counts if any access fell into it.
+    ///
+    /// ## Arguments
+    /// * `stats` - The counters after the last access of the window.
+    pub fn finish(&mut self, stats: &Statistics) {
+        let counters = (stats.l1_accesses(), stats.l1_misses(), stats.l2_read_misses);
+        let (accesses, l1_misses, l2_misses) = self.snapshot;
+        if counters.0 != accesses {
+            self.windows.push(WindowCounts {
+                start: self.start,
+                accesses: counters.0 - accesses,
+                l1_misses: counters.1 - l1_misses,
+                l2_misses: counters.2 - l2_misses,
+            });
+        }
+        self.snapshot = counters;
+    }
+
+    /// Forget every window so far, e.g. at the end of the warmup, when the counters are
+    /// reset. The current window starts over from the reset counters.
+    pub fn reset(&mut self) {
+        self.snapshot = (0, 0, 0);
+        self.windows.clear();
+    }
+
+    /// Print out the counts of every window, one per line.
+    ///
+    /// ## Arguments
+    /// * `out` - Where to write the windows, e.g. stdout.
+    pub fn print<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{:>16} {:>10} {:>10} {:>10}", "start", "accesses", "L1 misses", "L2 misses")?;
+        for window in &self.windows {
+            writeln!(out, "{:>16} {:>10} {:>10} {:>10}", window.start, window.accesses, window.l1_misses, window.l2_misses)?;
+        }
+        Ok(())
+    }
+}
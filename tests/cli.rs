@@ -0,0 +1,229 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// The trace most tests run.
+const TRACE: &str = "r 1000\nw 1f04\nr 1000\nr 2c10\nw 3010\n";
+
+/// The simulator with a 1K 2-way L1 and 4K 4-way L2, for a test to add its trace.
+fn simulator() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cache-simulator"));
+    command.args(["--block-size", "32", "--l1-size", "1024", "--l1-assoc", "2"]);
+    command.args(["--l2-size", "4096", "--l2-assoc", "4"]);
+    command
+}
+
+/// Get the lines a run printed, without the one naming the trace files, to compare runs of
+/// the same records read from different places.
+///
+/// ## Arguments
+/// * `out` - What the run printed.
+fn without_trace_file(out: &str) -> Vec<&str> {
+    out.lines().filter(|line| !line.starts_with("trace_file:")).collect()
+}
+
+/// Run the simulator on `TRACE` with extra flags.
+///
+/// ## Arguments
+/// * `name` - A name for the trace file, unique to the test.
+/// * `flags` - The flags to add to the hierarchy `simulator` sets up.
+fn simulator_output(name: &str, flags: &[&str]) -> Output {
+    let trace = env::temp_dir().join(format!("cache-simulator-{}-{}.txt", name, std::process::id()));
+    fs::write(&trace, TRACE).unwrap();
+    let output = simulator().args(flags).arg("--trace").arg(&trace).output().unwrap();
+    fs::remove_file(&trace).unwrap();
+    output
+}
+
+/// Run the simulator on `TRACE` with extra flags and return what it printed, like
+/// `simulator_output` but for runs that must succeed.
+fn run_simulator(name: &str, flags: &[&str]) -> String {
+    let output = simulator_output(name, flags);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn trace_from_stdin_matches_the_same_trace_from_a_file() {
+    let mut child = simulator()
+        .args(["--trace", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(TRACE.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let trace = env::temp_dir().join(format!("cache-simulator-stdin-{}.txt", std::process::id()));
+    fs::write(&trace, TRACE).unwrap();
+    let from_file = simulator().arg("--trace").arg(&trace).output().unwrap();
+    fs::remove_file(&trace).unwrap();
+    assert!(from_file.status.success(), "{}", String::from_utf8_lossy(&from_file.stderr));
+
+    // everything but the trace file line is the same
+    let from_stdin = String::from_utf8(output.stdout).unwrap();
+    let from_file = String::from_utf8(from_file.stdout).unwrap();
+    assert!(from_stdin.contains("trace_file:  -\n"));
+    assert_eq!(without_trace_file(&from_stdin), without_trace_file(&from_file));
+}
+
+#[test]
+fn bad_geometry_exits_with_an_error() {
+    let output = Command::new(env!("CARGO_BIN_EXE_cache-simulator"))
+        .args(["--block-size", "16", "--l1-size", "48", "--l1-assoc", "2", "--trace", "-"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let error = "Invalid L1 configuration: cache size 48 is not a multiple of assoc * block size (2 * 16)";
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), error);
+}
+
+#[test]
+fn dinero_trace_matches_the_same_accesses_in_the_legacy_format() {
+    let legacy = simulator().args(["--trace", "tests/legacy-trace.txt"]).output().unwrap();
+    assert!(legacy.status.success(), "{}", String::from_utf8_lossy(&legacy.stderr));
+    let dinero = simulator()
+        .args(["--trace-format", "dinero", "--trace", "tests/dinero-trace.txt"])
+        .output()
+        .unwrap();
+    assert!(dinero.status.success(), "{}", String::from_utf8_lossy(&dinero.stderr));
+
+    let legacy = String::from_utf8(legacy.stdout).unwrap();
+    let dinero = String::from_utf8(dinero.stdout).unwrap();
+    assert!(legacy.contains("L1 instruction fetches:        2\n"));
+    assert_eq!(without_trace_file(&legacy), without_trace_file(&dinero));
+}
+
+#[test]
+fn warmup_longer_than_the_trace_counts_nothing() {
+    let trace = env::temp_dir().join(format!("cache-simulator-warmup-{}.txt", std::process::id()));
+    fs::write(&trace, TRACE).unwrap();
+    let output = simulator().args(["--warmup", "10", "--trace"]).arg(&trace).output().unwrap();
+    fs::remove_file(&trace).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(out.contains("a. L1 reads:                   0\n"));
+    assert!(out.contains("c. L1 writes:                  0\n"));
+    assert!(out.contains("q. memory traffic:             0\n"));
+}
+
+#[test]
+fn bad_sweep_line_exits_with_an_error() {
+    let sweep = env::temp_dir().join(format!("cache-simulator-sweep-{}.txt", std::process::id()));
+    fs::write(&sweep, "32 1024 2 4096 4\n32 1024 two 4096 4\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cache-simulator"))
+        .args(["--trace", "-", "--sweep"])
+        .arg(&sweep)
+        .output()
+        .unwrap();
+    fs::remove_file(&sweep).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("sweep line 2 must be"));
+}
+
+#[test]
+fn skip_malformed_counts_the_lines_it_skips() {
+    let trace = env::temp_dir().join(format!("cache-simulator-malformed-{}.txt", std::process::id()));
+    fs::write(&trace, format!("{}r zz\nbogus\nw\n", TRACE)).unwrap();
+    let output = simulator().args(["--skip-malformed", "--trace"]).arg(&trace).output().unwrap();
+    fs::remove_file(&trace).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(out.contains("\nskipped malformed lines:       3\n"));
+
+    // without the flag the first bad line stops the run
+    let mut child = simulator()
+        .args(["--trace", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(format!("{}r zz\n", TRACE).as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "stdin, line 6: Invalid address zz");
+}
+
+#[test]
+fn malformed_trace_line_exits_with_its_line_number() {
+    let mut child = simulator()
+        .args(["--trace", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"r 1000\nr ab12345678\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "stdin, line 2: Address ab12345678 is wider than 32 bits");
+}
+
+#[test]
+fn trace_split_over_files_matches_the_whole_trace() {
+    // the same records as the single trace, split after the second, and the whole trace
+    let parts = ["r 1000\nw 1f04\n", "r 1000\nr 2c10\nw 3010\n", TRACE];
+    assert_eq!(parts[..2].concat(), TRACE);
+    let paths = [0, 1, 2].map(|part| {
+        env::temp_dir().join(format!("cache-simulator-part-{}-{}.txt", part, std::process::id()))
+    });
+    for (path, part) in paths.iter().zip(parts) {
+        fs::write(path, part).unwrap();
+    }
+    let from_files = simulator().args(["--flush-at-end", "--trace"]).args(&paths[..2]).output().unwrap();
+    let from_one_file = simulator().args(["--flush-at-end", "--trace"]).arg(&paths[2]).output().unwrap();
+    for path in &paths {
+        fs::remove_file(path).unwrap();
+    }
+    assert!(from_files.status.success(), "{}", String::from_utf8_lossy(&from_files.stderr));
+
+    // the caches carry over from one file to the next, so the contents and every counter match
+    let from_files = String::from_utf8(from_files.stdout).unwrap();
+    let from_one_file = String::from_utf8(from_one_file.stdout).unwrap();
+    assert_eq!(without_trace_file(&from_files), without_trace_file(&from_one_file));
+}
+
+#[test]
+fn quiet_prints_measurements_without_contents() {
+    let out = run_simulator("quiet", &["--quiet"]);
+    assert!(out.contains("===== Measurements ====="));
+    assert!(!out.contains("L1 contents") && !out.contains("L2 contents"));
+}
+
+#[test]
+fn contents_only_prints_contents_without_measurements() {
+    let out = run_simulator("contents-only", &["--contents-only"]);
+    assert!(out.contains("===== L1 contents =====") && out.contains("===== L2 contents ====="));
+    assert!(!out.contains("Measurements"));
+}
+
+#[test]
+fn window_stats_fall_back_to_record_index() {
+    let out = run_simulator("window-stats", &["--quiet", "--window-stats", "2"]);
+    let rows: Vec<Vec<&str>> = out
+        .lines()
+        .skip_while(|line| !line.starts_with("===== Misses per 2 cycles"))
+        .skip(2)
+        .take(3)
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(rows, [["0", "2", "2", "2"], ["2", "2", "1", "1"], ["4", "1", "1", "1"]]);
+}
+
+#[test]
+fn l2_partition_needs_several_cores() {
+    let output = simulator_output("partition-one-core", &["--l2-partition", "f"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "Partitioning L2 needs --cores 2 or more.");
+}
+
+#[test]
+fn bad_l2_partition_exits_with_an_error() {
+    let output = simulator_output("bad-partition", &["--cores", "2", "--l2-partition", "1f", "--l2-partition", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Invalid L2 partition"));
+}
@@ -0,0 +1,222 @@
+use cache_simulator::cache::cache::{Cache, EvictionResult, HitOrMiss, ADDRESS_BITS};
+use cache_simulator::cache::cache::HitOrMiss::{HIT, MISS};
+use cache_simulator::cache::error::CacheConfigError;
+
+/// Read an address the way the simulator does, evicting the least recently used block of a
+/// full set on a miss and installing the block.
+///
+/// ## Arguments
+/// * `cache` - The cache to read from.
+/// * `address` - The address to read.
+///
+/// Returns whether the read hit, and the block it evicted if any.
+fn read(cache: &mut Cache, address: usize) -> (HitOrMiss, Option<EvictionResult>) {
+    let (index, tag) = (cache.index_of(address), cache.tag_of(address));
+    if cache.read(index, tag) == HIT {
+        return (HIT, None);
+    }
+    let evicted = cache.set_is_full(index, tag).then(|| cache.evict_lru_block(index, tag));
+    cache.install(index, tag, address);
+    (MISS, evicted)
+}
+
+#[test]
+fn fully_associative_cache_has_one_set_and_keeps_tag_zero() {
+    // 4 blocks in 4 ways leave a single set and no index bits
+    let mut cache = Cache::new(64, 4, 16).unwrap();
+    assert_eq!((cache.sets, cache.index_bits), (1, 0));
+    assert_eq!((cache.index_of(0x0), cache.tag_of(0x0)), (0, 0));
+    assert_eq!((cache.index_of(0x1234), cache.tag_of(0x1234)), (0, 0x123));
+
+    // tag 0 misses once and then hits like any other tag
+    assert!(read(&mut cache, 0x0).0 == MISS);
+    assert!(read(&mut cache, 0x4).0 == HIT);
+
+    // every address shares the set, so it fills up without evicting anything
+    for address in [0x10, 0x1000, 0x1234] {
+        let (hit_or_miss, evicted) = read(&mut cache, address);
+        assert!(hit_or_miss == MISS && evicted.is_none());
+    }
+
+    // and the fifth block evicts the least recently used one, tag 0
+    let evicted = read(&mut cache, 0x40).1.unwrap();
+    assert_eq!(evicted.evicted_block_address, 0x0);
+    assert!(!cache.contains(0, 0));
+}
+
+#[test]
+fn direct_mapped_cache_evicts_on_every_conflict() {
+    // 4 sets of a single way, indexed by bits 4-5
+    let mut cache = Cache::new(64, 1, 16).unwrap();
+    assert_eq!((cache.sets, cache.index_bits), (4, 2));
+    assert_eq!((cache.index_of(0x40), cache.tag_of(0x40)), (0, 1));
+
+    // a block in another set leaves 0x0 alone
+    read(&mut cache, 0x0);
+    assert!(read(&mut cache, 0x10).1.is_none());
+    assert!(read(&mut cache, 0x0).0 == HIT);
+
+    // but each block in the same set evicts the previous one
+    assert_eq!(read(&mut cache, 0x40).1.unwrap().evicted_block_address, 0x0);
+    assert_eq!(read(&mut cache, 0x0).1.unwrap().evicted_block_address, 0x40);
+    assert!(read(&mut cache, 0x10).0 == HIT);
+}
+
+#[test]
+fn zero_block_size_is_rejected() {
+    assert_eq!(Cache::new(1024, 2, 0).err(), Some(CacheConfigError::ZeroBlockSize));
+}
+
+#[test]
+fn zero_assoc_is_rejected() {
+    assert_eq!(Cache::new(1024, 0, 32).err(), Some(CacheConfigError::ZeroAssoc));
+}
+
+#[test]
+fn non_power_of_two_block_size_is_rejected() {
+    assert_eq!(Cache::new(1536, 2, 48).err(), Some(CacheConfigError::BlockSizeNotPowerOfTwo(48)));
+}
+
+#[test]
+fn assoc_larger_than_the_number_of_blocks_is_rejected() {
+    let error = CacheConfigError::AssocTooLarge { assoc: 4, block_size: 32, blocks: 2 };
+    assert_eq!(Cache::new(64, 4, 32).err(), Some(error));
+    // a cache smaller than one block has no blocks at all
+    let error = CacheConfigError::AssocTooLarge { assoc: 1, block_size: 32, blocks: 0 };
+    assert_eq!(Cache::new(16, 1, 32).err(), Some(error));
+}
+
+#[test]
+fn size_that_isnt_a_whole_number_of_sets_is_rejected() {
+    let error = CacheConfigError::SizeNotMultiple { cache_size: 96, assoc: 2, block_size: 32 };
+    assert_eq!(Cache::new(96, 2, 32).err(), Some(error));
+}
+
+#[test]
+fn non_power_of_two_assoc_is_rejected() {
+    // 3072 bytes of 32-byte blocks in 3 ways would make 32 sets, but the ways aren't a power of two
+    assert_eq!(Cache::new(3072, 3, 32).err(), Some(CacheConfigError::AssocNotPowerOfTwo(3)));
+    assert_eq!(Cache::new(96, 3, 32).err(), Some(CacheConfigError::AssocNotPowerOfTwo(3)));
+}
+
+#[test]
+fn non_power_of_two_number_of_sets_is_rejected() {
+    assert_eq!(Cache::new(3072, 2, 32).err(), Some(CacheConfigError::SetsNotPowerOfTwo(48)));
+    assert_eq!(Cache::new(3072, 4, 32).err(), Some(CacheConfigError::SetsNotPowerOfTwo(24)));
+}
+
+#[test]
+fn geometry_without_tag_bits_is_rejected() {
+    // 2^27 direct-mapped sets of 32-byte blocks take all 32 address bits
+    let error = Cache::new(1 << 32, 1, 32).err().unwrap();
+    assert_eq!(error, CacheConfigError::NoTagBits { index_bits: 27, block_offset_bits: 5, address_bits: ADDRESS_BITS });
+    assert_eq!(error.to_string(), "27 index bits and 5 block offset bits leave no tag bits in a 32-bit address");
+
+    // two sets of 1 GiB blocks leave the single tag bit a cache needs
+    assert_eq!(Cache::new(1 << 31, 1, 1 << 30).unwrap().tag_bits, 1);
+}
+
+#[test]
+fn given_address_bits_must_match_the_geometry() {
+    // 1 KiB of 32-byte blocks in 2 ways has 16 sets, so 4 index bits and 5 offset bits
+    let cache = Cache::with_address_bits(1024, 2, 32, Some(4), Some(5)).unwrap();
+    assert_eq!((cache.index_bits, cache.block_offset_bits, cache.tag_bits), (4, 5, 23));
+
+    let error = Cache::with_address_bits(1024, 2, 32, Some(3), None).err().unwrap();
+    assert_eq!(error, CacheConfigError::IndexBitsMismatch { index_bits: 3, sets: 16 });
+    assert_eq!(error.to_string(), "3 index bits don't select one of 16 sets");
+
+    let error = Cache::with_address_bits(1024, 2, 32, None, Some(6)).err().unwrap();
+    assert_eq!(error, CacheConfigError::OffsetBitsMismatch { offset_bits: 6, block_size: 32 });
+    assert_eq!(error.to_string(), "6 block offset bits don't address a 32-byte block");
+}
+
+#[test]
+fn flat_block_layout_behaves_like_a_vec_per_set() {
+    // 8 sets of 4 ways, against a reference with one vec of tags per set, most recent first
+    let mut cache = Cache::new(512, 4, 16).unwrap();
+    let mut reference: Vec<Vec<usize>> = vec![vec![]; cache.sets];
+
+    // a pseudo-random trace over 64 blocks, twice the size of the cache
+    let mut state: usize = 1;
+    for _ in 0..2000 {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let address = (state >> 33) % 64 * 16;
+        let (index, tag) = (cache.index_of(address), cache.tag_of(address));
+
+        let set = &mut reference[index];
+        let expected_hit = set.contains(&tag);
+        set.retain(|&other| other != tag);
+        set.insert(0, tag);
+        let expected_evicted = (set.len() > cache.assoc).then(|| set.pop().unwrap());
+
+        let (hit_or_miss, evicted) = read(&mut cache, address);
+        assert_eq!(hit_or_miss == HIT, expected_hit);
+        let evicted = evicted.map(|block| cache.tag_of(block.evicted_block_address));
+        assert_eq!(evicted, expected_evicted);
+    }
+
+    // and every set ends up holding exactly its own blocks, in the same recency order
+    for (index, set) in reference.iter().enumerate() {
+        assert_eq!(&cache.resident_tags(index), set);
+        assert!(cache.set(index).iter().all(|block| cache.index_of(block.address) == index));
+    }
+}
+
+#[test]
+fn skewed_indexing_spreads_blocks_that_conflict_in_one_set() {
+    // three blocks that all map to set 0 of an 8-set 2-way cache, read in a cycle
+    let addresses = [0x0, 0x80, 0x100];
+    let misses = |cache: &mut Cache| {
+        (0..30).filter(|i| read(cache, addresses[i % 3]).0 == MISS).count()
+    };
+
+    // with conventional indexing they thrash the set under LRU
+    let mut conventional = Cache::new(256, 2, 16).unwrap();
+    assert_eq!(misses(&mut conventional), 30);
+
+    // each way of a skewed cache hashes them to its own row, which spreads them out enough
+    // that only the three compulsory misses are left
+    let mut skewed = Cache::new(256, 2, 16).unwrap();
+    skewed.set_skewed().unwrap();
+    assert_eq!(misses(&mut skewed), 3);
+}
+
+#[test]
+fn set_fills_one_way_at_a_time_before_anything_is_evicted() {
+    // a single 4-way set, with the first block written so a stale dirty bit would show
+    let mut cache = Cache::new(64, 4, 16).unwrap();
+    cache.enable_set_stats();
+    cache.install(0, 0, 0x0);
+    assert!(cache.write(0, 0) == HIT);
+
+    // the next three blocks each take a free way
+    for address in [0x10, 0x20, 0x30] {
+        assert!(!cache.set_is_full(0, cache.tag_of(address)));
+        let (hit_or_miss, evicted) = read(&mut cache, address);
+        assert!(hit_or_miss == MISS && evicted.is_none());
+        assert_eq!(cache.set_stats[0].evictions, 0);
+    }
+    assert_eq!(cache.resident_tags(0).len(), 4);
+
+    // only the fifth block has to evict one, the least recently used and dirty 0x0
+    assert!(cache.set_is_full(0, cache.tag_of(0x40)));
+    let evicted = read(&mut cache, 0x40).1.unwrap();
+    assert_eq!((evicted.evicted_block_address, evicted.evicted_block_was_dirty), (0x0, true));
+    assert_eq!(cache.set_stats[0].evictions, 1);
+}
+
+#[test]
+fn evicting_from_a_set_with_a_free_way_takes_the_free_way() {
+    let mut cache = Cache::new(64, 4, 16).unwrap();
+    cache.enable_set_stats();
+    cache.install(0, 0, 0x0);
+    assert!(cache.write(0, 0) == HIT);
+    read(&mut cache, 0x10);
+
+    // the two blocks stay, and the free way comes back clean and isn't counted
+    let evicted = cache.evict_lru_block(0, cache.tag_of(0x20));
+    assert!(!evicted.evicted_block_was_dirty);
+    assert_eq!(cache.set_stats[0].evictions, 0);
+    assert!(cache.holds_address(0x0) && cache.holds_address(0x10));
+}
@@ -0,0 +1,61 @@
+use cache_simulator::config::Config;
+use cache_simulator::simulator::simulate;
+use cache_simulator::statistics::Statistics;
+use cache_simulator::sweep::Geometry;
+use cache_simulator::trace::{read_trace, AddressRadix, TraceFormat};
+
+/// Every measurement of a small L1 + L2 run over `golden-trace.txt`, worked out by hand.
+///
+/// 16-byte blocks, a 64-byte 2-way L1 (set = bit 4 of the address) and a 256-byte 2-way L2
+/// (set = bits 4-6), both LRU, non-inclusive, and no flush at the end. An L1 victim is
+/// written back before the missing block is fetched, and every fetch and writeback to or
+/// from memory is one 16-byte transfer.
+///
+/// | #  | record | L1                         | L2                                              | traffic |
+/// |----|--------|----------------------------|-------------------------------------------------|---------|
+/// | 1  | r 0    | read miss                  | read miss                                       | 1       |
+/// | 2  | w 4    | write hit, 0 dirty         |                                                 | 1       |
+/// | 3  | r 20   | read miss                  | read miss                                       | 2       |
+/// | 4  | r 40   | read miss, evict dirty 0   | write hit on 0, read miss                       | 3       |
+/// | 5  | r 0    | read miss, evict clean 20  | read hit                                        | 3       |
+/// | 6  | w 10   | write miss                 | read miss                                       | 4       |
+/// | 7  | r 100  | read miss, evict clean 40  | read miss                                       | 5       |
+/// | 8  | r 200  | read miss, evict clean 0   | read miss, evict dirty 0 to memory              | 7       |
+/// | 9  | w 110  | write miss                 | read miss                                       | 8       |
+/// | 10 | w 210  | write miss, evict dirty 10 | write hit on 10, read miss, evict clean 110     | 9       |
+/// | 11 | r 10   | read miss, evict dirty 110 | write miss on 110, evict dirty 10 to memory and | 12      |
+/// |    |        |                            | fetch 110; read miss, evict clean 210           |         |
+/// | 12 | r 20   | read miss, evict clean 100 | read hit                                        | 12      |
+///
+/// Every L1 miss reads from L2, so L2 sees 11 reads, and each L1 writeback is an L2 write.
+fn expected() -> Statistics {
+    Statistics {
+        total_accesses: 12,
+        l1_reads: 8,
+        l1_read_misses: 8,
+        l1_writes: 4,
+        l1_write_misses: 3,
+        l1_write_backs: 3,
+        l1_evictions: 7,
+        l1_clean_evictions: 4,
+        l2_reads: 11,
+        l2_read_misses: 9,
+        l2_writes: 3,
+        l2_write_misses: 1,
+        l2_write_backs: 2,
+        l2_evictions: 4,
+        l2_clean_evictions: 2,
+        total_memory_traffic: 12,
+        memory_traffic_bytes: 12 * 16,
+        write_back_bytes: 2 * 16,
+        l2_enabled: true,
+        ..Statistics::new()
+    }
+}
+
+#[test]
+fn golden_trace_matches_every_counter() {
+    let geometry = Geometry { block_size: 16, l1_size: 64, l1_assoc: 2, l2_size: 256, l2_assoc: 2 };
+    let trace = read_trace(include_str!("golden-trace.txt").as_bytes(), TraceFormat::Legacy, AddressRadix::Hex).unwrap();
+    assert_eq!(simulate(&Config::new(geometry), trace.into_iter()), expected());
+}
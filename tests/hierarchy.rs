@@ -0,0 +1,223 @@
+use cache_simulator::access::AccessType::{Read, Write};
+use cache_simulator::cache::cache::Cache;
+use cache_simulator::cache::victim::VictimCache;
+use cache_simulator::simulator::{Inclusion, Simulator};
+use cache_simulator::tlb::Tlb;
+
+/// A single 2-way L1 set in front of a 4-set direct-mapped L2 (set = bits 4-5 of the
+/// address), both LRU with 16-byte blocks.
+///
+/// ## Arguments
+/// * `inclusion` - The inclusion policy between L1 and L2.
+fn small_hierarchy(inclusion: Inclusion) -> Simulator {
+    let (l1, l2) = (Cache::new(32, 2, 16).unwrap(), Cache::new(64, 1, 16).unwrap());
+    Simulator::new(l1, Some(l2), VictimCache::new(0), inclusion)
+}
+
+#[test]
+fn write_miss_hitting_in_l2_counts_one_write_and_installs_dirty() {
+    let mut simulator = small_hierarchy(Inclusion::Nine);
+
+    // 0x100 goes into both levels, then 0x110 and 0x120 push it out of l1 but not l2
+    for address in [0x100, 0x110, 0x120] {
+        simulator.access(Read, address);
+    }
+    let (index, tag) = (simulator.l1.index_of(0x100), simulator.l1.tag_of(0x100));
+    assert!(!simulator.l1.contains(index, tag));
+
+    simulator.access(Write, 0x104);
+    let stats = &simulator.stats;
+    assert_eq!((stats.l1_writes, stats.l1_write_misses), (1, 1));
+    assert_eq!((stats.l2_reads, stats.l2_read_misses, stats.l2_writes), (4, 3, 0));
+    assert_eq!(stats.total_memory_traffic, 3);
+    assert!(simulator.l1.set(index).iter().any(|block| block.valid && block.tag == tag && block.dirty));
+}
+
+#[test]
+fn inclusive_l2_eviction_back_invalidates_and_writes_back_a_dirty_l1_copy() {
+    // a 4-set 4-way l1, with more ways than the test fills, over a 4-set 2-way inclusive l2,
+    // both indexed by bits 4-5
+    let (l1, l2) = (Cache::new(256, 4, 16).unwrap(), Cache::new(128, 2, 16).unwrap());
+    let mut simulator = Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Inclusive);
+
+    // 0x100 is dirty in l1 and clean in l2, then 0x180 evicts it from l2 set 0 while l1
+    // still has free ways, so only the back-invalidation can take it out of l1
+    simulator.access(Write, 0x100);
+    simulator.access(Read, 0x140);
+    simulator.access(Read, 0x180);
+
+    let stats = &simulator.stats;
+    assert_eq!((stats.back_invalidations, stats.l1_write_backs), (1, 1));
+    assert_eq!(stats.l2_writes, 0);
+    // three fetches, and the dirty l1 copy written straight back to memory
+    assert_eq!(stats.total_memory_traffic, 4);
+    let mut l1_tags: Vec<usize> = simulator.l1.set(0).iter().filter(|block| block.valid).map(|block| block.tag).collect();
+    l1_tags.sort();
+    assert_eq!(l1_tags, [0x5, 0x6]);
+}
+
+/// A 1K 2-way L1 with 32-byte blocks and no L2.
+fn l1_only() -> Simulator {
+    Simulator::new(Cache::new(1024, 2, 32).unwrap(), None, VictimCache::new(0), Inclusion::Nine)
+}
+
+/// Read every third 32-byte block, never reused, through a 1K 2-way L1 with no L2.
+///
+/// ## Arguments
+/// * `stride_prefetch` - Whether to enable the stride prefetcher.
+fn read_every_third_block(stride_prefetch: bool) -> Simulator {
+    let mut simulator = l1_only();
+    if stride_prefetch {
+        simulator.enable_stride_prefetcher();
+    }
+    for i in 0..10 {
+        simulator.access(Read, i * 3 * 32);
+    }
+    simulator
+}
+
+#[test]
+fn stride_prefetcher_hits_once_the_stride_is_confirmed() {
+    let plain = read_every_third_block(false).stats;
+    assert_eq!((plain.l1_read_misses, plain.l1_prefetches), (10, 0));
+
+    // misses on blocks 0, 3, and 6 train the stride, and 9 confirms it a second time and
+    // prefetches 12. From then on every other read hits on a prefetched block: 12, 18, and
+    // 24 hit, while 15, 21, and 27 miss and prefetch 18, 24, and 30
+    let prefetched = read_every_third_block(true).stats;
+    assert_eq!((prefetched.l1_read_misses, prefetched.l1_prefetches, prefetched.useful_prefetches), (7, 4, 3));
+}
+
+#[test]
+fn prefetch_accuracy_and_coverage_count_useful_prefetches() {
+    let plain = read_every_third_block(false).stats;
+    assert_eq!(plain.prefetch_accuracy(), 0.0);
+    assert_eq!(plain.prefetch_coverage(), 0.0);
+
+    // 3 of the 4 prefetches hit, and block 30 is prefetched but never read
+    let prefetched = read_every_third_block(true).stats;
+    assert_eq!((prefetched.useful_prefetches, prefetched.unused_prefetches), (3, 0));
+    assert_eq!(prefetched.prefetch_accuracy(), 3.0 / 4.0);
+
+    // the prefetches took 3 of the 10 misses without them
+    assert_eq!(prefetched.prefetch_coverage(), 3.0 / 10.0);
+}
+
+#[test]
+fn warmup_zeroes_the_counters_but_keeps_the_cache_contents() {
+    // two passes over 16 blocks, which fit in l1
+    let mut cold = l1_only();
+    for i in 0..32 {
+        cold.access(Read, i % 16 * 32);
+    }
+    let cold = cold.stats;
+    assert_eq!((cold.l1_reads, cold.l1_read_misses, cold.total_memory_traffic), (32, 16, 16));
+
+    // with the first pass as warmup only the second pass is counted, and it all hits
+    let mut warm = l1_only();
+    for i in 0..32 {
+        if i == 16 {
+            warm.end_warmup();
+        }
+        warm.access(Read, i % 16 * 32);
+    }
+    let warm = warm.stats;
+    assert_eq!((warm.l1_reads, warm.l1_read_misses, warm.total_memory_traffic), (16, 0, 0));
+}
+
+#[test]
+fn l2_reads_count_demand_misses_and_not_writebacks() {
+    // a 2-set direct-mapped l1 (set = bit 4) over a 4-set direct-mapped l2 (set = bits 4-5)
+    let (l1, l2) = (Cache::new(32, 1, 16).unwrap(), Cache::new(64, 1, 16).unwrap());
+    let mut simulator = Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Nine);
+
+    // | record | L1                        | L2                         |
+    // |--------|---------------------------|----------------------------|
+    // | r 0    | read miss                 | read miss                  |
+    // | r 0    | read hit                  |                            |
+    // | w 10   | write miss                | read miss                  |
+    // | r 20   | read miss, evict clean 0  | read miss                  |
+    // | r 30   | read miss, evict dirty 10 | write hit on 10, read miss |
+    // | r 0    | read miss, evict clean 20 | read hit                   |
+    // | r 10   | read miss, evict clean 30 | read hit                   |
+    let trace = [(Read, 0x0), (Read, 0x0), (Write, 0x10), (Read, 0x20), (Read, 0x30), (Read, 0x0), (Read, 0x10)];
+    for (access_type, address) in trace {
+        simulator.access(access_type, address);
+    }
+
+    let stats = &simulator.stats;
+    assert_eq!((stats.l1_read_misses, stats.l1_write_misses, stats.l1_write_backs), (5, 1, 1));
+    assert_eq!((stats.l2_reads, stats.l2_read_misses), (6, 4));
+    assert_eq!((stats.l2_writes, stats.l2_write_misses), (1, 0));
+    assert_eq!(stats.total_memory_traffic, 4);
+}
+
+#[test]
+fn tlb_misses_once_per_page_that_fits() {
+    // a 2K stride over 50 pages, three times, through a 64-entry TLB
+    let mut simulator = l1_only();
+    simulator.enable_tlb(Tlb::new(64, 4, 4096).unwrap(), 30);
+    for i in 0..300 {
+        simulator.access(Read, i % 100 * 2048);
+    }
+    let stats = &simulator.stats;
+    assert_eq!((stats.tlb_hits, stats.tlb_misses), (250, 50));
+}
+
+#[test]
+fn exclusive_hierarchy_moves_blocks_between_levels_and_never_holds_both_copies() {
+    let mut simulator = small_hierarchy(Inclusion::Exclusive);
+    let l1_has = |simulator: &Simulator, address| simulator.l1.holds_address(address);
+    let l2_has = |simulator: &Simulator, address| simulator.l2.as_ref().unwrap().holds_address(address);
+
+    // 0x0 is fetched into l1 alone, then pushed out by 0x10 and 0x20 and moved to l2 dirty
+    simulator.access(Write, 0x0);
+    assert!(l1_has(&simulator, 0x0) && !l2_has(&simulator, 0x0));
+    simulator.access(Read, 0x10);
+    simulator.access(Read, 0x20);
+    assert!(!l1_has(&simulator, 0x0) && l2_has(&simulator, 0x0));
+    let stats = &simulator.stats;
+    assert_eq!((stats.l1_write_backs, stats.l2_writes, stats.l2_victim_fills), (1, 1, 1));
+
+    // reading it again moves it back up, still dirty, and the clean 0x10 moves down for it
+    simulator.access(Read, 0x0);
+    assert!(l1_has(&simulator, 0x0) && !l2_has(&simulator, 0x0) && l2_has(&simulator, 0x10));
+    assert!(simulator.l1.set(0).iter().any(|block| block.valid && block.tag == 0 && block.dirty));
+    assert_eq!((simulator.stats.l2_read_misses, simulator.stats.total_memory_traffic), (3, 3));
+
+    // and a longer mix of reads and writes never leaves a block in both levels
+    let mut state: usize = 7;
+    for _ in 0..1000 {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let address = (state >> 33) % 12 * 16;
+        simulator.access(if state >> 40 & 1 == 1 { Write } else { Read }, address);
+        for (_, _, block) in simulator.l1.iter_resident() {
+            assert!(!l2_has(&simulator, block.address));
+        }
+    }
+}
+
+#[test]
+fn l2_next_line_prefetch_turns_demand_misses_into_useful_prefetches() {
+    // a 1K 2-way l1 over an 8K 4-way l2, reading 4000 blocks in order
+    let read_in_order = |l2_prefetch_degree| {
+        let (l1, l2) = (Cache::new(1024, 2, 32).unwrap(), Cache::new(8192, 4, 32).unwrap());
+        let mut simulator = Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Nine);
+        if l2_prefetch_degree != 0 {
+            simulator.enable_l2_next_line_prefetch(l2_prefetch_degree);
+        }
+        for block in 0..4000 {
+            simulator.access(Read, block * 32);
+        }
+        simulator.stats
+    };
+    let plain = read_in_order(0);
+    assert_eq!((plain.l2_reads, plain.l2_read_misses, plain.l2_prefetches), (4000, 4000, 0));
+
+    // every demand miss brings in the next two blocks, which the following reads hit, and
+    // prefetches aren't l2 reads so the miss rate stays over demand reads only
+    let prefetched = read_in_order(2);
+    assert_eq!((prefetched.l2_reads, prefetched.l2_read_misses), (4000, 1334));
+    assert_eq!((prefetched.l2_prefetches, prefetched.l2_useful_prefetches), (2668, 2666));
+    assert_eq!(prefetched.total_memory_traffic, 4002);
+}
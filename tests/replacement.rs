@@ -0,0 +1,134 @@
+use cache_simulator::cache::cache::{Cache, DIP_PSEL_MAX};
+use cache_simulator::cache::cache::HitOrMiss::MISS;
+use cache_simulator::cache::replacement::ReplacementPolicy;
+
+/// Read a sequence of tags from a cache of a single 4-way set with 16-byte blocks.
+///
+/// ## Arguments
+/// * `replacement` - The replacement policy of the set.
+/// * `tags` - The tags to read, in order.
+///
+/// Returns the number of misses and the tags evicted, in order.
+fn run_set(replacement: ReplacementPolicy, tags: &[usize]) -> (usize, Vec<usize>) {
+    let mut cache = Cache::new(64, 4, 16).unwrap();
+    cache.set_replacement_policy(replacement).unwrap();
+    let (mut misses, mut evicted) = (0, vec![]);
+    for &tag in tags {
+        if cache.read(0, tag) == MISS {
+            misses += 1;
+            if cache.set_is_full(0, tag) {
+                let block = cache.evict_lru_block(0, tag);
+                evicted.push(cache.tag_of(block.evicted_block_address));
+            }
+            cache.install(0, tag, tag * 16);
+        }
+    }
+    (misses, evicted)
+}
+
+/// Read a block from a set and return the tag it evicted, if any.
+///
+/// ## Arguments
+/// * `cache` - The cache to read from, with 16-byte blocks.
+/// * `index` - The set to read from.
+/// * `tag` - The tag of the block to read.
+fn evicted_tag(cache: &mut Cache, index: usize, tag: usize) -> Option<usize> {
+    if cache.read(index, tag) != MISS {
+        return None;
+    }
+    let evicted = cache.set_is_full(index, tag).then(|| cache.evict_lru_block(index, tag));
+    cache.install(index, tag, (tag << cache.index_bits | index) * 16);
+    evicted.map(|block| cache.tag_of(block.evicted_block_address))
+}
+
+#[test]
+fn rrip_keeps_a_reused_block_through_a_scan() {
+    // A B C D fill the set, A is reused, and E F G H scan through it before A comes back
+    let tags = [0xa, 0xb, 0xc, 0xd, 0xa, 0xe, 0xf, 0x10, 0x11, 0xa];
+
+    // LRU lets the scan age A out
+    assert_eq!(run_set(ReplacementPolicy::Lru, &tags), (9, vec![0xb, 0xc, 0xd, 0xa, 0xe]));
+
+    // under RRIP the hit predicts A is reused soon, so the scanned blocks go first
+    assert_eq!(run_set(ReplacementPolicy::Rrip, &tags), (8, vec![0xb, 0xc, 0xd, 0xe]));
+}
+
+#[test]
+fn dip_followers_switch_to_bimodal_insertion_when_lru_sampler_misses() {
+    // 4 two-way sets: set 0 samples LRU insertion, set 2 samples bimodal insertion (BIP),
+    // and sets 1 and 3 follow the policy selector, which starts out on LRU
+    let mut lru = Cache::new(128, 2, 16).unwrap();
+    let mut dip = Cache::new(128, 2, 16).unwrap();
+    dip.set_replacement_policy(ReplacementPolicy::Dip).unwrap();
+    let fill = |cache: &mut Cache, index| [0xa, 0xb, 0xc].map(|tag| evicted_tag(cache, index, tag));
+
+    // LRU evicts the older A for C, and so does a follower while the selector favors LRU
+    assert_eq!(fill(&mut lru, 3), [None, None, Some(0xa)]);
+    assert_eq!(fill(&mut dip, 3), [None, None, Some(0xa)]);
+
+    // BIP inserts B at the LRU position, so B goes first and A stays
+    assert_eq!(fill(&mut dip, 2), [None, None, Some(0xb)]);
+    assert_eq!(dip.psel, DIP_PSEL_MAX / 2 - 3);
+
+    // misses in the LRU sampler count against LRU until the selector favors BIP
+    for tag in 1..=4 {
+        evicted_tag(&mut dip, 0, tag);
+    }
+    assert_eq!(dip.psel, DIP_PSEL_MAX / 2 + 1);
+    assert_eq!(fill(&mut dip, 1), [None, None, Some(0xb)]);
+    assert_eq!(fill(&mut lru, 1), [None, None, Some(0xa)]);
+}
+
+#[test]
+fn tree_plru_evicts_a_different_block_than_lru() {
+    // A B C D fill the set and A is reused before E needs room
+    let tags = [0xa, 0xb, 0xc, 0xd, 0xa, 0xe, 0xb, 0xc];
+
+    // LRU evicts B, the oldest, and then every block that comes back has just been evicted
+    assert_eq!(run_set(ReplacementPolicy::Lru, &tags), (7, vec![0xb, 0xc, 0xd]));
+
+    // the tree only remembers that A's half was used last and D last within the other half,
+    // so it evicts C for E and B still hits
+    assert_eq!(run_set(ReplacementPolicy::TreePlru, &tags), (6, vec![0xc, 0xd]));
+}
+
+#[test]
+fn random_replacement_repeats_its_victims_for_the_same_seed() {
+    // ten tags cycling through one 4-way set, evicting a random way on every miss once full
+    let victims = |seed| {
+        let mut cache = Cache::new(64, 4, 16).unwrap();
+        cache.set_replacement_policy(ReplacementPolicy::Random).unwrap();
+        cache.set_seed(seed);
+        (0..40).filter_map(|i| evicted_tag(&mut cache, 0, i % 10)).collect::<Vec<_>>()
+    };
+    assert_eq!(victims(7), victims(7));
+
+    // unlike LRU, which would evict every tag in the order it came in, a random victim
+    // sometimes leaves the next tag resident, and another seed picks other victims
+    assert!(victims(7).len() < 36);
+    assert!((0..4).any(|seed| victims(seed) != victims(7)));
+}
+
+#[test]
+fn lfu_keeps_a_hot_block_through_a_scan_until_aging_cools_it() {
+    // A is read four times, B C D fill the set, and 0xe to 0x15 scan through it before A
+    // comes back
+    let tags: Vec<usize> = [0xa; 4].into_iter().chain(0xb..=0x15).chain([0xa]).collect();
+    let run = |aging_interval| {
+        let mut cache = Cache::new(64, 4, 16).unwrap();
+        cache.set_replacement_policy(ReplacementPolicy::Lfu).unwrap();
+        cache.lfu_aging_interval = aging_interval;
+        tags.iter().filter_map(|&tag| evicted_tag(&mut cache, 0, tag)).collect::<Vec<_>>()
+    };
+
+    // LRU evicts A as soon as the scan starts, and misses on it at the end
+    let (misses, evicted) = run_set(ReplacementPolicy::Lru, &tags);
+    assert_eq!((misses, evicted[0]), (13, 0xa));
+
+    // LFU only evicts the scanned blocks, which have never been hit, so A is still there
+    assert_eq!(run(0), [0xb, 0xc, 0xd, 0xe, 0xf, 0x10, 0x11, 0x12]);
+
+    // halving every counter each 4 accesses brings A's count down to the scanned blocks' 0
+    // by the time 0x12 needs room, and then A is the least recently used block in the set
+    assert_eq!(run(4), [0xb, 0xc, 0xd, 0xe, 0xa, 0xf, 0x10, 0x11, 0x12]);
+}
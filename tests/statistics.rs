@@ -0,0 +1,61 @@
+use cache_simulator::access::AccessType;
+use cache_simulator::cache::cache::Cache;
+use cache_simulator::cache::victim::VictimCache;
+use cache_simulator::simulator::{Inclusion, Simulator};
+use cache_simulator::statistics::StatsStyle;
+
+/// Simulate a trace through an L1 and L2 with every optional report that prints a rate
+/// turned on, and print the measurements.
+///
+/// ## Arguments
+/// * `accesses` - The records of the trace.
+///
+/// Returns everything printed.
+fn print_every_rate(accesses: Vec<(AccessType, usize)>) -> String {
+    let (l1, l2) = (Cache::new(1024, 2, 32).unwrap(), Cache::new(4096, 4, 32).unwrap());
+    let mut simulator = Simulator::new(l1, Some(l2), VictimCache::new(0), Inclusion::Nine);
+    simulator.enable_miss_classification();
+    simulator.enable_stride_prefetcher();
+    for (access_type, address) in accesses {
+        simulator.access(access_type, address);
+    }
+    let mut out = vec![];
+    simulator.stats.print_stats(StatsStyle::Assignment, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn empty_trace_prints_zero_rates() {
+    let out = print_every_rate(vec![]);
+    assert!(out.contains("e. L1 miss rate:               0.0000\n"));
+    assert!(!out.contains("NaN") && !out.contains("inf"), "{}", out);
+}
+
+#[test]
+fn read_only_trace_prints_finite_rates() {
+    // two passes over 64 blocks, twice the size of l1
+    let reads = (0..128).map(|block| (AccessType::Read, block * 32 % 2048));
+    let out = print_every_rate(reads.collect());
+    assert!(out.contains("c. L1 writes:                  0\n"));
+    assert!(!out.contains("NaN") && !out.contains("inf"), "{}", out);
+}
+
+#[test]
+fn total_accesses_counts_instruction_fetches_but_not_prefetches() {
+    // reads of blocks 0, 3, 6, and 9 train the stride prefetcher to prefetch 12, which the
+    // next read hits on, then a write and two instruction fetches
+    let mut simulator = Simulator::new(Cache::new(1024, 2, 32).unwrap(), None, VictimCache::new(0), Inclusion::Nine);
+    simulator.enable_stride_prefetcher();
+    for block in 0..5 {
+        simulator.access(AccessType::Read, block * 3 * 32);
+    }
+    simulator.access(AccessType::Write, 0x1000);
+    simulator.access(AccessType::InstructionFetch, 0x2000);
+    simulator.access(AccessType::InstructionFetch, 0x2040);
+
+    // the fetches are accesses without being reads or writes, and the prefetch is neither
+    let stats = &simulator.stats;
+    assert_eq!((stats.l1_reads, stats.l1_writes, stats.l1_instruction_fetches), (5, 1, 2));
+    assert_eq!(stats.total_accesses, 8);
+    assert_eq!(stats.l1_prefetches, 1);
+}